@@ -0,0 +1,70 @@
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+
+/// Something a skin asks luna to do, written as a command line such as
+/// `set_variable Accent #ff0000`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Sets a variable of the skin and re-evaluates whatever uses it. The
+    /// value is the rest of the line, kept as a number when it is one.
+    SetVariable {
+        name: String,
+        value: toml::Value,
+    },
+}
+
+impl FromStr for Action {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Action> {
+        let s = s.trim();
+        let (command, args) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let args = args.trim();
+
+        match command {
+            "set_variable" => {
+                let (name, value) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                if name.is_empty() {
+                    return Err(Error::parse(s, s.len(), "set_variable needs a variable name"));
+                }
+                Ok(Action::SetVariable {
+                    name: name.to_string(),
+                    value: parse_value(value.trim()),
+                })
+            },
+            _ => Err(Error::parse(s, 0, format!("unknown action {:?}", command))),
+        }
+    }
+}
+
+// Numbers become integers or floats, anything else a string. `nan` and
+// `inf` stay strings, as NaN would never compare equal to itself and count
+// as a change every time it is set.
+fn parse_value(value: &str) -> toml::Value {
+    if let Ok(i) = value.parse() {
+        return toml::Value::Integer(i);
+    }
+    match value.parse::<f64>() {
+        Ok(f) if f.is_finite() => toml::Value::Float(f),
+        _ => toml::Value::String(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_actions() {
+        let set = |name: &str, value: toml::Value| Action::SetVariable { name: name.to_string(), value };
+
+        assert_eq!("set_variable Left 20".parse::<Action>().unwrap(), set("Left", toml::Value::Integer(20)));
+        assert_eq!(" set_variable  Scale 0.5 ".parse::<Action>().unwrap(), set("Scale", toml::Value::Float(0.5)));
+        assert_eq!("set_variable Scale nan".parse::<Action>().unwrap(), set("Scale", toml::Value::String("nan".to_string())));
+        assert_eq!("set_variable Scale -inf".parse::<Action>().unwrap(), set("Scale", toml::Value::String("-inf".to_string())));
+        assert_eq!("set_variable Title Hello world".parse::<Action>().unwrap(), set("Title", "Hello world".into()));
+        assert!("set_variable".parse::<Action>().is_err());
+        assert!("explode now".parse::<Action>().is_err());
+    }
+}
@@ -1,242 +1,624 @@
 use std::collections::HashMap;
-use std::sync::mpsc::{Sender, Receiver, channel, TryRecvError };
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
-
-use winapi::{
-    ctypes::c_int,
-    shared::{
-        minwindef::{DWORD, HINSTANCE, LPARAM, LRESULT, FALSE, UINT, WPARAM},
-        ntdef::LPCWSTR,
-        windef::{HBRUSH, HICON, HMENU, HWND, POINT, HDC, RECT, HCURSOR},
-    },
-    um::{
-        libloaderapi,
-        winuser::{
-            self, CW_USEDEFAULT, WM_DESTROY, WNDCLASSW, MSG, WM_CLOSE, SW_SHOW,
-            WS_OVERLAPPEDWINDOW, WS_EX_TOOLWINDOW, PAINTSTRUCT, COLOR_WINDOW, COLOR_GRAYTEXT, WM_PAINT, VK_ESCAPE, WM_CHAR, WM_LBUTTONUP, WM_RBUTTONUP,
-            WS_OVERLAPPED, WS_CHILDWINDOW, WS_POPUP, WS_SYSMENU, WS_CAPTION, WS_BORDER, WS_HSCROLL, WS_DISABLED,
-            WS_EX_TRANSPARENT, WS_EX_LAYERED, WS_EX_WINDOWEDGE, LWA_ALPHA,
-        },
-        errhandlingapi,
-    },
-};
-
-use log::{info, debug};
+use std::path::Path;
+use std::time::Instant;
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+use log::{info, debug, warn};
 
 use crate::geometry::Rectangle;
-use crate::tray_old::Application;
-use crate::util::to_os_string;
-use crate::event::{EVENT_HANDLER, Handler};
+use crate::event::{EVENT_HANDLER, Handler, Event, Key, MouseButton};
+use crate::platform::{Platform, WindowId, WindowOptions, ZPosition};
 use crate::window::Window;
+use crate::skin::{SkinDefinition, Position};
+use crate::library::{SkinLibrary, SkinRef};
+use crate::layout::{Layout, LayoutEntry, LayoutStore};
+use crate::action::Action;
 use crate::error::{Error, Result};
 
-pub struct App {
-    windows: HashMap<HWND, Window>,
+#[cfg(windows)]
+use crate::platform::Native;
+
+pub struct App<P: Platform> {
+    platform: P,
+
+    windows: HashMap<WindowId, Window>,
 
-    tray: Application,
+    receiver: Receiver<Event>,
 
-    receiver: Receiver<HWND>,
+    library: SkinLibrary,
 
-    running: Arc<AtomicBool>,
+    layouts: Option<LayoutStore>,
+
+    // Set while a whole layout is being applied so the state file is only
+    // written once at the end.
+    restoring: bool,
+
+    running: bool,
 
     #[allow(dead_code)]
     window_count: u32,
+}
 
-    #[allow(dead_code)]
-    instance: HINSTANCE,
-    
-    #[allow(dead_code)]
-    class: WNDCLASSW,
+#[cfg(windows)]
+impl App<Native> {
+    /// Creates the app with the native backend, the user's skin library and
+    /// the layout they had active last time.
+    pub fn new() -> Result<App<Native>> {
+        let mut app = App::with_platform(Native::new()?)?;
+        if let Some(root) = crate::library::default_root() {
+            app.scan_skins(root)?;
+        }
+        if let Some(store) = LayoutStore::default_store() {
+            app.set_layout_store(store)?;
+        }
+        Ok(app)
+    }
 }
 
-impl App {
-    pub fn new() -> Result<App> {
-        unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, w_param: WPARAM, l_param: LPARAM) -> LRESULT {
-            match msg {
-                WM_DESTROY => {
-                    EVENT_HANDLER.with(|handler| {
-                        if let Some(h) = handler.borrow().as_ref() {
-                            h.sender.send(hwnd).ok();
-                        }
-                    });
-                    return 0;
-                },
-                WM_PAINT => {
-                    let mut ps = PAINTSTRUCT {
-                        hdc: 0 as HDC,
-                        fErase: FALSE,
-                        rcPaint: RECT {
-                            left: 0,
-                            top: 0,
-                            right: 0,
-                            bottom: 0,
-                        },
-                        fRestore: FALSE,
-                        fIncUpdate: FALSE,
-                        rgbReserved: [0; 32],
-                    };
-                    let hdc = winuser::BeginPaint(hwnd, &mut ps);
-        
-                    winuser::FillRect(hdc, &ps.rcPaint, COLOR_GRAYTEXT as HBRUSH);
-        
-                    winuser::EndPaint(hwnd, &ps);
-                    return 0;
-                },
-                WM_CHAR => {
-                    if w_param as c_int == VK_ESCAPE {
-                        winuser::DestroyWindow(hwnd);
-                        return 0;
-                    }
-        
-                    return 0;
-                },
-                WM_LBUTTONUP => {
-                    info!("window clicked: {:?}", hwnd);
-                    return 0;
-                },
-                WM_RBUTTONUP => {
-                    winuser::DestroyWindow(hwnd);
-                    return 0;
-                },
-                _ => return winuser::DefWindowProcW(hwnd, msg, w_param, l_param),
-            };
+impl<P: Platform> App<P> {
+    pub fn with_platform(platform: P) -> Result<App<P>> {
+        let (handler, receiver) = Handler::new();
+
+        EVENT_HANDLER.with(|h| {
+            (*h.borrow_mut()) = Some(handler);
+        });
+
+        Ok(App {
+            platform,
+            windows: HashMap::new(),
+            receiver,
+            library: SkinLibrary::default(),
+            layouts: None,
+            restoring: false,
+            running: false,
+            window_count: 0,
+        })
+    }
+
+    pub fn platform(&self) -> &P {
+        &self.platform
+    }
+
+    pub fn platform_mut(&mut self) -> &mut P {
+        &mut self.platform
+    }
+
+    pub fn windows(&self) -> &HashMap<WindowId, Window> {
+        &self.windows
+    }
+
+    pub fn window_mut(&mut self, id: WindowId) -> Option<&mut Window> {
+        self.windows.get_mut(&id)
+    }
+
+    /// Loads the skin file at `path` and creates a window for it.
+    pub fn load_skin<T: AsRef<Path>>(&mut self, path: T) -> Result<WindowId> {
+        let skin = SkinDefinition::load(path)?;
+        self.add_skin(skin)
+    }
+
+    pub fn add_skin(&mut self, skin: SkinDefinition) -> Result<WindowId> {
+        let window = Window::from_skin(&mut self.platform, skin)?;
+        self.insert_window(window)
+    }
+
+    fn insert_window(&mut self, mut window: Window) -> Result<WindowId> {
+        debug!("skin {} loaded: {}", window.name, window.id);
+        window.present(&mut self.platform)?;
+
+        let id = window.id;
+        self.windows.insert(id, window);
+        if self.running {
+            self.platform.show_window(id)?;
         }
-        let class_name = to_os_string("Sample Window Class");
-
-        unsafe {
-            let hinstance: HINSTANCE = libloaderapi::GetModuleHandleW(std::ptr::null_mut());
-            let wnd = WNDCLASSW {
-                style: 0,
-                lpfnWndProc: Some(window_proc),
-                cbClsExtra: 0,
-                cbWndExtra: 0,
-                hInstance: hinstance,
-                hIcon: 0 as HICON,
-                hCursor: 0 as HCURSOR,
-                hbrBackground: 0 as HBRUSH,
-                lpszMenuName: 0 as LPCWSTR,
-                lpszClassName: class_name.as_ptr(),
+        Ok(id)
+    }
+
+    /// Rebuilds the skin library index from `root`.
+    pub fn scan_skins<T: AsRef<Path>>(&mut self, root: T) -> Result<&SkinLibrary> {
+        self.library = SkinLibrary::scan(root)?;
+        info!("{} skins found in {}", self.library.skins().len(), self.library.root().display());
+        Ok(&self.library)
+    }
+
+    pub fn library(&self) -> &SkinLibrary {
+        &self.library
+    }
+
+    /// Loads a skin from the library, the first variant if `variant` is `None`.
+    pub fn activate_skin(&mut self, skin: &str, variant: Option<&str>) -> Result<WindowId> {
+        let id = self.open_skin(skin, variant, None)?;
+        self.layout_changed();
+        Ok(id)
+    }
+
+    fn open_skin(&mut self, skin: &str, variant: Option<&str>, saved: Option<&LayoutEntry>) -> Result<WindowId> {
+        let (source, path) = {
+            let (source, variant) = self.library.resolve(skin, variant)?;
+            (source, variant.path.clone())
+        };
+
+        let mut skin = SkinDefinition::load(path)?;
+        let mut options = skin.window_options();
+        if let Some(saved) = saved {
+            skin.position = Position { x: saved.position[0], y: saved.position[1] };
+            options = WindowOptions {
+                alpha: saved.alpha,
+                z: saved.z,
+                locked: saved.locked,
+                ..options
             };
-            if winuser::RegisterClassW(&wnd) == 0 {
-                return Err(Error::from("Error creating window class".to_string()));
+        }
+
+        let mut window = Window::from_skin_with(&mut self.platform, skin, options)?;
+        window.source = Some(source);
+        self.insert_window(window)
+    }
+
+    /// Library skins that currently have a window.
+    pub fn active_skins(&self) -> Vec<(WindowId, &SkinRef)> {
+        let mut active: Vec<_> = self.windows.values()
+            .filter_map(|w| w.source.as_ref().map(|s| (w.id, s)))
+            .collect();
+        active.sort();
+        active
+    }
+
+    /// Destroys a window, removing it once the platform confirms.
+    pub fn close_window(&mut self, id: WindowId) -> Result<()> {
+        self.platform.destroy_window(id)?;
+        self.process_events()
+    }
+
+    pub fn move_window(&mut self, id: WindowId, x: i32, y: i32) -> Result<()> {
+        self.reconfigure(id, |bounds, _| *bounds = Rectangle::new(x, y, bounds.w(), bounds.h()))
+    }
+
+    pub fn set_alpha(&mut self, id: WindowId, alpha: u8) -> Result<()> {
+        self.reconfigure(id, |_, options| options.alpha = alpha)
+    }
+
+    pub fn set_z_position(&mut self, id: WindowId, z: ZPosition) -> Result<()> {
+        self.reconfigure(id, |_, options| options.z = z)
+    }
+
+    pub fn set_locked(&mut self, id: WindowId, locked: bool) -> Result<()> {
+        self.reconfigure(id, |_, options| options.locked = locked)
+    }
+
+    /// Runs `action` on behalf of the skin in window `id`.
+    pub fn execute(&mut self, id: WindowId, action: &Action) -> Result<()> {
+        let window = self.windows.get_mut(&id).ok_or(Error::NoWindowFound((id, 0)))?;
+        match *action {
+            Action::SetVariable { ref name, ref value } => {
+                let before = window.bounds;
+                let meters = window.set_variable(name, value.clone())?;
+                debug!("{}: {} set, re-evaluated meters {:?}", id, name, meters);
+                if window.bounds != before {
+                    self.platform.configure_window(id, window.bounds, &window.options)?;
+                }
+                window.present(&mut self.platform)?;
+            },
+        }
+        Ok(())
+    }
+
+    fn reconfigure<F>(&mut self, id: WindowId, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Rectangle<i32>, &mut WindowOptions),
+    {
+        let window = self.windows.get_mut(&id).ok_or(Error::NoWindowFound((id, 0)))?;
+        f(&mut window.bounds, &mut window.options);
+        self.platform.configure_window(id, window.bounds, &window.options)?;
+        self.layout_changed();
+        Ok(())
+    }
+
+    /// Uses `store` for layouts and restores its saved state.
+    pub fn set_layout_store(&mut self, store: LayoutStore) -> Result<()> {
+        let state = store.load_state()?;
+        self.layouts = Some(store);
+        self.apply_layout(&state)
+    }
+
+    /// Snapshot of the active library skins.
+    pub fn layout(&self) -> Layout {
+        let mut skins: Vec<_> = self.windows.values()
+            .filter_map(|w| w.source.as_ref().map(|s| (s, w)))
+            .map(|(s, w)| LayoutEntry {
+                skin: s.skin.clone(),
+                variant: s.variant.clone(),
+                position: [w.bounds.x(), w.bounds.y()],
+                alpha: w.options.alpha,
+                z: w.options.z,
+                locked: w.options.locked,
+            })
+            .collect();
+        skins.sort_by(|a, b| (&a.skin, &a.variant).cmp(&(&b.skin, &b.variant)));
+        Layout { skins }
+    }
+
+    /// Replaces every active library skin with the ones in `layout`. Skins
+    /// that are no longer in the library are skipped.
+    pub fn apply_layout(&mut self, layout: &Layout) -> Result<()> {
+        self.restoring = true;
+        let result = self.replace_skins(layout);
+        self.restoring = false;
+
+        self.layout_changed();
+        result
+    }
+
+    fn replace_skins(&mut self, layout: &Layout) -> Result<()> {
+        let active: Vec<_> = self.active_skins().iter().map(|(id, _)| *id).collect();
+        for id in active {
+            self.close_window(id)?;
+        }
+
+        for entry in &layout.skins {
+            if let Err(e) = self.open_skin(&entry.skin, Some(&entry.variant), Some(entry)) {
+                warn!("skipping {} from layout: {}", entry.source(), e);
             }
+        }
+        Ok(())
+    }
 
-            let (handler, receiver) = Handler::new();
-
-            EVENT_HANDLER.with(|h| {
-                (*h.borrow_mut()) = Some(handler);
-            });
-            
-            let mut windows = HashMap::new();
-            {
-                let window = Window::new(wnd, "test window", Rectangle::new(50, 50, 500, 500), WS_EX_TOOLWINDOW | WS_EX_LAYERED, WS_POPUP, None, None)?;
-                winuser::SetLayeredWindowAttributes(window.hwnd, 0, 100, LWA_ALPHA);
-                debug!("window 1: {:?}", window.hwnd);
-    
-                windows.insert(window.hwnd, window);
-    
-                let window2 = Window::new(wnd, "test window 2", Rectangle::new(600, 50, 500, 500), WS_EX_TOOLWINDOW | WS_EX_LAYERED, WS_POPUP, None, None)?;
-                winuser::SetLayeredWindowAttributes(window2.hwnd, 0, 100, LWA_ALPHA);
-                debug!("window 2: {:?}", window2.hwnd);
-    
-                windows.insert(window2.hwnd, window2);
-    
-                debug!("{}", windows.len());
+    pub fn layout_names(&self) -> Result<Vec<String>> {
+        self.layout_store()?.names()
+    }
+
+    pub fn save_layout_as(&self, name: &str) -> Result<()> {
+        self.layout_store()?.save_named(name, &self.layout())
+    }
+
+    pub fn switch_layout(&mut self, name: &str) -> Result<()> {
+        let layout = self.layout_store()?.load_named(name)?;
+        info!("switching to layout {}", name);
+        self.apply_layout(&layout)
+    }
+
+    fn layout_store(&self) -> Result<&LayoutStore> {
+        self.layouts.as_ref().ok_or_else(|| Error::from("no layout store configured".to_string()))
+    }
+
+    fn layout_changed(&mut self) {
+        if self.restoring {
+            return;
+        }
+        if let Some(store) = &self.layouts {
+            if let Err(e) = store.save_state(&self.layout()) {
+                warn!("unable to save layout: {}", e);
             }
-            let mut tray = Application::new().unwrap();
-
-            tray.set_icon("./src/rust.ico").unwrap();
-        
-            let running = Arc::new(AtomicBool::new(false));
-        
-            tray.add_menu_item("filler", |_| {
-                println!("filler button");
-                Ok::<_, Error>(())
-            }).unwrap();
-        
-            tray.add_menu_separator().unwrap();
-        
-            let running_clone = running.clone();
-            tray.add_menu_item("Quit", move |window| {
-                println!("Quitting app");
-                window.quit();
-                running_clone.store(true, Ordering::SeqCst);
-                Ok::<_, Error>(())
-            }).unwrap();
-        
-            tray.set_tooltip("luna").unwrap();
-
-            Ok(App {
-                windows,
-                tray,
-                receiver,
-                running,
-                window_count: 0,
-                instance: hinstance,
-                class: wnd,
-            })
         }
     }
 
     pub fn run(&mut self) -> Result<()> {
-        unsafe {
-            for (_, window) in self.windows.iter() {
-                winuser::ShowWindow(window.hwnd, SW_SHOW);
+        self.running = true;
+        let result = self.run_loop();
+        self.running = false;
+        result
+    }
+
+    fn run_loop(&mut self) -> Result<()> {
+        for id in self.windows.keys() {
+            self.platform.show_window(*id)?;
+        }
+
+        loop {
+            let timeout = self.next_update().map(|t| t.saturating_duration_since(Instant::now()));
+            if !self.platform.pump(timeout)? {
+                break;
             }
 
-            let mut msg = MSG {
-                hwnd: 0 as HWND,
-                message: 0 as UINT,
-                wParam: 0 as WPARAM,
-                lParam: 0 as LPARAM,
-                time: 0 as DWORD,
-                pt: POINT { x: 0, y: 0 },
-            };
+            self.process_events()?;
+            self.update(Instant::now());
 
-            loop {
-                self.tray.update().unwrap();
-                match self.running.load(Ordering::SeqCst) {
-                    true => {
-                        debug!("closing luna from tray");
-                        winuser::PostQuitMessage(0);
-                    },
-                    false => (),
-                }
+            if self.windows.is_empty() {
+                debug!("no windows, closing program");
+                self.platform.quit();
+                break;
+            }
+        }
 
-                match winuser::GetMessageW(&mut msg, 0 as HWND, 0, 0) {
-                    0 => {
-                        debug!("closing window");
-                        break;
-                    },
-                    -1 => {
-                        return Err(Error::from(format!("error on GetMessage: {}", errhandlingapi::GetLastError())));
-                    },
-                    _ => (),
-                }
+        Ok(())
+    }
 
-                match self.receiver.try_recv() {
-                    Ok(hwnd) => {
-                        match self.windows.remove(&hwnd) {
-                            Some(w) => debug!("{:?} removed", w.hwnd),
-                            None => panic!("remove of same window twice"),
-                        }
-                    },
-                    Err(TryRecvError::Empty) => (),
-                    Err(e) => panic!(e),
-                }
+    /// Earliest time any window's measures are due.
+    pub fn next_update(&self) -> Option<Instant> {
+        self.windows.values().filter_map(|w| w.measures.deadline()).min()
+    }
 
-                if self.windows.len() == 0 {
-                    debug!("no windows, closing program");
-                    self.tray.quit();
-                    winuser::PostQuitMessage(0);
-                    break;
+    /// Updates the measures of every window that is due at `now`, resizing
+    /// windows whose meters outgrew them and presenting what changed.
+    pub fn update(&mut self, now: Instant) {
+        for window in self.windows.values_mut() {
+            let before = window.bounds;
+            if !window.tick(now) {
+                continue;
+            }
+            if window.bounds != before {
+                if let Err(e) = self.platform.configure_window(window.id, window.bounds, &window.options) {
+                    warn!("unable to resize {}: {}", window.id, e);
                 }
+            }
+            if let Err(e) = window.present(&mut self.platform) {
+                warn!("unable to present {}: {}", window.id, e);
+            }
+        }
+    }
 
-                winuser::TranslateMessage(&mut msg);
-                winuser::DispatchMessageW(&mut msg);
-            }   
+    fn process_events(&mut self) -> Result<()> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => self.handle_event(event)?,
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(e) => return Err(Error::from(format!("event channel closed: {}", e))),
+            }
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) -> Result<()> {
+        match event {
+            Event::Destroyed(id) => {
+                match self.windows.remove(&id) {
+                    Some(w) => debug!("{} removed", w.id),
+                    None => panic!("remove of same window twice"),
+                }
+                self.layout_changed();
+            },
+            Event::Moved { window, x, y } => {
+                if let Some(w) = self.windows.get_mut(&window) {
+                    w.bounds = Rectangle::new(x, y, w.bounds.w(), w.bounds.h());
+                    self.layout_changed();
+                }
+            },
+            Event::Key { window, key: Key::Escape } => {
+                self.platform.destroy_window(window)?;
+            },
+            Event::Click { window, button: MouseButton::Left, .. } => {
+                info!("window clicked: {}", window);
+            },
+            Event::Click { window, button: MouseButton::Right, .. } => {
+                self.platform.destroy_window(window)?;
+            },
+            _ => (),
         }
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::headless::Headless;
+    use crate::measure::tests::Counter;
+
+    fn app() -> App<Headless> {
+        let mut app = App::with_platform(Headless::new()).unwrap();
+        for name in &["one", "two"] {
+            let skin = format!("name = \"{}\"\nsize = [500, 500]\nopacity = 100\n", name);
+            app.add_skin(skin.parse().unwrap()).unwrap();
+        }
+        app
+    }
+
+    fn ids(app: &App<Headless>) -> Vec<WindowId> {
+        app.platform().z_order()
+    }
+
+    #[test]
+    fn add_skin_creates_window() {
+        let app = app();
+        let ids = ids(&app);
+
+        assert_eq!(app.windows().len(), 2);
+        assert_eq!(app.windows()[&ids[1]].name, "two");
+        assert_eq!(app.platform().window(ids[0]).unwrap().options.alpha, 100);
+    }
+
+    #[test]
+    fn load_skin_missing_file() {
+        let mut app = app();
+        let err = app.load_skin("does/not/exist.toml").err().unwrap();
+        assert!(matches!(err, Error::Io(_)));
+    }
+
+    #[test]
+    fn activate_skin_from_library() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("Clock")).unwrap();
+        std::fs::write(dir.path().join("Clock/small.toml"), "name = \"Clock\"\nsize = [10, 10]\n").unwrap();
+
+        let mut app = App::with_platform(Headless::new()).unwrap();
+        assert_eq!(app.scan_skins(dir.path()).unwrap().skins().len(), 1);
+
+        let id = app.activate_skin("Clock", None).unwrap();
+        assert_eq!(app.active_skins(), vec![(id, &SkinRef::new("Clock", "small"))]);
+        assert!(app.activate_skin("Clock", Some("large")).is_err());
+    }
+
+    fn library() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for skin in &["Clock", "Cpu"] {
+            std::fs::create_dir_all(dir.path().join("skins").join(skin)).unwrap();
+            let contents = format!("name = \"{}\"\nposition = [5, 5]\nsize = [10, 10]\n", skin);
+            std::fs::write(dir.path().join("skins").join(skin).join("main.toml"), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn layout_is_saved_and_restored() {
+        let dir = library();
+        let store = LayoutStore::new(dir.path());
+
+        let mut app = App::with_platform(Headless::new()).unwrap();
+        app.scan_skins(dir.path().join("skins")).unwrap();
+        app.set_layout_store(store.clone()).unwrap();
+        assert!(app.windows().is_empty());
+
+        let clock = app.activate_skin("Clock", None).unwrap();
+        let cpu = app.activate_skin("Cpu", None).unwrap();
+        app.set_alpha(cpu, 50).unwrap();
+        app.set_z_position(cpu, ZPosition::Bottom).unwrap();
+        app.set_locked(cpu, true).unwrap();
+        app.platform_mut().drag(clock, 100, 200);
+        app.platform_mut().drag(cpu, 300, 300);
+        app.run().unwrap();
+
+        let state = store.load_state().unwrap();
+        assert_eq!(state, app.layout());
+        assert_eq!(state.skins[0].position, [100, 200]);
+        assert_eq!(state.skins[1].position, [5, 5]);
+
+        let mut app = App::with_platform(Headless::new()).unwrap();
+        app.scan_skins(dir.path().join("skins")).unwrap();
+        app.set_layout_store(store).unwrap();
+
+        assert_eq!(app.layout(), state);
+        let cpu = app.active_skins().iter().find(|(_, s)| s.skin == "Cpu").unwrap().0;
+        let restored = app.platform().window(cpu).unwrap();
+        assert_eq!(restored.options, WindowOptions { alpha: 50, click_through: false, z: ZPosition::Bottom, locked: true });
+    }
+
+    #[test]
+    fn switch_named_layouts() {
+        let dir = library();
+        let mut app = App::with_platform(Headless::new()).unwrap();
+        app.scan_skins(dir.path().join("skins")).unwrap();
+        app.set_layout_store(LayoutStore::new(dir.path())).unwrap();
+
+        app.activate_skin("Clock", None).unwrap();
+        app.save_layout_as("one").unwrap();
+        app.activate_skin("Cpu", None).unwrap();
+        app.save_layout_as("two").unwrap();
+
+        app.switch_layout("one").unwrap();
+        assert_eq!(app.active_skins().len(), 1);
+        assert_eq!(app.platform().windows().len(), 1);
+
+        app.switch_layout("two").unwrap();
+        assert_eq!(app.active_skins().len(), 2);
+        assert_eq!(app.layout_names().unwrap(), vec!["one", "two"]);
+        assert!(app.switch_layout("three").is_err());
+    }
+
+    #[test]
+    fn run_updates_measures_between_events() {
+        let mut app = App::with_platform(Headless::new()).unwrap();
+        let id = app.add_skin("name = \"m\"\nsize = [1, 1]\nupdate = 5\n".parse().unwrap()).unwrap();
+        app.window_mut(id).unwrap().measures.insert("count", Box::new(Counter(0.0)));
+        assert!(app.next_update().unwrap() <= Instant::now());
+
+        app.platform_mut().idle(3);
+        app.run().unwrap();
+
+        assert!(app.windows()[&id].measures.value("count").unwrap().number >= 3.0);
+    }
+
+    #[test]
+    fn set_variable_rebuilds_measures() {
+        let mut app = App::with_platform(Headless::new()).unwrap();
+        let skin = "name = \"v\"\nsize = [1, 1]\n[variables]\nFactor = 2\n[[measure]]\nname = \"c\"\ntype = \"calc\"\nformula = \"#Factor# * 10\"\n";
+        let id = app.add_skin(skin.parse().unwrap()).unwrap();
+
+        app.update(Instant::now());
+        assert_eq!(app.windows()[&id].measures.value("c").unwrap().number, 20.0);
+
+        app.execute(id, &"set_variable Factor 3".parse().unwrap()).unwrap();
+        app.window_mut(id).unwrap().measures.update();
+        assert_eq!(app.windows()[&id].measures.value("c").unwrap().number, 30.0);
+
+        assert!(app.execute(id, &"set_variable Factor (".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn failed_set_variable_leaves_window_unchanged() {
+        let mut app = App::with_platform(Headless::new()).unwrap();
+        let skin = r##"
+            name = "v"
+            [variables]
+            Factor = 2
+            Color = "#ff0000"
+            [[measure]]
+            name = "c"
+            type = "calc"
+            formula = "#Factor# * 10"
+            [[meter]]
+            name = "Bar"
+            type = "bar"
+            measure = "c"
+            w = "#Factor#"
+            h = 2
+            color = "#Color#"
+        "##;
+        let id = app.add_skin(skin.parse().unwrap()).unwrap();
+        app.update(Instant::now());
+        let skin = app.windows()[&id].skin.clone();
+        let bounds = app.windows()[&id].bounds;
+
+        // A measure depending on itself, a meter that fails to build, and a
+        // width that fails after the measure was built.
+        assert!(app.execute(id, &"set_variable Factor c".parse().unwrap()).is_err());
+        assert!(app.execute(id, &"set_variable Color nope".parse().unwrap()).is_err());
+        assert!(app.execute(id, &"set_variable Factor 2.5".parse().unwrap()).is_err());
+
+        let window = app.window_mut(id).unwrap();
+        assert_eq!(window.skin, skin);
+        assert_eq!(window.bounds, bounds);
+        window.measures.update();
+        assert_eq!(window.measures.value("c").unwrap().number, 20.0);
+    }
+
+    #[test]
+    fn update_resizes_fitted_windows() {
+        use crate::meter::{Common, tests::block};
+
+        let mut app = App::with_platform(Headless::new()).unwrap();
+        let id = app.add_skin("name = \"m\"\nposition = [4, 4]\nupdate = 5\n".parse().unwrap()).unwrap();
+        assert_eq!(app.platform().window(id).unwrap().bounds, Rectangle::new(4, 4, 1, 1));
+
+        let window = app.window_mut(id).unwrap();
+        window.measures.insert("count", Box::new(Counter(1.0)));
+        window.meters.insert("grows", Common { h: Some(3), ..Common::default() }, vec!["count".to_string()], block(0, 0));
+
+        app.update(Instant::now());
+        assert_eq!(app.platform().window(id).unwrap().bounds, Rectangle::new(4, 4, 2, 3));
+        assert_eq!(app.platform().framebuffer(id).unwrap(), &[255; 2 * 3 * 4][..]);
+    }
+
+    #[test]
+    fn closes_when_every_window_is_gone() {
+        let mut app = app();
+        for id in ids(&app) {
+            app.platform_mut().request_close(id);
+        }
+
+        app.run().unwrap();
+
+        assert!(app.windows().is_empty());
+        assert!(app.platform().is_quit());
+    }
+
+    #[test]
+    fn escape_and_right_click_destroy_windows() {
+        let mut app = app();
+        let ids = ids(&app);
+        app.platform_mut().key(ids[0], Key::Escape);
+        app.platform_mut().click(ids[1], MouseButton::Left, 10, 10);
+
+        app.run().unwrap();
+
+        assert_eq!(app.windows().len(), 1);
+        assert!(app.windows().contains_key(&ids[1]));
+        assert!(app.platform().window(ids[1]).unwrap().visible);
+        assert!(!app.platform().is_quit());
+
+        app.platform_mut().click(ids[1], MouseButton::Right, 10, 10);
+        app.run().unwrap();
+
+        assert!(app.windows().is_empty());
+        assert!(app.platform().is_quit());
+    }
+}
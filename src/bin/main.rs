@@ -1,14 +1,100 @@
 use std::env;
-use env_logger;
+use std::path::{Path, PathBuf};
 
+use log::error;
+
+use luna::library::{self, SkinLibrary};
+
+#[cfg(windows)]
 use luna::app::App;
 
+struct Args {
+    skins: Option<PathBuf>,
+    list: bool,
+    activate: Vec<String>,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        skins: None,
+        list: false,
+        activate: Vec::new(),
+    };
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--list" => args.list = true,
+            "--skins" => args.skins = iter.next().map(PathBuf::from),
+            _ => args.activate.push(arg),
+        }
+    }
+    args
+}
+
+fn list(library: &SkinLibrary) {
+    println!("skins in {}", library.root().display());
+    for skin in library.skins() {
+        let m = &skin.manifest;
+        println!("  {} ({}) {} by {}",
+            skin.name,
+            skin.display_name(),
+            m.version.as_deref().unwrap_or("-"),
+            m.author.as_deref().unwrap_or("unknown"),
+        );
+        if let Some(d) = &m.description {
+            println!("    {}", d);
+        }
+        for v in &skin.variants {
+            println!("    {}/{}", skin.name, v.name);
+        }
+    }
+}
+
 fn main () {
     env::set_var("RUST_LOG", "debug");
     env_logger::init();
 
-    App::new()
-    .unwrap()
-    .run()
-    .unwrap();
-}
\ No newline at end of file
+    let args = parse_args();
+    let root = args.skins.clone().or_else(library::default_root).unwrap_or_default();
+
+    if args.list {
+        match SkinLibrary::scan(&root) {
+            Ok(l) => list(&l),
+            Err(e) => error!("{}", e),
+        }
+        return;
+    }
+
+    run(args, &root);
+}
+
+#[cfg(windows)]
+fn run(args: Args, root: &Path) {
+    let mut app = App::new().unwrap();
+    if let Err(e) = app.scan_skins(root) {
+        error!("{}", e);
+    }
+
+    for arg in &args.activate {
+        let result = if Path::new(arg).is_file() {
+            app.load_skin(arg)
+        } else {
+            let mut parts = arg.splitn(2, '/');
+            let skin = parts.next().unwrap_or_default();
+            app.activate_skin(skin, parts.next())
+        };
+
+        if let Err(e) = result {
+            error!("{}", e);
+        }
+    }
+
+    app.run().unwrap();
+}
+
+#[cfg(not(windows))]
+fn run(_args: Args, _root: &Path) {
+    error!("luna has no native window backend for this platform yet");
+    std::process::exit(1);
+}
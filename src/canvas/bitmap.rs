@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use image::ImageFormat;
+
+use crate::canvas::Color;
+use crate::error::{Error, Result};
+
+/// How many decoded files are kept around for reuse.
+const CACHE_SIZE: usize = 64;
+
+/// Premultiplied RGBA8 pixels, like a `Canvas`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Bitmap {
+    /// A bitmap of straight, not premultiplied, RGBA8 pixels.
+    pub fn from_rgba(width: u32, height: u32, data: &[u8]) -> Bitmap {
+        assert_eq!(data.len(), width as usize * height as usize * 4, "pixel data does not match the size");
+        Bitmap {
+            width,
+            height,
+            data: data.chunks_exact(4)
+                .flat_map(|p| Color::rgba(p[0], p[1], p[2], p[3]).premultiplied())
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Premultiplied pixel at `x`, `y`, which have to lie within the bitmap.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [self.data[i], self.data[i + 1], self.data[i + 2], self.data[i + 3]]
+    }
+
+    /// Premultiplied color at `x`, `y` in pixels, interpolated between the
+    /// nearest pixels and fading out past the edges.
+    pub fn sample(&self, x: f32, y: f32) -> [f32; 4] {
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let mut sum = [0.0; 4];
+        for (dy, wy) in [(0, 1.0 - fy), (1, fy)] {
+            for (dx, wx) in [(0, 1.0 - fx), (1, fx)] {
+                let (px, py) = (x0 as i64 + dx, y0 as i64 + dy);
+                if px < 0 || py < 0 || px >= self.width as i64 || py >= self.height as i64 {
+                    continue;
+                }
+                for (s, c) in sum.iter_mut().zip(self.pixel(px as u32, py as u32)) {
+                    *s += c as f32 * wx * wy;
+                }
+            }
+        }
+        sum
+    }
+
+    /// The bitmap turned grey if `greyscale`, then multiplied by `tint`.
+    pub fn adjusted(&self, greyscale: bool, tint: Option<Color>) -> Bitmap {
+        let tint = tint.unwrap_or(Color::WHITE);
+        let scale = |c: u32, t: u8| (c * t as u32 + 127) / 255;
+        let mut data = self.data.clone();
+        for p in data.chunks_exact_mut(4) {
+            let [mut r, mut g, mut b, a] = [p[0] as u32, p[1] as u32, p[2] as u32, p[3] as u32];
+            if greyscale {
+                let grey = (r * 299 + g * 587 + b * 114 + 500) / 1000;
+                r = grey;
+                g = grey;
+                b = grey;
+            }
+            let [r, g, b] = [scale(r, tint.r), scale(g, tint.g), scale(b, tint.b)];
+            p.copy_from_slice(&[scale(r, tint.a) as u8, scale(g, tint.a) as u8, scale(b, tint.a) as u8, scale(a, tint.a) as u8]);
+        }
+        Bitmap { width: self.width, height: self.height, data }
+    }
+}
+
+/// Source pixels and their weights for one destination pixel along one axis.
+///
+/// `center` is where the destination pixel's middle falls in the source and
+/// `footprint` how many source pixels it spans. Enlarged images are
+/// interpolated between the two nearest pixels, shrunk ones averaged over
+/// the footprint.
+pub(crate) fn taps(center: f32, footprint: f32, len: u32) -> Vec<(u32, f32)> {
+    let clamp = |i: f32| (i.max(0.0) as u32).min(len.saturating_sub(1));
+    if footprint <= 1.0 {
+        let t = center - 0.5;
+        let i = t.floor();
+        let f = t - i;
+        return vec![(clamp(i), 1.0 - f), (clamp(i + 1.0), f)];
+    }
+
+    let (a, b) = (center - footprint / 2.0, center + footprint / 2.0);
+    let mut taps = Vec::with_capacity(footprint.ceil() as usize + 1);
+    let mut i = a.floor();
+    while i < b {
+        taps.push((clamp(i), (b.min(i + 1.0) - a.max(i)) / footprint));
+        i += 1.0;
+    }
+    taps
+}
+
+/// A decoded image file. Icons keep every resolution they hold.
+#[derive(Debug, Clone)]
+pub struct ImageFile {
+    // Smallest first.
+    bitmaps: Arc<Vec<Bitmap>>,
+}
+
+struct Cached {
+    modified: Option<SystemTime>,
+    used: u64,
+    image: ImageFile,
+}
+
+impl ImageFile {
+    /// Decodes the PNG, JPEG, BMP or ICO file at `path`. Files are decoded
+    /// once and shared until they change on disk.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ImageFile> {
+        static LOADED: OnceLock<Mutex<(u64, HashMap<PathBuf, Cached>)>> = OnceLock::new();
+
+        let path = path.as_ref();
+        let error = |e: &dyn std::fmt::Display| Error::from(format!("can not load image {}: {}", path.display(), e));
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+
+        let loaded = LOADED.get_or_init(Default::default);
+        {
+            let mut guard = loaded.lock().unwrap();
+            let (clock, cache) = &mut *guard;
+            *clock += 1;
+            if let Some(cached) = cache.get_mut(path).filter(|c| c.modified == modified) {
+                cached.used = *clock;
+                return Ok(cached.image.clone());
+            }
+        }
+
+        let data = fs::read(path).map_err(|e| error(&e))?;
+        let image = ImageFile::decode(&data).map_err(|e| error(&e))?;
+
+        let mut guard = loaded.lock().unwrap();
+        let (clock, cache) = &mut *guard;
+        if cache.len() >= CACHE_SIZE && !cache.contains_key(path) {
+            let oldest = cache.iter().min_by_key(|(_, c)| c.used).map(|(p, _)| p.clone());
+            if let Some(oldest) = oldest {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(path.to_path_buf(), Cached { modified, used: *clock, image: image.clone() });
+        Ok(image)
+    }
+
+    /// Decodes an image from the contents of a file.
+    pub fn decode(data: &[u8]) -> Result<ImageFile> {
+        let mut bitmaps = if data.starts_with(&[0, 0, 1, 0]) {
+            decode_icon(data)?
+        } else {
+            vec![decode_one(data, None)?]
+        };
+        bitmaps.sort_by_key(|b| b.width * b.height);
+        Ok(ImageFile { bitmaps: Arc::new(bitmaps) })
+    }
+
+    /// Size of the largest resolution.
+    pub fn size(&self) -> (u32, u32) {
+        let largest = self.largest();
+        (largest.width, largest.height)
+    }
+
+    pub fn largest(&self) -> &Bitmap {
+        self.bitmaps.last().expect("images hold at least one bitmap")
+    }
+
+    /// The smallest resolution at least `width` by `height`, the largest if
+    /// none is.
+    pub fn best(&self, width: u32, height: u32) -> &Bitmap {
+        self.bitmaps.iter()
+            .find(|b| b.width >= width && b.height >= height)
+            .unwrap_or_else(|| self.largest())
+    }
+
+    pub fn bitmaps(&self) -> &[Bitmap] {
+        &self.bitmaps
+    }
+
+    /// Every resolution adjusted as by `Bitmap::adjusted`.
+    pub fn adjusted(&self, greyscale: bool, tint: Option<Color>) -> ImageFile {
+        ImageFile {
+            bitmaps: Arc::new(self.bitmaps.iter().map(|b| b.adjusted(greyscale, tint)).collect()),
+        }
+    }
+}
+
+fn decode_one(data: &[u8], format: Option<ImageFormat>) -> Result<Bitmap> {
+    let decoded = match format {
+        Some(format) => image::load_from_memory_with_format(data, format),
+        None => image::load_from_memory(data),
+    };
+    let rgba = decoded.map_err(|e| Error::from(e.to_string()))?.to_rgba8();
+    Ok(Bitmap::from_rgba(rgba.width(), rgba.height(), rgba.as_raw()))
+}
+
+// Decodes every entry of an icon on its own, as an icon holding only that
+// entry, since decoders only hand out the largest.
+fn decode_icon(data: &[u8]) -> Result<Vec<Bitmap>> {
+    const HEADER: usize = 6;
+    const ENTRY: usize = 16;
+
+    let invalid = || Error::from("invalid icon directory".to_string());
+    if data.len() < HEADER {
+        return Err(invalid());
+    }
+    let count = u16::from_le_bytes([data[4], data[5]]) as usize;
+    if count == 0 || data.len() < HEADER + count * ENTRY {
+        return Err(invalid());
+    }
+
+    let mut bitmaps = Vec::with_capacity(count);
+    for entry in data[HEADER..HEADER + count * ENTRY].chunks_exact(ENTRY) {
+        let size = u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]) as usize;
+        let offset = u32::from_le_bytes([entry[12], entry[13], entry[14], entry[15]]) as usize;
+        let image = data.get(offset..offset.saturating_add(size)).ok_or_else(invalid)?;
+
+        let mut single = Vec::with_capacity(HEADER + ENTRY + image.len());
+        single.extend_from_slice(&[0, 0, 1, 0, 1, 0]);
+        single.extend_from_slice(&entry[..12]);
+        single.extend_from_slice(&((HEADER + ENTRY) as u32).to_le_bytes());
+        single.extend_from_slice(image);
+        bitmaps.push(decode_one(&single, Some(ImageFormat::Ico))?);
+    }
+    Ok(bitmaps)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// `pixels` encoded as `format`.
+    pub fn encode(width: u32, height: u32, pixels: &[u8], format: ImageFormat) -> Vec<u8> {
+        let image = image::RgbaImage::from_raw(width, height, pixels.to_vec()).unwrap();
+        let mut data = Cursor::new(Vec::new());
+        match format {
+            ImageFormat::Jpeg => image::DynamicImage::ImageRgba8(image).to_rgb8().write_to(&mut data, format).unwrap(),
+            _ => image.write_to(&mut data, format).unwrap(),
+        }
+        data.into_inner()
+    }
+
+    #[test]
+    fn decodes_formats() {
+        let pixels = [255, 0, 0, 255, 0, 0, 255, 128, 0, 255, 0, 255, 255, 255, 255, 0];
+        for format in [ImageFormat::Png, ImageFormat::Bmp, ImageFormat::Ico] {
+            let image = ImageFile::decode(&encode(2, 2, &pixels, format)).unwrap();
+            assert_eq!(image.size(), (2, 2), "{:?}", format);
+            assert_eq!(image.largest().pixel(1, 0), [0, 0, 128, 128], "{:?}", format);
+        }
+
+        let jpeg = ImageFile::decode(&encode(2, 2, &[200; 16], ImageFormat::Jpeg)).unwrap();
+        let [r, _, _, a] = jpeg.largest().pixel(0, 0);
+        assert!(r.abs_diff(200) < 8 && a == 255);
+
+        assert!(ImageFile::decode(b"not an image").is_err());
+        assert!(ImageFile::decode(&[0, 0, 1, 0, 1, 0]).is_err());
+    }
+
+    #[test]
+    fn keeps_every_icon_resolution() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/rust.ico");
+        let icon = ImageFile::load(&path).unwrap();
+        let sizes: Vec<_> = icon.bitmaps().iter().map(|b| b.width()).collect();
+        assert_eq!(sizes, vec![16, 24, 32, 48, 256]);
+        assert_eq!(icon.best(20, 20).width(), 24);
+        assert_eq!(icon.best(300, 10).width(), 256);
+        assert!(Arc::ptr_eq(&icon.bitmaps, &ImageFile::load(&path).unwrap().bitmaps));
+    }
+
+    #[test]
+    fn adjusts_and_samples() {
+        let bitmap = Bitmap::from_rgba(1, 1, &[255, 0, 0, 255]);
+        assert_eq!(bitmap.adjusted(true, None).pixel(0, 0), [76, 76, 76, 255]);
+        assert_eq!(bitmap.adjusted(false, Some(Color::rgba(255, 255, 255, 128))).pixel(0, 0), [128, 0, 0, 128]);
+        assert_eq!(bitmap.adjusted(false, Some(Color::rgb(128, 255, 255))).pixel(0, 0), [128, 0, 0, 255]);
+
+        let two = Bitmap::from_rgba(2, 1, &[255, 0, 0, 255, 0, 0, 255, 255]);
+        assert_eq!(two.sample(1.0, 0.5), [127.5, 0.0, 127.5, 255.0]);
+        assert_eq!(two.sample(0.0, 0.5), [127.5, 0.0, 0.0, 127.5]);
+
+        assert_eq!(taps(1.5, 1.0, 4), vec![(1, 1.0), (2, 0.0)]);
+        assert_eq!(taps(0.25, 0.5, 4), vec![(0, 0.25), (0, 0.75)]);
+        assert_eq!(taps(2.0, 2.0, 4), vec![(1, 0.5), (2, 0.5)]);
+        assert_eq!(taps(3.0, 3.0, 4), vec![(1, 0.5 / 3.0), (2, 1.0 / 3.0), (3, 1.0 / 3.0), (3, 0.5 / 3.0)]);
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use log::{debug, warn};
+use rustybuzz::ttf_parser::{self, name_id};
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+const EXTENSIONS: [&str; 4] = ["ttf", "otf", "ttc", "otc"];
+
+/// How heavy a font is, 100 for thin to 900 for black.
+///
+/// Skins write it as a number or as one of `thin`, `extralight`, `light`,
+/// `normal`, `medium`, `semibold`, `bold`, `extrabold` and `black`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "WeightValue")]
+pub struct Weight(pub u16);
+
+impl Weight {
+    pub const NORMAL: Weight = Weight(400);
+    pub const BOLD: Weight = Weight(700);
+}
+
+impl Default for Weight {
+    fn default() -> Weight {
+        Weight::NORMAL
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WeightValue {
+    Number(u16),
+    Name(String),
+}
+
+impl TryFrom<WeightValue> for Weight {
+    type Error = String;
+
+    fn try_from(v: WeightValue) -> std::result::Result<Weight, String> {
+        let weight = match v {
+            WeightValue::Number(n) if (1..=1000).contains(&n) => n,
+            WeightValue::Number(n) => return Err(format!("font weight must be between 1 and 1000, got {}", n)),
+            WeightValue::Name(name) => match name.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+                "thin" => 100,
+                "extralight" => 200,
+                "light" => 300,
+                "normal" | "regular" => 400,
+                "medium" => 500,
+                "semibold" => 600,
+                "bold" => 700,
+                "extrabold" => 800,
+                "black" => 900,
+                _ => return Err(format!("unknown font weight {:?}", name)),
+            },
+        };
+        Ok(Weight(weight))
+    }
+}
+
+/// What a font file says about one of its faces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaceInfo {
+    pub path: PathBuf,
+    /// Face within a collection, 0 for single font files.
+    pub index: u32,
+    pub family: String,
+    pub weight: Weight,
+    pub italic: bool,
+}
+
+/// A parsed font face. Clones share the font data.
+#[derive(Clone)]
+pub struct Font {
+    data: Arc<Vec<u8>>,
+    info: FaceInfo,
+}
+
+impl fmt::Debug for Font {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Font").field("info", &self.info).finish()
+    }
+}
+
+impl Font {
+    /// Reads face `index` of the font file at `path`. Files are read once and
+    /// shared from then on.
+    pub fn load<P: AsRef<Path>>(path: P, index: u32) -> Result<Font> {
+        static LOADED: OnceLock<Mutex<HashMap<(PathBuf, u32), Font>>> = OnceLock::new();
+
+        let path = path.as_ref();
+        let key = (path.to_path_buf(), index);
+        let loaded = LOADED.get_or_init(Default::default);
+        if let Some(font) = loaded.lock().unwrap().get(&key) {
+            return Ok(font.clone());
+        }
+
+        let data = fs::read(path).map_err(|e| Error::from(format!("can not read font {}: {}", path.display(), e)))?;
+        let font = Font::from_data(data, index, path)?;
+        loaded.lock().unwrap().insert(key, font.clone());
+        Ok(font)
+    }
+
+    /// Parses face `index` of font file contents read from `path`.
+    pub fn from_data(data: Vec<u8>, index: u32, path: &Path) -> Result<Font> {
+        let info = face_info(&data, index, path)?;
+        Ok(Font {
+            data: Arc::new(data),
+            info,
+        })
+    }
+
+    /// The installed face of `family` closest to `weight` and `italic`.
+    pub fn system(family: &str, weight: Weight, italic: bool) -> Result<Font> {
+        static FACES: OnceLock<Vec<FaceInfo>> = OnceLock::new();
+
+        let faces = FACES.get_or_init(|| {
+            let faces = scan(&system_dirs());
+            debug!("{} font faces installed", faces.len());
+            faces
+        });
+        match find(faces, family, weight, italic) {
+            Some(info) => Font::load(&info.path, info.index),
+            None => Err(Error::from(format!("font {:?} is not installed", family))),
+        }
+    }
+
+    pub fn info(&self) -> &FaceInfo {
+        &self.info
+    }
+
+    /// The face for shaping and reading glyphs.
+    pub fn face(&self) -> rustybuzz::Face<'_> {
+        // The data was parsed successfully when the font was created.
+        rustybuzz::Face::from_slice(&self.data, self.info.index).expect("font parsed before")
+    }
+}
+
+/// Whether `name` looks like a font file rather than a family.
+pub fn is_font_file(name: &str) -> bool {
+    Path::new(name).extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+fn face_info(data: &[u8], index: u32, path: &Path) -> Result<FaceInfo> {
+    let face = ttf_parser::Face::parse(data, index)
+        .map_err(|e| Error::from(format!("invalid font {}: {}", path.display(), e)))?;
+
+    let name = |id: u16| face.names().into_iter()
+        .filter(|n| n.name_id == id && n.is_unicode())
+        .find_map(|n| n.to_string());
+    let family = name(name_id::TYPOGRAPHIC_FAMILY)
+        .or_else(|| name(name_id::FAMILY))
+        .unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().into_owned());
+
+    Ok(FaceInfo {
+        path: path.to_path_buf(),
+        index,
+        family,
+        weight: Weight(face.weight().to_number()),
+        italic: face.is_italic(),
+    })
+}
+
+/// Where the platform keeps installed fonts.
+pub fn system_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(windows) {
+        if let Some(windir) = env::var_os("WINDIR") {
+            dirs.push(Path::new(&windir).join("Fonts"));
+        }
+        if let Some(local) = env::var_os("LOCALAPPDATA") {
+            dirs.push(Path::new(&local).join("Microsoft/Windows/Fonts"));
+        }
+    } else if cfg!(target_os = "macos") {
+        dirs.extend(["/System/Library/Fonts", "/Library/Fonts"].iter().map(PathBuf::from));
+        if let Some(home) = env::var_os("HOME") {
+            dirs.push(Path::new(&home).join("Library/Fonts"));
+        }
+    } else {
+        dirs.extend(["/usr/share/fonts", "/usr/local/share/fonts"].iter().map(PathBuf::from));
+        if let Some(data) = env::var_os("XDG_DATA_HOME") {
+            dirs.push(Path::new(&data).join("fonts"));
+        }
+        if let Some(home) = env::var_os("HOME") {
+            dirs.push(Path::new(&home).join(".local/share/fonts"));
+            dirs.push(Path::new(&home).join(".fonts"));
+        }
+    }
+    dirs
+}
+
+/// Every face of the font files in `dirs` and their subdirectories.
+/// Unreadable files are skipped, and directories reached more than once,
+/// like through symlinks, are only scanned the first time.
+pub fn scan(dirs: &[PathBuf]) -> Vec<FaceInfo> {
+    let mut faces = Vec::new();
+    let mut pending = dirs.to_vec();
+    let mut visited = HashSet::new();
+
+    while let Some(dir) = pending.pop() {
+        if !fs::canonicalize(&dir).is_ok_and(|real| visited.insert(real)) {
+            continue;
+        }
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for path in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+            if path.is_dir() {
+                pending.push(path);
+            } else if is_font_file(&path.to_string_lossy()) {
+                match fs::read(&path) {
+                    Ok(data) => {
+                        let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+                        faces.extend((0..count).filter_map(|i| face_info(&data, i, &path).ok()));
+                    },
+                    Err(e) => warn!("skipping font {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+
+    faces.sort_by(|a, b| (&a.path, a.index).cmp(&(&b.path, b.index)));
+    faces
+}
+
+/// The face of `family`, ignoring case, that matches `italic` if possible
+/// and is closest to `weight`, the heavier one on ties.
+pub fn find<'a>(faces: &'a [FaceInfo], family: &str, weight: Weight, italic: bool) -> Option<&'a FaceInfo> {
+    faces.iter()
+        .filter(|f| f.family.eq_ignore_ascii_case(family))
+        .min_by_key(|f| (f.italic != italic, (f.weight.0 as i32 - weight.0 as i32).abs(), std::cmp::Reverse(f.weight)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/fonts")
+    }
+
+    #[test]
+    fn finds_faces_by_family() {
+        let faces = scan(&[fixtures()]);
+        assert_eq!(faces.len(), 2);
+        assert!(faces.iter().all(|f| f.family == "DejaVu Sans" && !f.italic));
+
+        let bold = find(&faces, "dejavu sans", Weight(600), true).unwrap();
+        assert_eq!((bold.weight, bold.path.file_name().unwrap().to_str()), (Weight::BOLD, Some("DejaVuSans-Bold.ttf")));
+        assert_eq!(find(&faces, "DejaVu Sans", Weight(500), false).unwrap().weight, Weight::NORMAL);
+        assert!(find(&faces, "Comic Sans", Weight::NORMAL, false).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scans_linked_dirs_once() {
+        let dir = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(fixtures(), dir.path().join("fonts")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("loop")).unwrap();
+
+        let faces = scan(&[dir.path().to_path_buf(), fixtures()]);
+        assert_eq!(faces.len(), 2);
+    }
+
+    #[test]
+    fn loads_and_shares_fonts() {
+        let path = fixtures().join("DejaVuSans.ttf");
+        let font = Font::load(&path, 0).unwrap();
+        let again = Font::load(&path, 0).unwrap();
+        assert!(Arc::ptr_eq(&font.data, &again.data));
+        assert!(font.face().glyph_index('A').is_some());
+
+        assert!(Font::load(fixtures().join("missing.ttf"), 0).is_err());
+        assert!(Font::from_data(b"not a font".to_vec(), 0, Path::new("x.ttf")).is_err());
+        assert!(is_font_file("fonts/My Font.TTF") && !is_font_file("Segoe UI"));
+    }
+
+    #[test]
+    fn parses_weights() {
+        #[derive(Deserialize)]
+        struct W {
+            weight: Weight,
+        }
+        let weight = |s: &str| toml::from_str::<W>(&format!("weight = {}", s)).map(|w| w.weight);
+        assert_eq!(weight("\"Semi-Bold\"").unwrap(), Weight(600));
+        assert_eq!(weight("300").unwrap(), Weight(300));
+        assert!(weight("\"heavy-ish\"").is_err());
+        assert!(weight("0").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use std::f32::consts::TAU;
+
+use crate::canvas::Color;
+use crate::canvas::path::Point;
+use crate::canvas::transform::Transform;
+
+/// Where a gradient runs.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Kind {
+    /// Along the line from `from` to `to`, the same all across it.
+    Linear { from: Point, to: Point },
+    /// Out from `center`, reaching its end at `radius`.
+    Radial { center: Point, radius: f32 },
+    /// Around `center`, from angle `start` over `sweep` radians, clockwise
+    /// on screen when positive.
+    Conic { center: Point, start: f32, sweep: f32 },
+}
+
+/// Colors blended between stops at offsets from 0 at the start of the
+/// gradient to 1 at its end. Past the ends the nearest stop's color holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    kind: Kind,
+    stops: Vec<(f32, Color)>,
+    // From canvas points to the ones `kind` is laid out in.
+    to_local: Transform,
+}
+
+impl Gradient {
+    /// A gradient through `stops`, sorted by their offsets.
+    pub fn new(kind: Kind, mut stops: Vec<(f32, Color)>) -> Gradient {
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Gradient { kind, stops, to_local: Transform::IDENTITY }
+    }
+
+    /// A gradient through `colors`, spread evenly.
+    pub fn even(kind: Kind, colors: &[Color]) -> Gradient {
+        let last = colors.len().saturating_sub(1).max(1) as f32;
+        Gradient::new(kind, colors.iter().enumerate().map(|(i, &c)| (i as f32 / last, c)).collect())
+    }
+
+    pub fn linear(from: Point, to: Point, colors: &[Color]) -> Gradient {
+        Gradient::even(Kind::Linear { from, to }, colors)
+    }
+
+    pub fn radial(center: Point, radius: f32, colors: &[Color]) -> Gradient {
+        Gradient::even(Kind::Radial { center, radius }, colors)
+    }
+
+    pub fn conic(center: Point, start: f32, sweep: f32, colors: &[Color]) -> Gradient {
+        Gradient::even(Kind::Conic { center, start, sweep }, colors)
+    }
+
+    /// The gradient with its points moved by `transform`. One that
+    /// flattens the plane leaves it as it is.
+    pub fn transformed(self, transform: &Transform) -> Gradient {
+        match transform.invert() {
+            Some(inverse) => Gradient { to_local: inverse.then(self.to_local), ..self },
+            None => self,
+        }
+    }
+
+    /// How far along the gradient `p` lies, 0 to 1.
+    pub fn offset(&self, p: Point) -> f32 {
+        let p = self.to_local.apply(p);
+        match self.kind {
+            Kind::Linear { from, to } => {
+                let d = to - from;
+                let len = d.x * d.x + d.y * d.y;
+                if len == 0.0 {
+                    return 0.0;
+                }
+                let along = p - from;
+                ((along.x * d.x + along.y * d.y) / len).clamp(0.0, 1.0)
+            },
+            Kind::Radial { center, radius } => {
+                if radius <= 0.0 {
+                    return 1.0;
+                }
+                ((p - center).length() / radius).min(1.0)
+            },
+            Kind::Conic { center, start, sweep } => {
+                if sweep == 0.0 {
+                    return 0.0;
+                }
+                let d = p - center;
+                let angle = d.y.atan2(d.x);
+                let turned = if sweep > 0.0 { angle - start } else { start - angle };
+                let turned = turned.rem_euclid(TAU);
+                let sweep = sweep.abs();
+                if turned <= sweep {
+                    return turned / sweep;
+                }
+                // Outside the sweep, the nearer end.
+                if turned - sweep < TAU - turned { 1.0 } else { 0.0 }
+            },
+        }
+    }
+
+    /// Color at `offset` along the gradient.
+    pub fn color_at(&self, offset: f32) -> Color {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return Color::TRANSPARENT,
+        };
+        if offset <= first.0 {
+            return first.1;
+        }
+        if offset >= last.0 {
+            return last.1;
+        }
+
+        let i = self.stops.iter().position(|s| s.0 > offset).unwrap_or(self.stops.len() - 1);
+        let ((o0, c0), (o1, c1)) = (self.stops[i - 1], self.stops[i]);
+        let t = if o1 > o0 { (offset - o0) / (o1 - o0) } else { 1.0 };
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color::rgba(mix(c0.r, c1.r), mix(c0.g, c1.g), mix(c0.b, c1.b), mix(c0.a, c1.a))
+    }
+
+    pub fn color(&self, p: Point) -> Color {
+        self.color_at(self.offset(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn blends_between_stops() {
+        let (red, blue) = (Color::rgb(255, 0, 0), Color::rgb(0, 0, 255));
+        let g = Gradient::new(Kind::Conic { center: Point::default(), start: 0.0, sweep: PI }, vec![(0.5, blue), (0.0, red)]);
+        assert_eq!(g.color_at(-1.0), red);
+        assert_eq!(g.color_at(0.25), Color::rgb(128, 0, 128));
+        assert_eq!(g.color_at(2.0), blue);
+        assert_eq!(Gradient::even(g.kind, &[red]).color_at(0.7), red);
+    }
+
+    #[test]
+    fn lays_out_kinds() {
+        let g = Gradient::conic(Point::new(1.0, 1.0), -PI / 2.0, PI, &[Color::BLACK, Color::WHITE]);
+        let at = |x, y| g.offset(Point::new(x, y));
+        assert!(at(1.0, 0.0).abs() < 1e-6);
+        assert!((at(2.0, 1.0) - 0.5).abs() < 1e-6);
+        assert!((at(1.0, 2.0) - 1.0).abs() < 1e-6);
+        assert_eq!((at(0.0, 1.5), at(0.0, 0.5)), (1.0, 0.0));
+
+        let line = Gradient::linear(Point::new(0.0, 0.0), Point::new(4.0, 0.0), &[Color::BLACK, Color::WHITE]);
+        assert_eq!((line.offset(Point::new(1.0, 9.0)), line.offset(Point::new(-1.0, 0.0))), (0.25, 0.0));
+        let turned = line.transformed(&Transform::rotate(PI / 2.0));
+        assert!((turned.offset(Point::new(-9.0, 3.0)) - 0.75).abs() < 1e-6);
+
+        let disc = Gradient::radial(Point::new(1.0, 1.0), 2.0, &[Color::BLACK, Color::WHITE]);
+        assert_eq!((disc.offset(Point::new(1.0, 2.0)), disc.offset(Point::new(9.0, 1.0))), (0.5, 1.0));
+
+        let back = Gradient::conic(Point::default(), 0.0, -PI / 2.0, &[Color::BLACK, Color::WHITE]);
+        assert!((back.offset(Point::new(1.0, -1.0)) - 0.5).abs() < 1e-6);
+    }
+}
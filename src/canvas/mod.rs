@@ -0,0 +1,441 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::geometry::Rectangle;
+use crate::error::{Error, Result};
+
+pub mod bitmap;
+pub mod font;
+pub mod gradient;
+pub mod path;
+pub mod raster;
+pub mod svg;
+pub mod text;
+pub mod transform;
+
+pub use self::bitmap::Bitmap;
+pub use self::gradient::Gradient;
+pub use self::path::{Cap, Join, Path, Point, Stroke};
+pub use self::raster::Mask;
+pub use self::transform::Transform;
+
+/// An sRGB color with straight, not premultiplied, alpha.
+///
+/// Skins write colors as `"#rgb"`, `"#rrggbb"`, `"#rrggbbaa"` or as arrays
+/// `[r, g, b]` and `[r, g, b, a]`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "ColorValue")]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+
+    /// The color with its alpha scaled by `opacity`, 0 to 1.
+    pub fn with_opacity(self, opacity: f32) -> Color {
+        let a = (self.a as f32 * opacity.clamp(0.0, 1.0)).round() as u8;
+        Color { a, ..self }
+    }
+
+    /// Premultiplied RGBA bytes.
+    pub fn premultiplied(self) -> [u8; 4] {
+        let a = self.a as u32;
+        let mul = |c: u8| ((c as u32 * a + 127) / 255) as u8;
+        [mul(self.r), mul(self.g), mul(self.b), self.a]
+    }
+
+    /// Color of premultiplied RGBA bytes.
+    pub fn from_premultiplied(p: [u8; 4]) -> Color {
+        if p[3] == 0 {
+            return Color::TRANSPARENT;
+        }
+        let a = p[3] as u32;
+        let div = |c: u8| ((c as u32 * 255 + a / 2) / a).min(255) as u8;
+        Color::rgba(div(p[0]), div(p[1]), div(p[2]), p[3])
+    }
+}
+
+impl FromStr for Color {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Color> {
+        let invalid = || Error::from(format!("invalid color {:?}, expected #rgb, #rrggbb or #rrggbbaa", s));
+        let hex = s.trim().strip_prefix('#').ok_or_else(invalid)?;
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let channel = |i: usize, len: usize| {
+            let v = u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).unwrap_or(0);
+            if len == 1 { v * 17 } else { v }
+        };
+        match hex.len() {
+            3 => Ok(Color::rgb(channel(0, 1), channel(1, 1), channel(2, 1))),
+            6 => Ok(Color::rgb(channel(0, 2), channel(1, 2), channel(2, 2))),
+            8 => Ok(Color::rgba(channel(0, 2), channel(1, 2), channel(2, 2), channel(3, 2))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Hex(String),
+    Rgb([u8; 3]),
+    Rgba([u8; 4]),
+}
+
+impl TryFrom<ColorValue> for Color {
+    type Error = String;
+
+    fn try_from(v: ColorValue) -> std::result::Result<Color, String> {
+        match v {
+            ColorValue::Hex(s) => s.parse().map_err(|e: Error| e.to_string()),
+            ColorValue::Rgb([r, g, b]) => Ok(Color::rgb(r, g, b)),
+            ColorValue::Rgba([r, g, b, a]) => Ok(Color::rgba(r, g, b, a)),
+        }
+    }
+}
+
+/// A premultiplied RGBA8 pixel buffer that skins are drawn into, row by
+/// row from the top left.
+///
+/// Drawing is anti-aliased and blended over what is already there. Nothing
+/// is drawn outside the clip rectangle, the whole canvas unless set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+    clip: Rectangle<i32>,
+}
+
+impl Canvas {
+    /// A transparent canvas.
+    pub fn new(width: u32, height: u32) -> Canvas {
+        Canvas {
+            width,
+            height,
+            data: vec![0; width as usize * height as usize * 4],
+            clip: Rectangle::new(0, 0, width as i32, height as i32),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The premultiplied pixels.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn bounds(&self) -> Rectangle<i32> {
+        Rectangle::new(0, 0, self.width as i32, self.height as i32)
+    }
+
+    pub fn clip(&self) -> Rectangle<i32> {
+        self.clip
+    }
+
+    /// Limits drawing to `rect`, within the canvas.
+    pub fn set_clip(&mut self, rect: Rectangle<i32>) {
+        self.clip = raster::intersect(self.bounds(), rect);
+    }
+
+    pub fn reset_clip(&mut self) {
+        self.clip = self.bounds();
+    }
+
+    /// Sets every pixel to `color`, without blending or clipping.
+    pub fn clear(&mut self, color: Color) {
+        let p = color.premultiplied();
+        for pixel in self.data.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&p);
+        }
+    }
+
+    /// Color at `x`, `y`, transparent outside the canvas.
+    pub fn pixel(&self, x: i32, y: i32) -> Color {
+        match self.index(x, y) {
+            Some(i) => Color::from_premultiplied([self.data[i], self.data[i + 1], self.data[i + 2], self.data[i + 3]]),
+            None => Color::TRANSPARENT,
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return None;
+        }
+        Some((y as usize * self.width as usize + x as usize) * 4)
+    }
+
+    /// Draws premultiplied `src` over the pixel at `x`, `y`, scaled by
+    /// `coverage` from 0 to 255.
+    pub fn blend_pixel(&mut self, x: i32, y: i32, src: [u8; 4], coverage: u8) {
+        if !self.clip.contains(x, y) {
+            return;
+        }
+        let i = match self.index(x, y) {
+            Some(i) => i,
+            None => return,
+        };
+        let scale = |c: u8| (c as u32 * coverage as u32 + 127) / 255;
+        let alpha = scale(src[3]);
+        if alpha == 0 {
+            return;
+        }
+        for (d, s) in self.data[i..i + 4].iter_mut().zip(src) {
+            *d = (scale(s) + (*d as u32 * (255 - alpha) + 127) / 255).min(255) as u8;
+        }
+    }
+
+    /// Fills whole pixels of `rect`.
+    pub fn fill_rect(&mut self, rect: Rectangle<i32>, color: Color) {
+        let src = color.premultiplied();
+        let area = raster::intersect(self.clip, rect);
+        for y in area.y()..area.bottom() {
+            for x in area.x()..area.right() {
+                self.blend_pixel(x, y, src, 255);
+            }
+        }
+    }
+
+    /// Fills the pixels of `mask` with `color`, as much as they are covered.
+    pub fn fill_mask(&mut self, mask: &Mask, color: Color) {
+        let src = color.premultiplied();
+        let area = mask.bounds();
+        for y in area.y()..area.bottom() {
+            for x in area.x()..area.right() {
+                self.blend_pixel(x, y, src, mask.get(x, y));
+            }
+        }
+    }
+
+    pub fn fill_path(&mut self, path: &Path, color: Color) {
+        let mask = Mask::new(path, self.clip);
+        self.fill_mask(&mask, color);
+    }
+
+    /// Fills `path` with `gradient`, sampled at the middle of each pixel.
+    pub fn fill_path_gradient(&mut self, path: &Path, gradient: &Gradient) {
+        let mask = Mask::new(path, self.clip);
+        let area = mask.bounds();
+        for y in area.y()..area.bottom() {
+            for x in area.x()..area.right() {
+                let coverage = mask.get(x, y);
+                if coverage > 0 {
+                    let color = gradient.color(Point::new(x as f32 + 0.5, y as f32 + 0.5));
+                    self.blend_pixel(x, y, color.premultiplied(), coverage);
+                }
+            }
+        }
+    }
+
+    pub fn stroke_path(&mut self, path: &Path, stroke: &Stroke, color: Color) {
+        self.fill_path(&path.stroke(stroke), color);
+    }
+
+    pub fn draw_line(&mut self, from: Point, to: Point, width: f32, color: Color) {
+        self.stroke_path(&Path::line(from, to), &Stroke::new(width), color);
+    }
+
+    /// Strokes a circular arc around `center` from angle `start` over
+    /// `sweep` radians, clockwise when positive.
+    pub fn draw_arc(&mut self, center: Point, radius: f32, start: f32, sweep: f32, stroke: &Stroke, color: Color) {
+        let mut arc = Path::new();
+        arc.arc(center, radius, radius, start, sweep);
+        self.stroke_path(&arc, stroke, color);
+    }
+
+    pub fn fill_rounded_rect(&mut self, rect: Rectangle<f32>, radius: f32, color: Color) {
+        self.fill_path(&Path::rounded_rect(rect, radius), color);
+    }
+
+    /// Draws the `src` part of `bitmap` scaled into `dest`, with its alpha
+    /// scaled by `opacity`.
+    pub fn draw_bitmap(&mut self, bitmap: &Bitmap, src: Rectangle<f32>, dest: Rectangle<i32>, opacity: u8) {
+        let area = raster::intersect(self.clip, dest);
+        if area.w() == 0 || area.h() == 0 || src.w() <= 0.0 || src.h() <= 0.0 {
+            return;
+        }
+        let (sx, sy) = (src.w() / dest.w() as f32, src.h() / dest.h() as f32);
+        let columns: Vec<_> = (area.x()..area.right())
+            .map(|x| bitmap::taps(src.x() + ((x - dest.x()) as f32 + 0.5) * sx, sx, bitmap.width()))
+            .collect();
+
+        for y in area.y()..area.bottom() {
+            let rows = bitmap::taps(src.y() + ((y - dest.y()) as f32 + 0.5) * sy, sy, bitmap.height());
+            for (x, columns) in (area.x()..).zip(&columns) {
+                let mut sum = [0.0f32; 4];
+                for &(row, wy) in &rows {
+                    for &(column, wx) in columns {
+                        for (s, c) in sum.iter_mut().zip(bitmap.pixel(column, row)) {
+                            *s += c as f32 * wx * wy;
+                        }
+                    }
+                }
+                self.blend_pixel(x, y, sum.map(|s| s.round().min(255.0) as u8), opacity);
+            }
+        }
+    }
+
+    /// Draws `bitmap` with its pixels placed by `transform`, with its alpha
+    /// scaled by `opacity`.
+    pub fn draw_bitmap_transformed(&mut self, bitmap: &Bitmap, transform: &Transform, opacity: u8) {
+        let inverse = match transform.invert() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let (w, h) = (bitmap.width() as f32, bitmap.height() as f32);
+        let corners = [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)].map(|(x, y)| transform.apply(Point::new(x, y)));
+        let (left, right) = corners.iter().fold((f32::MAX, f32::MIN), |(l, r), p| (l.min(p.x), r.max(p.x)));
+        let (top, bottom) = corners.iter().fold((f32::MAX, f32::MIN), |(t, b), p| (t.min(p.y), b.max(p.y)));
+        let area = raster::intersect(self.clip, Rectangle::new(
+            left.floor() as i32,
+            top.floor() as i32,
+            (right.ceil() - left.floor()) as i32,
+            (bottom.ceil() - top.floor()) as i32,
+        ));
+
+        for y in area.y()..area.bottom() {
+            for x in area.x()..area.right() {
+                let p = inverse.apply(Point::new(x as f32 + 0.5, y as f32 + 0.5));
+                let color = bitmap.sample(p.x, p.y);
+                self.blend_pixel(x, y, color.map(|c| c.round().min(255.0) as u8), opacity);
+            }
+        }
+    }
+
+    /// Strokes the border of `rect` so that it stays inside it.
+    pub fn stroke_rect(&mut self, rect: Rectangle<f32>, width: f32, color: Color) {
+        let half = width / 2.0;
+        let inner = Rectangle::new(rect.x() + half, rect.y() + half, rect.w() - width, rect.h() - width);
+        self.stroke_path(&Path::rect(inner), &Stroke::new(width), color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!("#f80".parse::<Color>().unwrap(), Color::rgb(255, 136, 0));
+        assert_eq!("#ff8800".parse::<Color>().unwrap(), Color::rgb(255, 136, 0));
+        assert_eq!("#ff880080".parse::<Color>().unwrap(), Color::rgba(255, 136, 0, 128));
+        assert!("ff8800".parse::<Color>().is_err());
+        assert!("#ff88zz".parse::<Color>().is_err());
+
+        #[derive(Deserialize)]
+        struct Colors {
+            a: Color,
+            b: Color,
+        }
+        let colors: Colors = toml::from_str("a = [1, 2, 3]\nb = \"#0000ff\"").unwrap();
+        assert_eq!((colors.a, colors.b), (Color::rgb(1, 2, 3), Color::rgb(0, 0, 255)));
+        assert!(toml::from_str::<Colors>("a = \"red\"\nb = [1, 2, 3]").is_err());
+    }
+
+    #[test]
+    fn blends_premultiplied() {
+        let mut canvas = Canvas::new(4, 3);
+        canvas.clear(Color::WHITE);
+        canvas.fill_rect(Rectangle::new(-1, 1, 3, 5), Color::rgba(0, 0, 0, 128));
+
+        assert_eq!(canvas.pixel(0, 0), Color::WHITE);
+        assert_eq!(canvas.pixel(1, 2), Color::rgb(127, 127, 127));
+        assert_eq!(canvas.pixel(2, 2), Color::WHITE);
+        assert_eq!(canvas.pixel(9, 9), Color::TRANSPARENT);
+
+        let mut clear = Canvas::new(1, 1);
+        clear.fill_rect(clear.bounds(), Color::rgba(255, 0, 0, 128));
+        assert_eq!(clear.data(), &[128, 0, 0, 128]);
+        assert_eq!(clear.pixel(0, 0), Color::rgba(255, 0, 0, 128));
+    }
+
+    #[test]
+    fn draws_shapes_within_clip() {
+        let mut canvas = Canvas::new(10, 10);
+        canvas.set_clip(Rectangle::new(-5, 2, 8, 20));
+        assert_eq!(canvas.clip(), Rectangle::new(0, 2, 3, 8));
+
+        canvas.fill_rounded_rect(Rectangle::new(0.0, 0.0, 10.0, 10.0), 3.0, Color::WHITE);
+        assert_eq!(canvas.pixel(2, 5), Color::WHITE);
+        assert_eq!(canvas.pixel(0, 1), Color::TRANSPARENT);
+        assert_eq!(canvas.pixel(3, 5), Color::TRANSPARENT);
+        let corner = canvas.pixel(0, 2);
+        assert!(corner.a > 0 && corner.a < 255, "{:?}", corner);
+
+        canvas.reset_clip();
+        canvas.clear(Color::TRANSPARENT);
+        canvas.stroke_rect(Rectangle::new(1.0, 1.0, 8.0, 8.0), 2.0, Color::rgb(0, 0, 255));
+        assert_eq!(canvas.pixel(1, 5), Color::rgb(0, 0, 255));
+        assert_eq!(canvas.pixel(2, 2), Color::rgb(0, 0, 255));
+        assert_eq!(canvas.pixel(3, 5), Color::TRANSPARENT);
+        assert_eq!(canvas.pixel(0, 0), Color::TRANSPARENT);
+
+        canvas.clear(Color::TRANSPARENT);
+        canvas.draw_line(Point::new(0.0, 5.0), Point::new(10.0, 5.0), 1.0, Color::BLACK);
+        assert_eq!((canvas.pixel(4, 4).a, canvas.pixel(4, 5).a), (128, 128));
+    }
+
+    #[test]
+    fn draws_scaled_bitmaps() {
+        let mut canvas = Canvas::new(10, 10);
+        let bitmap = Bitmap::from_rgba(2, 1, &[255, 0, 0, 255, 0, 0, 255, 255]);
+        canvas.draw_bitmap(&bitmap, Rectangle::new(0.0, 0.0, 2.0, 1.0), Rectangle::new(6, 0, 4, 2), 255);
+        assert_eq!((canvas.pixel(6, 1), canvas.pixel(9, 0)), (Color::rgb(255, 0, 0), Color::rgb(0, 0, 255)));
+        assert_eq!(canvas.pixel(7, 0), Color::rgb(191, 0, 64));
+        canvas.draw_bitmap(&bitmap, Rectangle::new(0.0, 0.0, 2.0, 1.0), Rectangle::new(0, 3, 1, 1), 128);
+        assert_eq!(canvas.pixel(0, 3), Color::rgba(128, 0, 128, 128));
+    }
+
+    #[test]
+    fn draws_transformed_bitmaps() {
+        let mut canvas = Canvas::new(10, 10);
+        let bitmap = Bitmap::from_rgba(2, 1, &[255, 0, 0, 255, 0, 0, 255, 255]);
+        let turn = Transform::rotate(std::f32::consts::FRAC_PI_2).then(Transform::translate(5.0, 5.0));
+        canvas.draw_bitmap_transformed(&bitmap, &turn, 255);
+        assert_eq!((canvas.pixel(4, 5), canvas.pixel(4, 6)), (Color::rgb(255, 0, 0), Color::rgb(0, 0, 255)));
+        assert_eq!((canvas.pixel(5, 5), canvas.pixel(4, 4)), (Color::TRANSPARENT, Color::TRANSPARENT));
+    }
+
+    #[test]
+    fn draws_arcs() {
+        let mut canvas = Canvas::new(10, 10);
+        let ring = Stroke::new(2.0).with_cap(Cap::Round);
+        canvas.draw_arc(Point::new(5.0, 5.0), 4.0, 0.0, std::f32::consts::PI, &ring, Color::WHITE);
+        assert_eq!((canvas.pixel(5, 8), canvas.pixel(8, 5), canvas.pixel(5, 1)), (Color::WHITE, Color::WHITE, Color::TRANSPARENT));
+        assert_eq!(canvas.pixel(5, 5), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn fills_conic_gradients() {
+        let mut canvas = Canvas::new(10, 10);
+        let gradient = Gradient::conic(Point::new(5.0, 5.0), 0.0, std::f32::consts::TAU, &[Color::BLACK, Color::WHITE]);
+        canvas.fill_path_gradient(&Path::rect(Rectangle::new(0.0, 0.0, 10.0, 10.0)), &gradient);
+        assert!(canvas.pixel(9, 6).r < 30 && canvas.pixel(9, 4).r > 225);
+    }
+}
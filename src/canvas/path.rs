@@ -0,0 +1,602 @@
+use std::f32::consts::PI;
+use std::ops::{Add, Mul, Sub};
+
+use serde::Deserialize;
+
+use crate::canvas::transform::Transform;
+use crate::geometry::Rectangle;
+
+/// Largest distance, in pixels, a flattened curve may stray from the real one.
+const TOLERANCE: f32 = 0.05;
+
+/// Longest a miter join may reach past its point, in stroke widths, before
+/// it is beveled instead. The same as SVG's default.
+const MITER_LIMIT: f32 = 4.0;
+
+/// Most dashes a path is cut into.
+const MAX_DASHES: f32 = 100_000.0;
+
+/// A point in canvas pixels, `y` pointing down.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub const fn new(x: f32, y: f32) -> Point {
+        Point { x, y }
+    }
+
+    pub fn length(self) -> f32 {
+        self.x.hypot(self.y)
+    }
+
+    /// The point turned a quarter clockwise around the origin.
+    pub fn perpendicular(self) -> Point {
+        Point::new(-self.y, self.x)
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f32> for Point {
+    type Output = Point;
+
+    fn mul(self, factor: f32) -> Point {
+        Point::new(self.x * factor, self.y * factor)
+    }
+}
+
+/// A run of connected points. Closed contours join their last point back to
+/// the first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Contour {
+    pub points: Vec<Point>,
+    pub closed: bool,
+}
+
+/// An outline of straight line contours. Arcs are flattened into lines as
+/// they are added.
+///
+/// Filling treats every contour as closed. Where contours overlap their
+/// coverage adds up when they wind the same way and cancels out when they
+/// wind opposite ways, which is how holes are cut.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Path {
+    contours: Vec<Contour>,
+}
+
+impl Path {
+    pub fn new() -> Path {
+        Path::default()
+    }
+
+    pub fn rect(rect: Rectangle<f32>) -> Path {
+        Path::polygon(&[
+            Point::new(rect.x(), rect.y()),
+            Point::new(rect.right(), rect.y()),
+            Point::new(rect.right(), rect.bottom()),
+            Point::new(rect.x(), rect.bottom()),
+        ])
+    }
+
+    /// A rectangle with its corners rounded by `radius`, at most half the
+    /// shorter side.
+    pub fn rounded_rect(rect: Rectangle<f32>, radius: f32) -> Path {
+        let r = radius.min(rect.w() / 2.0).min(rect.h() / 2.0).max(0.0);
+        if r == 0.0 {
+            return Path::rect(rect);
+        }
+
+        let mut path = Path::new();
+        path.arc(Point::new(rect.right() - r, rect.y() + r), r, r, -PI / 2.0, PI / 2.0);
+        path.arc(Point::new(rect.right() - r, rect.bottom() - r), r, r, 0.0, PI / 2.0);
+        path.arc(Point::new(rect.x() + r, rect.bottom() - r), r, r, PI / 2.0, PI / 2.0);
+        path.arc(Point::new(rect.x() + r, rect.y() + r), r, r, PI, PI / 2.0);
+        path.close();
+        path
+    }
+
+    pub fn ellipse(center: Point, rx: f32, ry: f32) -> Path {
+        let mut path = Path::new();
+        path.arc(center, rx, ry, 0.0, 2.0 * PI);
+        path.close();
+        path
+    }
+
+    /// A closed polygon through `points`.
+    pub fn polygon(points: &[Point]) -> Path {
+        Path {
+            contours: vec![Contour { points: points.to_vec(), closed: true }],
+        }
+    }
+
+    /// An open line from `from` to `to`, for stroking.
+    pub fn line(from: Point, to: Point) -> Path {
+        Path {
+            contours: vec![Contour { points: vec![from, to], closed: false }],
+        }
+    }
+
+    /// Starts a new contour at `p`.
+    pub fn move_to(&mut self, p: Point) {
+        self.contours.push(Contour { points: vec![p], closed: false });
+    }
+
+    /// Adds a line to `p`, starting a contour there if there is none.
+    pub fn line_to(&mut self, p: Point) {
+        match self.contours.last_mut() {
+            Some(c) if !c.closed => c.points.push(p),
+            _ => self.move_to(p),
+        }
+    }
+
+    /// Adds an elliptic arc around `center` that starts at angle `start` and
+    /// sweeps `sweep` radians, clockwise on screen when positive. The current
+    /// contour is joined to the start of the arc by a line.
+    pub fn arc(&mut self, center: Point, rx: f32, ry: f32, start: f32, sweep: f32) {
+        let steps = arc_steps(rx.abs().max(ry.abs()), sweep);
+        for i in 0..=steps {
+            let angle = start + sweep * i as f32 / steps as f32;
+            self.line_to(Point::new(center.x + rx * angle.cos(), center.y + ry * angle.sin()));
+        }
+    }
+
+    /// Adds a quadratic Bézier curve through control point `c` to `p`.
+    pub fn quad_to(&mut self, c: Point, p: Point) {
+        let start = self.current();
+        let steps = curve_steps((start - c * 2.0 + p).length());
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            let u = 1.0 - t;
+            self.line_to(start * (u * u) + c * (2.0 * u * t) + p * (t * t));
+        }
+    }
+
+    /// Adds a cubic Bézier curve through control points `c1` and `c2` to
+    /// `p`.
+    pub fn cubic_to(&mut self, c1: Point, c2: Point, p: Point) {
+        let start = self.current();
+        let bend = (start - c1 * 2.0 + c2).length().max((c1 - c2 * 2.0 + p).length());
+        let steps = curve_steps(bend * 6.0);
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            let u = 1.0 - t;
+            self.line_to(start * (u * u * u) + c1 * (3.0 * u * u * t) + c2 * (3.0 * u * t * t) + p * (t * t * t));
+        }
+    }
+
+    // Last point of the current contour, where curves start.
+    fn current(&self) -> Point {
+        match self.contours.last() {
+            Some(c) if !c.closed => c.points.last().copied().unwrap_or_default(),
+            _ => Point::default(),
+        }
+    }
+
+    /// Closes the current contour.
+    pub fn close(&mut self) {
+        if let Some(c) = self.contours.last_mut() {
+            c.closed = true;
+        }
+    }
+
+    pub fn contours(&self) -> &[Contour] {
+        &self.contours
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contours.iter().all(|c| c.points.is_empty())
+    }
+
+    /// Turns every contour around, so it winds the other way.
+    pub fn reverse(&mut self) {
+        for c in self.contours.iter_mut() {
+            c.points.reverse();
+        }
+    }
+
+    /// Adds every contour of `other`.
+    pub fn extend(&mut self, other: Path) {
+        self.contours.extend(other.contours);
+    }
+
+    /// The path moved by `offset`.
+    pub fn translated(&self, offset: Point) -> Path {
+        Path {
+            contours: self.contours.iter().map(|c| Contour {
+                points: c.points.iter().map(|&p| p + offset).collect(),
+                closed: c.closed,
+            }).collect(),
+        }
+    }
+
+    /// The path with every point moved by `transform`.
+    pub fn transformed(&self, transform: &Transform) -> Path {
+        Path {
+            contours: self.contours.iter().map(|c| Contour {
+                points: c.points.iter().map(|&p| transform.apply(p)).collect(),
+                closed: c.closed,
+            }).collect(),
+        }
+    }
+
+    /// The path cut into open dashes. `pattern` gives the lengths of the
+    /// dashes and of the gaps between them in turn, and is repeated twice
+    /// if it has an odd number of them. Every contour starts the pattern
+    /// anew, `offset` into it. Patterns that would cut the path into too many
+    /// dashes, or too small to move along it at its scale, leave it solid.
+    pub fn dashed(&self, pattern: &[f32], offset: f32) -> Path {
+        if pattern.iter().sum::<f32>() <= 0.0 || pattern.iter().any(|&l| l < 0.0) {
+            return self.clone();
+        }
+        let pattern = if pattern.len() % 2 == 1 { pattern.repeat(2) } else { pattern.to_vec() };
+        let total: f32 = pattern.iter().sum();
+        let length: f32 = self.contours.iter().map(|c| {
+            let closing = if c.closed { c.points.first().zip(c.points.last()).map_or(0.0, |(&a, &b)| (a - b).length()) } else { 0.0 };
+            c.points.windows(2).map(|w| (w[1] - w[0]).length()).sum::<f32>() + closing
+        }).sum();
+        if length / total * pattern.len() as f32 > MAX_DASHES {
+            return self.clone();
+        }
+
+        let mut dashes = Path::new();
+        for contour in &self.contours {
+            let mut points = contour.points.clone();
+            if contour.closed && !points.is_empty() {
+                points.push(points[0]);
+            }
+            let first = match points.first() {
+                Some(&first) => first,
+                None => continue,
+            };
+
+            // Where the contour starts in the pattern, and how much of that
+            // dash or gap is left.
+            let (mut index, mut left) = (0, pattern[0]);
+            let mut skip = offset.rem_euclid(total);
+            while skip >= left && skip > 0.0 {
+                skip -= left;
+                index = (index + 1) % pattern.len();
+                left = pattern[index];
+            }
+            left -= skip;
+            if index % 2 == 0 {
+                dashes.move_to(first);
+            }
+
+            for pair in points.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                let len = (b - a).length();
+                let mut done = 0.0;
+                while len - done > left {
+                    if left > 0.0 && done + left == done {
+                        return self.clone();
+                    }
+                    done += left;
+                    let p = a + (b - a) * (done / len);
+                    if index % 2 == 0 {
+                        dashes.line_to(p);
+                    } else {
+                        dashes.move_to(p);
+                    }
+                    index = (index + 1) % pattern.len();
+                    left = pattern[index];
+                }
+                left -= len - done;
+                if index % 2 == 0 {
+                    dashes.line_to(b);
+                }
+            }
+        }
+        dashes
+    }
+
+    /// Smallest rectangle holding every point, `None` for an empty path.
+    pub fn bounds(&self) -> Option<Rectangle<f32>> {
+        let mut points = self.contours.iter().flat_map(|c| c.points.iter());
+        let first = *points.next()?;
+        let (min, max) = points.fold((first, first), |(min, max), p| {
+            (Point::new(min.x.min(p.x), min.y.min(p.y)), Point::new(max.x.max(p.x), max.y.max(p.y)))
+        });
+        Some(Rectangle::new(min.x, min.y, max.x - min.x, max.y - min.y))
+    }
+
+    /// The area covered by drawing along the path with `stroke`, as a path
+    /// to fill.
+    pub fn stroke(&self, stroke: &Stroke) -> Path {
+        let mut outline = Path::new();
+        let half = stroke.width / 2.0;
+        if half <= 0.0 {
+            return outline;
+        }
+
+        for contour in &self.contours {
+            let points = &contour.points;
+            let segments = if contour.closed { points.len() } else { points.len().saturating_sub(1) };
+            for i in 0..segments {
+                let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                let d = b - a;
+                let len = d.length();
+                if len == 0.0 {
+                    continue;
+                }
+                let n = d.perpendicular() * (half / len);
+                outline.add_positive(vec![a + n, b + n, b - n, a - n]);
+            }
+
+            // Joins fill the wedges between segments.
+            let joins = if contour.closed { 0..points.len() } else { 1..points.len().saturating_sub(1) };
+            if points.len() > 2 || contour.closed {
+                let n = points.len();
+                for i in joins {
+                    outline.add_join(points[(i + n - 1) % n], points[i], points[(i + 1) % n], half, stroke.join);
+                }
+            }
+
+            if !contour.closed {
+                outline.add_caps(points, half, stroke.cap);
+            }
+        }
+        outline
+    }
+
+    // Adds the join at `p` between the segments from `prev` and to `next`,
+    // on the outside of the turn.
+    fn add_join(&mut self, prev: Point, p: Point, next: Point, half: f32, join: Join) {
+        if join == Join::Round {
+            self.add_disc(p, half);
+            return;
+        }
+        let (d0, d1) = (p - prev, next - p);
+        let (l0, l1) = (d0.length(), d1.length());
+        let turn = d0.x * d1.y - d0.y * d1.x;
+        if l0 == 0.0 || l1 == 0.0 || turn == 0.0 {
+            return;
+        }
+
+        // Segments are offset to both sides, the outer side is away from
+        // the turn.
+        let side = if turn > 0.0 { -half } else { half };
+        let (a, b) = (p + d0.perpendicular() * (side / l0), p + d1.perpendicular() * (side / l1));
+        let mut wedge = vec![p, a, b];
+        if join == Join::Miter {
+            // The outer edges meet out along the bisector, as far past `p`
+            // as `half` over the cosine of half the angle between them.
+            let mid = (a + b) * 0.5 - p;
+            let cos = mid.length() / half;
+            if cos > 0.0 && 1.0 / cos <= MITER_LIMIT {
+                wedge.insert(2, p + mid * (1.0 / (cos * cos)));
+            }
+        }
+        self.add_positive(wedge);
+    }
+
+    // Adds the caps of an open contour, past its first and last points.
+    fn add_caps(&mut self, points: &[Point], half: f32, cap: Cap) {
+        // Unit direction from `end` to the first of `rest` apart from it.
+        fn inward<'a>(end: Point, rest: impl Iterator<Item = &'a Point>) -> Option<Point> {
+            rest.map(|&p| p - end).find(|d| d.length() > 0.0).map(|d| d * (1.0 / d.length()))
+        }
+
+        let (first, last) = match (points.first(), points.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return,
+        };
+        let ends = [
+            (first, inward(first, points.iter().skip(1))),
+            (last, inward(last, points.iter().rev().skip(1))),
+        ];
+        for (p, u) in ends {
+            match (cap, u) {
+                // Without a direction only round caps are drawn, so that
+                // zero length dashes make dots.
+                (Cap::Round, _) => self.add_disc(p, half),
+                (Cap::Butt, _) | (Cap::Square, None) => {},
+                (Cap::Square, Some(u)) => {
+                    let (n, out) = (u.perpendicular() * half, u * -half);
+                    self.add_positive(vec![p + n, p - n, p - n + out, p + n + out]);
+                },
+            }
+        }
+    }
+
+    fn add_disc(&mut self, center: Point, radius: f32) {
+        let mut disc = Path::ellipse(center, radius, radius);
+        self.add_positive(disc.contours.remove(0).points);
+    }
+
+    // Adds a closed contour wound so that it adds to the coverage of the
+    // others added this way.
+    fn add_positive(&mut self, mut points: Vec<Point>) {
+        if signed_area(&points) < 0.0 {
+            points.reverse();
+        }
+        self.contours.push(Contour { points, closed: true });
+    }
+}
+
+/// How the ends of open contours are stroked.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cap {
+    /// Cut off square at the end.
+    #[default]
+    Butt,
+    /// Rounded by a half circle around the end.
+    Round,
+    /// Cut off square half the width past the end.
+    Square,
+}
+
+/// How the corners between segments are stroked.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Join {
+    /// Rounded by a circle around the corner.
+    #[default]
+    Round,
+    /// Cut off straight across the outer corners of the segments.
+    Bevel,
+    /// Sharp, where the outer edges meet. Corners that would reach too far
+    /// are beveled.
+    Miter,
+}
+
+/// How a path is stroked.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stroke {
+    pub width: f32,
+    pub cap: Cap,
+    pub join: Join,
+}
+
+impl Stroke {
+    pub fn new(width: f32) -> Stroke {
+        Stroke { width, cap: Cap::Butt, join: Join::Round }
+    }
+
+    pub fn with_cap(self, cap: Cap) -> Stroke {
+        Stroke { cap, ..self }
+    }
+
+    pub fn with_join(self, join: Join) -> Stroke {
+        Stroke { join, ..self }
+    }
+}
+
+/// Twice the area enclosed by `points`, positive when they run clockwise on
+/// screen.
+pub fn signed_area(points: &[Point]) -> f32 {
+    let n = points.len();
+    (0..n).map(|i| {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        a.x * b.y - b.x * a.y
+    }).sum()
+}
+
+// Line segments needed for a curve to stay within `TOLERANCE`, given the
+// largest second difference of its control points. Evenly split curves stray
+// by at most an eighth of it over the square of the steps.
+fn curve_steps(bend: f32) -> usize {
+    ((bend / (8.0 * TOLERANCE)).sqrt().ceil() as usize).clamp(1, 256)
+}
+
+// Line segments needed for an arc of `radius` to stay within `TOLERANCE`.
+fn arc_steps(radius: f32, sweep: f32) -> usize {
+    if radius <= TOLERANCE {
+        return 4;
+    }
+    let step = 2.0 * (1.0 - TOLERANCE / radius).acos();
+    ((sweep.abs() / step).ceil() as usize).clamp(1, 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_shapes() {
+        let rect = Path::rect(Rectangle::new(1.0, 2.0, 3.0, 4.0));
+        assert_eq!(rect.bounds(), Some(Rectangle::new(1.0, 2.0, 3.0, 4.0)));
+        assert_eq!(signed_area(&rect.contours()[0].points), 24.0);
+
+        let circle = Path::ellipse(Point::new(10.0, 10.0), 10.0, 10.0);
+        let area = signed_area(&circle.contours()[0].points) / 2.0;
+        assert!((area - PI * 100.0).abs() < 2.5, "{}", area);
+
+        let rounded = Path::rounded_rect(Rectangle::new(0.0, 0.0, 10.0, 4.0), 5.0);
+        let b = rounded.bounds().unwrap();
+        assert!((b.w() - 10.0).abs() < 1e-4 && (b.h() - 4.0).abs() < 1e-4);
+
+        let mut path = Path::new();
+        path.line_to(Point::new(0.0, 0.0));
+        path.line_to(Point::new(1.0, 0.0));
+        path.close();
+        path.line_to(Point::new(5.0, 5.0));
+        assert_eq!(path.contours().len(), 2);
+        assert!(Path::new().is_empty());
+
+        let mut curve = Path::new();
+        curve.move_to(Point::new(0.0, 0.0));
+        curve.quad_to(Point::new(5.0, 10.0), Point::new(10.0, 0.0));
+        curve.cubic_to(Point::new(10.0, -10.0), Point::new(0.0, -10.0), Point::new(0.0, 0.0));
+        let points = &curve.contours()[0].points;
+        assert!(points.len() > 8);
+        assert_eq!(points.last(), Some(&Point::new(0.0, 0.0)));
+        let b = curve.translated(Point::new(1.0, 1.0)).bounds().unwrap();
+        assert!((b.bottom() - 6.0).abs() < 0.1 && (b.y() - (1.0 - 7.5)).abs() < 0.1, "{:?}", b);
+    }
+
+    #[test]
+    fn strokes_are_wound_alike() {
+        let mut zigzag = Path::new();
+        for (i, x) in [0.0, 10.0, 20.0, 30.0].iter().enumerate() {
+            zigzag.line_to(Point::new(*x, (i % 2) as f32 * 10.0));
+        }
+        let outline = zigzag.stroke(&Stroke::new(2.0));
+
+        // Three segments and two joins.
+        assert_eq!(outline.contours().len(), 5);
+        assert!(outline.contours().iter().all(|c| c.closed && signed_area(&c.points) > 0.0));
+        assert!(Path::line(Point::new(0.0, 0.0), Point::new(0.0, 0.0)).stroke(&Stroke::new(1.0)).is_empty());
+
+        let line = Path::line(Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        let round = line.stroke(&Stroke::new(2.0).with_cap(Cap::Round)).bounds().unwrap();
+        assert!((round.x() + 1.0).abs() < 1e-4 && (round.right() - 11.0).abs() < 1e-4);
+        let square = line.stroke(&Stroke::new(2.0).with_cap(Cap::Square));
+        assert_eq!(square.bounds(), Some(Rectangle::new(-1.0, -1.0, 12.0, 2.0)));
+        assert!(square.contours().iter().all(|c| signed_area(&c.points) > 0.0));
+
+        let mut corner = Path::new();
+        corner.line_to(Point::new(0.0, 0.0));
+        corner.line_to(Point::new(10.0, 0.0));
+        corner.line_to(Point::new(10.0, 10.0));
+        let miter = corner.stroke(&Stroke::new(2.0).with_join(Join::Miter));
+        let b = miter.bounds().unwrap();
+        assert!((b.y() + 1.0).abs() < 1e-4 && (b.right() - 11.0).abs() < 1e-4, "{:?}", b);
+        assert!(miter.contours().iter().all(|c| signed_area(&c.points) > 0.0));
+        let bevel = corner.stroke(&Stroke::new(2.0).with_join(Join::Bevel));
+        assert_eq!(signed_area(&bevel.contours()[2].points), 1.0);
+    }
+
+    #[test]
+    fn dashes_and_transforms() {
+        let line = Path::line(Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        let dashes = line.dashed(&[3.0, 1.0], 1.0);
+        let spans: Vec<_> = dashes.contours().iter().map(|c| (c.points[0].x, c.points.last().unwrap().x)).collect();
+        assert_eq!(spans, vec![(0.0, 2.0), (3.0, 6.0), (7.0, 10.0)]);
+        assert_eq!(line.dashed(&[], 0.0), line);
+        let long = Path::line(Point::new(0.0, 0.0), Point::new(100.0, 0.0));
+        assert_eq!(long.dashed(&[0.000001], 0.0), long);
+        assert_eq!(long.dashed(&[0.000001, 10.0], 0.0), long);
+
+        let square = Path::rect(Rectangle::new(0.0, 0.0, 4.0, 4.0));
+        let dashes = square.dashed(&[6.0], 0.0);
+        assert_eq!(dashes.contours().len(), 2);
+        assert_eq!(dashes.contours()[0].points, vec![Point::new(0.0, 0.0), Point::new(4.0, 0.0), Point::new(4.0, 2.0)]);
+
+        let dots = line.dashed(&[0.0, 5.0], 0.0).stroke(&Stroke::new(2.0).with_cap(Cap::Round));
+        // Two dots, each capped at both ends.
+        assert_eq!(dots.contours().len(), 4);
+        assert_eq!(dots.bounds().map(|b| (b.x(), b.right())), Some((-1.0, 6.0)));
+
+        let moved = square.transformed(&Transform::scale(2.0, 1.0).then(Transform::translate(1.0, 0.0)));
+        assert_eq!(moved.bounds(), Some(Rectangle::new(1.0, 0.0, 8.0, 4.0)));
+    }
+}
@@ -0,0 +1,243 @@
+use crate::canvas::path::{Path, Point};
+use crate::geometry::Rectangle;
+
+/// Anti-aliased coverage of a path within a clip rectangle, 0 to 255 per
+/// pixel.
+///
+/// Coverage is the exact area of each pixel inside the outline, found by
+/// accumulating the signed area every edge adds to the pixels right of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mask {
+    bounds: Rectangle<i32>,
+    coverage: Vec<u8>,
+}
+
+impl Mask {
+    pub fn new(path: &Path, clip: Rectangle<i32>) -> Mask {
+        let bounds = match path.bounds() {
+            Some(b) => intersect(clip, Rectangle::new(
+                b.x().floor() as i32,
+                b.y().floor() as i32,
+                (b.right().ceil() - b.x().floor()) as i32,
+                (b.bottom().ceil() - b.y().floor()) as i32,
+            )),
+            None => Rectangle::new(0, 0, 0, 0),
+        };
+
+        let mut area = Accumulator::new(bounds.w() as usize, bounds.h() as usize);
+        let origin = Point::new(bounds.x() as f32, bounds.y() as f32);
+        for contour in path.contours() {
+            let points = &contour.points;
+            for i in 0..points.len() {
+                let next = points[(i + 1) % points.len()];
+                area.edge(points[i] - origin, next - origin);
+            }
+        }
+
+        Mask {
+            bounds,
+            coverage: area.coverage(),
+        }
+    }
+
+    /// The pixels the mask may cover, in canvas coordinates.
+    pub fn bounds(&self) -> Rectangle<i32> {
+        self.bounds
+    }
+
+    /// Coverage at canvas position `x`, `y`, 0 outside the mask.
+    pub fn get(&self, x: i32, y: i32) -> u8 {
+        if !self.bounds.contains(x, y) {
+            return 0;
+        }
+        let (x, y) = ((x - self.bounds.x()) as usize, (y - self.bounds.y()) as usize);
+        self.coverage[y * self.bounds.w() as usize + x]
+    }
+}
+
+/// The overlap of two rectangles, empty ones placed at `a`'s origin.
+pub fn intersect(a: Rectangle<i32>, b: Rectangle<i32>) -> Rectangle<i32> {
+    let (x, y) = (a.x().max(b.x()), a.y().max(b.y()));
+    let (right, bottom) = (a.right().min(b.right()), a.bottom().min(b.bottom()));
+    if right <= x || bottom <= y {
+        return Rectangle::new(a.x(), a.y(), 0, 0);
+    }
+    Rectangle::new(x, y, right - x, bottom - y)
+}
+
+// Signed area deltas per pixel. Each row has two spare cells for edges that
+// end on its right border.
+struct Accumulator {
+    width: usize,
+    height: usize,
+    area: Vec<f32>,
+}
+
+impl Accumulator {
+    fn new(width: usize, height: usize) -> Accumulator {
+        Accumulator {
+            width,
+            height,
+            area: vec![0.0; (width + 2) * height],
+        }
+    }
+
+    fn coverage(&self) -> Vec<u8> {
+        let mut coverage = Vec::with_capacity(self.width * self.height);
+        for row in self.area.chunks_exact(self.width + 2) {
+            let mut sum = 0.0;
+            for delta in &row[..self.width] {
+                sum += delta;
+                coverage.push((sum.abs().min(1.0) * 255.0).round() as u8);
+            }
+        }
+        coverage
+    }
+
+    /// Adds an edge, split where it crosses the left and right borders.
+    /// Parts outside run along the border instead, which leaves the
+    /// coverage inside unchanged.
+    fn edge(&mut self, p0: Point, p1: Point) {
+        let width = self.width as f32;
+        let mut cuts = [0.0f32, 1.0, 1.0, 1.0];
+        let mut n = 1;
+        for border in [0.0, width] {
+            if (p0.x - border) * (p1.x - border) < 0.0 {
+                cuts[n] = (border - p0.x) / (p1.x - p0.x);
+                n += 1;
+            }
+        }
+        cuts[n] = 1.0;
+        cuts[1..n].sort_by(|a, b| a.total_cmp(b));
+
+        let at = |t: f32| Point::new((p0.x + (p1.x - p0.x) * t).clamp(0.0, width), p0.y + (p1.y - p0.y) * t);
+        for pair in cuts[..=n].windows(2) {
+            self.line(at(pair[0]), at(pair[1]));
+        }
+    }
+
+    // Adds a line lying between the left and right borders, row by row.
+    fn line(&mut self, p0: Point, p1: Point) {
+        if p0.y == p1.y {
+            return;
+        }
+        let (dir, p0, p1) = if p0.y < p1.y { (1.0, p0, p1) } else { (-1.0, p1, p0) };
+        let dxdy = (p1.x - p0.x) / (p1.y - p0.y);
+        let top = p0.y.max(0.0);
+        let bottom = p1.y.min(self.height as f32);
+        if top >= bottom {
+            return;
+        }
+
+        let mut x = p0.x + (top - p0.y) * dxdy;
+        for row in top as usize..bottom.ceil() as usize {
+            let dy = ((row + 1) as f32).min(bottom) - (row as f32).max(top);
+            let next = x + dxdy * dy;
+            self.span(row, x, next, dy * dir);
+            x = next;
+        }
+    }
+
+    // Adds the part of a line crossing one row, running from `x` to `next`
+    // and covering `d` of the row's height, negative when going up.
+    fn span(&mut self, row: usize, x: f32, next: f32, d: f32) {
+        let stride = self.width + 2;
+        let cells = &mut self.area[row * stride..(row + 1) * stride];
+        let max = self.width as f32;
+        let (x0, x1) = if x < next { (x, next) } else { (next, x) };
+        let (x0, x1) = (x0.clamp(0.0, max), x1.clamp(0.0, max));
+
+        let x0_floor = x0.floor();
+        let x0i = x0_floor as usize;
+        let x1_ceil = x1.ceil();
+        let x1i = x1_ceil as usize;
+
+        if x1i <= x0i + 1 {
+            // Within one pixel, split by where the line passes its middle.
+            let mid = 0.5 * (x0 + x1) - x0_floor;
+            cells[x0i] += d - d * mid;
+            cells[x0i + 1] += d * mid;
+            return;
+        }
+
+        // Across several pixels the covered area grows linearly in between
+        // and quadratically in the first and last one.
+        let s = (x1 - x0).recip();
+        let x0f = x0 - x0_floor;
+        let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+        let x1f = x1 - x1_ceil + 1.0;
+        let am = 0.5 * s * x1f * x1f;
+
+        cells[x0i] += d * a0;
+        if x1i == x0i + 2 {
+            cells[x0i + 1] += d * (1.0 - a0 - am);
+        } else {
+            let a1 = s * (1.5 - x0f);
+            cells[x0i + 1] += d * (a1 - a0);
+            for cell in &mut cells[x0i + 2..x1i - 1] {
+                *cell += d * s;
+            }
+            let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+            cells[x1i - 1] += d * (1.0 - a2 - am);
+        }
+        cells[x1i] += d * am;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::path::Stroke;
+
+    fn total(mask: &Mask) -> f32 {
+        mask.coverage.iter().map(|&c| c as f32 / 255.0).sum()
+    }
+
+    #[test]
+    fn covers_pixel_fractions() {
+        let clip = Rectangle::new(0, 0, 10, 10);
+        let mask = Mask::new(&Path::rect(Rectangle::new(1.5, 1.0, 2.0, 1.0)), clip);
+        assert_eq!(mask.bounds(), Rectangle::new(1, 1, 3, 1));
+        assert_eq!((mask.get(1, 1), mask.get(2, 1), mask.get(3, 1)), (128, 255, 128));
+        assert_eq!(mask.get(0, 0), 0);
+
+        let triangle = Path::polygon(&[Point::new(0.0, 0.0), Point::new(8.0, 0.0), Point::new(0.0, 8.0)]);
+        assert!((total(&Mask::new(&triangle, clip)) - 32.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn clips_without_changing_coverage() {
+        let circle = Path::ellipse(Point::new(5.0, 5.0), 4.0, 4.0);
+        let full = Mask::new(&circle, Rectangle::new(0, 0, 10, 10));
+        let clipped = Mask::new(&circle, Rectangle::new(3, 2, 4, 20));
+
+        assert_eq!(clipped.bounds(), Rectangle::new(3, 2, 4, 7));
+        for y in 0..10 {
+            for x in 0..10 {
+                let expected = if clipped.bounds().contains(x, y) { full.get(x, y) } else { 0 };
+                assert_eq!(clipped.get(x, y), expected, "{},{}", x, y);
+            }
+        }
+        let area = crate::canvas::path::signed_area(&circle.contours()[0].points) / 2.0;
+        assert!((total(&full) - area).abs() < 0.05);
+    }
+
+    #[test]
+    fn overlaps_add_up_and_holes_cancel() {
+        let clip = Rectangle::new(0, 0, 10, 10);
+        let outline = Path::line(Point::new(1.0, 1.0), Point::new(1.0, 8.0)).stroke(&Stroke::new(2.0));
+        let line = Mask::new(&outline, clip);
+        assert_eq!((line.get(0, 4), line.get(1, 4), line.get(2, 4)), (255, 255, 0));
+
+        let mut ring = Path::rect(Rectangle::new(0.0, 0.0, 6.0, 6.0));
+        let mut hole = vec![Point::new(2.0, 2.0), Point::new(2.0, 4.0), Point::new(4.0, 4.0), Point::new(4.0, 2.0)];
+        ring.move_to(hole.remove(0));
+        for p in hole {
+            ring.line_to(p);
+        }
+        ring.close();
+        let ring = Mask::new(&ring, clip);
+        assert_eq!((ring.get(1, 1), ring.get(2, 2), ring.get(3, 3)), (255, 0, 0));
+        assert!((total(&ring) - 32.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,315 @@
+use std::f32::consts::TAU;
+use std::str::FromStr;
+
+use crate::canvas::path::{Path, Point};
+use crate::canvas::transform::Transform;
+use crate::error::{Error, Result};
+
+/// Parses SVG path data, like `"M 0 0 h 10 a 5 5 0 0 1 0 10 Z"`. Every
+/// command is supported, absolute and relative.
+impl FromStr for Path {
+    type Err = Error;
+
+    fn from_str(data: &str) -> Result<Path> {
+        let mut parser = Parser {
+            data,
+            pos: 0,
+            path: Path::new(),
+            current: Point::default(),
+            start: Point::default(),
+            closed: false,
+            control: None,
+        };
+        parser.parse().map_err(|e| Error::from(format!("invalid path {:?}: {}", data, e)))?;
+        Ok(parser.path)
+    }
+}
+
+struct Parser<'a> {
+    data: &'a str,
+    pos: usize,
+    path: Path,
+    current: Point,
+    // Where the current contour started, and where it goes on after `Z`.
+    start: Point,
+    closed: bool,
+    // The last control point of the previous curve, with whether it was
+    // cubic, for `S` and `T` to mirror.
+    control: Option<(Point, bool)>,
+}
+
+impl Parser<'_> {
+    fn parse(&mut self) -> std::result::Result<(), String> {
+        let mut command = None;
+        loop {
+            self.skip_separators();
+            let c = match self.data[self.pos..].chars().next() {
+                Some(c) => c,
+                None => return Ok(()),
+            };
+            if c.is_ascii_alphabetic() {
+                self.pos += 1;
+                command = Some(c);
+            } else {
+                // Numbers without a command repeat the last one, with moves
+                // going on as lines.
+                command = match command {
+                    Some('M') => Some('L'),
+                    Some('m') => Some('l'),
+                    Some('Z' | 'z') | None => return Err(format!("expected a command at {}", self.pos)),
+                    c => c,
+                };
+            }
+            self.command(command.unwrap_or_default())?;
+        }
+    }
+
+    fn command(&mut self, command: char) -> std::result::Result<(), String> {
+        let relative = command.is_ascii_lowercase();
+        let origin = if relative { self.current } else { Point::default() };
+        let mut control = None;
+
+        if self.path.contours().is_empty() && !command.eq_ignore_ascii_case(&'m') {
+            return Err("expected a move to start with".to_string());
+        }
+        if command.eq_ignore_ascii_case(&'z') {
+            self.path.close();
+            self.current = self.start;
+            self.closed = true;
+            self.control = None;
+            return Ok(());
+        }
+        if command.eq_ignore_ascii_case(&'m') {
+            let p = origin + self.point()?;
+            self.path.move_to(p);
+            self.start = p;
+            self.closed = false;
+            self.current = p;
+            self.control = None;
+            return Ok(());
+        }
+
+        // Drawing after `Z` goes on from where the contour started.
+        if self.closed {
+            self.path.move_to(self.start);
+            self.closed = false;
+        }
+        let p = match command.to_ascii_lowercase() {
+            'l' => {
+                let p = origin + self.point()?;
+                self.path.line_to(p);
+                p
+            },
+            'h' => {
+                let p = Point::new(origin.x + self.number()?, self.current.y);
+                self.path.line_to(p);
+                p
+            },
+            'v' => {
+                let p = Point::new(self.current.x, origin.y + self.number()?);
+                self.path.line_to(p);
+                p
+            },
+            'c' => {
+                let (c1, c2, p) = (origin + self.point()?, origin + self.point()?, origin + self.point()?);
+                self.path.cubic_to(c1, c2, p);
+                control = Some((c2, true));
+                p
+            },
+            's' => {
+                let c1 = self.mirrored(true);
+                let (c2, p) = (origin + self.point()?, origin + self.point()?);
+                self.path.cubic_to(c1, c2, p);
+                control = Some((c2, true));
+                p
+            },
+            'q' => {
+                let (c, p) = (origin + self.point()?, origin + self.point()?);
+                self.path.quad_to(c, p);
+                control = Some((c, false));
+                p
+            },
+            't' => {
+                let c = self.mirrored(false);
+                let p = origin + self.point()?;
+                self.path.quad_to(c, p);
+                control = Some((c, false));
+                p
+            },
+            'a' => {
+                let (rx, ry, angle) = (self.number()?, self.number()?, self.number()?);
+                let (large, sweep) = (self.flag()?, self.flag()?);
+                let p = origin + self.point()?;
+                self.arc_to(rx, ry, angle.to_radians(), large, sweep, p);
+                p
+            },
+            _ => return Err(format!("unknown command {:?}", command)),
+        };
+        self.current = p;
+        self.control = control;
+        Ok(())
+    }
+
+    // The previous curve's last control point mirrored around the current
+    // point, or the current point if the previous command was no curve of
+    // the same degree.
+    fn mirrored(&self, cubic: bool) -> Point {
+        match self.control {
+            Some((c, was_cubic)) if was_cubic == cubic => self.current * 2.0 - c,
+            _ => self.current,
+        }
+    }
+
+    // Adds an elliptic arc to `p`, turned by `angle` radians, found from its
+    // end points as SVG describes.
+    fn arc_to(&mut self, rx: f32, ry: f32, angle: f32, large: bool, sweep: bool, p: Point) {
+        let (mut rx, mut ry) = (rx.abs(), ry.abs());
+        if p == self.current {
+            return;
+        }
+        if rx == 0.0 || ry == 0.0 {
+            self.path.line_to(p);
+            return;
+        }
+
+        // The start point in a frame centered between the ends and turned
+        // with the ellipse.
+        let (sin, cos) = angle.sin_cos();
+        let half = (self.current - p) * 0.5;
+        let x1 = cos * half.x + sin * half.y;
+        let y1 = -sin * half.x + cos * half.y;
+
+        // Radii too small to reach are scaled up until they just do.
+        let reach = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if reach > 1.0 {
+            rx *= reach.sqrt();
+            ry *= reach.sqrt();
+        }
+
+        let (rx2, ry2) = (rx * rx, ry * ry);
+        let den = rx2 * y1 * y1 + ry2 * x1 * x1;
+        let coef = ((rx2 * ry2 - den) / den).max(0.0).sqrt() * if large == sweep { -1.0 } else { 1.0 };
+        let (cx, cy) = (coef * rx * y1 / ry, -coef * ry * x1 / rx);
+        let mid = (self.current + p) * 0.5;
+        let center = Point::new(cos * cx - sin * cy + mid.x, sin * cx + cos * cy + mid.y);
+
+        let angle_of = |x: f32, y: f32| ((y - cy) / ry).atan2((x - cx) / rx);
+        let start = angle_of(x1, y1);
+        let mut delta = angle_of(-x1, -y1) - start;
+        if sweep && delta < 0.0 {
+            delta += TAU;
+        } else if !sweep && delta > 0.0 {
+            delta -= TAU;
+        }
+
+        let mut arc = Path::new();
+        arc.arc(Point::default(), rx, ry, start, delta);
+        let arc = arc.transformed(&Transform::rotate(angle).then(Transform::translate(center.x, center.y)));
+        for &q in arc.contours().iter().flat_map(|c| c.points.iter()) {
+            self.path.line_to(q);
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        let rest = &self.data[self.pos..];
+        self.pos += rest.len() - rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',').len();
+    }
+
+    fn point(&mut self) -> std::result::Result<Point, String> {
+        Ok(Point::new(self.number()?, self.number()?))
+    }
+
+    fn number(&mut self) -> std::result::Result<f32, String> {
+        self.skip_separators();
+        let bytes = self.data.as_bytes();
+        let start = self.pos;
+        let mut end = start;
+        let digits = |mut i: usize| {
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            i
+        };
+
+        if end < bytes.len() && matches!(bytes[end], b'+' | b'-') {
+            end += 1;
+        }
+        end = digits(end);
+        if end < bytes.len() && bytes[end] == b'.' {
+            end = digits(end + 1);
+        }
+        if end < bytes.len() && matches!(bytes[end], b'e' | b'E') {
+            let mut exponent = end + 1;
+            if exponent < bytes.len() && matches!(bytes[exponent], b'+' | b'-') {
+                exponent += 1;
+            }
+            if digits(exponent) > exponent {
+                end = digits(exponent);
+            }
+        }
+
+        let number = self.data[start..end].parse().map_err(|_| format!("expected a number at {}", start))?;
+        self.pos = end;
+        Ok(number)
+    }
+
+    // Arc flags may be written without separators, like `a 5 5 0 01 10 0`.
+    fn flag(&mut self) -> std::result::Result<bool, String> {
+        self.skip_separators();
+        let flag = match self.data.as_bytes().get(self.pos) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(format!("expected a flag at {}", self.pos)),
+        };
+        self.pos += 1;
+        Ok(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Rectangle;
+
+    fn close(a: Point, b: Point) -> bool {
+        (a - b).length() < 1e-3
+    }
+
+    #[test]
+    fn parses_commands() {
+        let path: Path = "M1,2 L3-4 h2 v.5e1 m-1 0 2 2z l1 1".parse().unwrap();
+        let contours = path.contours();
+        assert_eq!(contours.len(), 3);
+        assert_eq!(contours[0].points, vec![Point::new(1.0, 2.0), Point::new(3.0, -4.0), Point::new(5.0, -4.0), Point::new(5.0, 1.0)]);
+        assert_eq!(contours[1].points, vec![Point::new(4.0, 1.0), Point::new(6.0, 3.0)]);
+        assert!(contours[1].closed && !contours[0].closed);
+        assert_eq!(contours[2].points, vec![Point::new(4.0, 1.0), Point::new(5.0, 2.0)]);
+
+        let curves: Path = "M0 0 Q5 10 10 0 T20 0 C20 -5 30 -5 30 0 S40 5 40 0".parse().unwrap();
+        let b = curves.bounds().unwrap();
+        assert!((b.bottom() - 5.0).abs() < 0.1 && (b.y() + 5.0).abs() < 0.1 && b.right() == 40.0, "{:?}", b);
+
+        assert!("L 1 2".parse::<Path>().is_err());
+        assert!("M 1".parse::<Path>().is_err());
+        assert!("M 1 2 X".parse::<Path>().is_err());
+        assert!("".parse::<Path>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn finds_arc_centers() {
+        let half: Path = "M0 0 A5 5 0 0 1 10 0".parse().unwrap();
+        let b = half.bounds().unwrap();
+        assert!((b.y() + 5.0).abs() < 1e-3 && b.bottom().abs() < 1e-3, "{:?}", b);
+        assert!(close(*half.contours()[0].points.last().unwrap(), Point::new(10.0, 0.0)));
+
+        // The large arc the other way round, with radii too small to reach.
+        let large: Path = "M0 0 a1 1 0 1 0 10 0".parse().unwrap();
+        let b = large.bounds().unwrap();
+        assert!((b.bottom() - 5.0).abs() < 1e-3 && b.y().abs() < 1e-3, "{:?}", b);
+
+        let turned: Path = "M0 0 A10 5 90 0 1 0 20".parse().unwrap();
+        let b = turned.bounds().unwrap();
+        assert!(b.x().abs() < 1e-3 && (b.right() - 5.0).abs() < 1e-3, "{:?}", b);
+        assert_eq!("M0 0 A0 5 0 0 1 3 4".parse::<Path>().unwrap().bounds(), Some(Rectangle::new(0.0, 0.0, 3.0, 4.0)));
+    }
+}
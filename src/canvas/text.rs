@@ -0,0 +1,357 @@
+use rustybuzz::ttf_parser::{GlyphId, OutlineBuilder};
+use rustybuzz::UnicodeBuffer;
+use serde::Deserialize;
+
+use crate::canvas::font::Font;
+use crate::canvas::path::{signed_area, Path, Point, Stroke};
+use crate::geometry::Rectangle;
+
+const ELLIPSIS: &str = "\u{2026}";
+
+/// Where lines sit between the left and right edges.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Where the block of lines sits between the top and bottom edges.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VAlign {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// How shaped text is broken into lines and placed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextStyle {
+    pub align: Align,
+    pub valign: VAlign,
+    /// Break lines at spaces, or inside words that do not fit alone, to stay
+    /// within the width.
+    pub wrap: bool,
+    /// Cut lines that do not fit, and the last line when more lines do not
+    /// fit the height, with an ellipsis.
+    pub ellipsis: bool,
+    /// Factor of the font's own line height.
+    pub line_height: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> TextStyle {
+        TextStyle {
+            align: Align::Left,
+            valign: VAlign::Top,
+            wrap: false,
+            ellipsis: false,
+            line_height: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Glyph {
+    id: u16,
+    advance: f32,
+    offset: Point,
+    // A line may break after a space.
+    space: bool,
+}
+
+/// One line of placed glyphs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    glyphs: Vec<Glyph>,
+    /// Width without trailing spaces.
+    pub width: f32,
+}
+
+impl Line {
+    fn new(glyphs: &[Glyph]) -> Line {
+        let end = glyphs.iter().rposition(|g| !g.space).map_or(0, |i| i + 1);
+        Line {
+            width: glyphs[..end].iter().map(|g| g.advance).sum(),
+            glyphs: glyphs.to_vec(),
+        }
+    }
+}
+
+/// Text shaped with one font at one pixel size, one run of glyphs per
+/// paragraph.
+#[derive(Debug, Clone)]
+pub struct ShapedText {
+    font: Font,
+    size: f32,
+    paragraphs: Vec<Vec<Glyph>>,
+    ellipsis: Vec<Glyph>,
+}
+
+impl ShapedText {
+    pub fn new(font: &Font, size: f32, text: &str) -> ShapedText {
+        ShapedText {
+            font: font.clone(),
+            size,
+            paragraphs: text.split('\n').map(|p| shape(font, size, p.trim_end_matches('\r'))).collect(),
+            ellipsis: shape(font, size, ELLIPSIS),
+        }
+    }
+
+    fn scale(&self) -> f32 {
+        self.size / self.font.face().units_per_em() as f32
+    }
+
+    /// Distance from the top of a line to its baseline.
+    pub fn ascent(&self) -> f32 {
+        self.font.face().ascender() as f32 * self.scale()
+    }
+
+    pub fn line_height(&self, style: &TextStyle) -> f32 {
+        let face = self.font.face();
+        let height = (face.ascender() - face.descender() + face.line_gap()) as f32 * self.scale();
+        height * style.line_height
+    }
+
+    /// Breaks the text into lines for a box of `width` and `height`, either
+    /// unbounded if `None`.
+    pub fn lines(&self, style: &TextStyle, width: Option<f32>, height: Option<f32>) -> Vec<Line> {
+        let mut lines = Vec::new();
+        for glyphs in &self.paragraphs {
+            match width {
+                Some(width) if style.wrap => wrap(glyphs, width, &mut lines),
+                _ => lines.push(Line::new(glyphs)),
+            }
+        }
+
+        let width = match width {
+            Some(width) if style.ellipsis => width,
+            _ => return lines,
+        };
+        if let Some(height) = height {
+            let fitting = ((height / self.line_height(style)).floor() as usize).max(1);
+            if lines.len() > fitting {
+                lines.truncate(fitting);
+                let last = lines.pop().unwrap_or_else(|| Line::new(&[]));
+                lines.push(self.cut(&last, width, true));
+            }
+        }
+        lines.iter().map(|l| if l.width > width { self.cut(l, width, false) } else { l.clone() }).collect()
+    }
+
+    // Drops glyphs from the end of `line` until an ellipsis fits after it,
+    // adding the ellipsis if the line was cut or `always`.
+    fn cut(&self, line: &Line, width: f32, always: bool) -> Line {
+        let ellipsis: f32 = self.ellipsis.iter().map(|g| g.advance).sum();
+        let mut glyphs = line.glyphs.clone();
+        let mut cut = false;
+        while !glyphs.is_empty() && (Line::new(&glyphs).width + ellipsis > width || glyphs.last().is_some_and(|g| g.space)) {
+            glyphs.pop();
+            cut = true;
+        }
+        if cut || always {
+            glyphs.extend_from_slice(&self.ellipsis);
+        }
+        Line::new(&glyphs)
+    }
+
+    /// Width and height of `lines`.
+    pub fn extent(&self, lines: &[Line], style: &TextStyle) -> (f32, f32) {
+        let width = lines.iter().map(|l| l.width).fold(0.0, f32::max);
+        (width, lines.len() as f32 * self.line_height(style))
+    }
+
+    /// Outline of `lines` placed in `bounds`.
+    pub fn outline(&self, lines: &[Line], bounds: Rectangle<f32>, style: &TextStyle) -> Path {
+        let face = self.font.face();
+        let scale = self.scale();
+        let line_height = self.line_height(style);
+        let block = lines.len() as f32 * line_height;
+        let top = match style.valign {
+            VAlign::Top => bounds.y(),
+            VAlign::Middle => bounds.y() + (bounds.h() - block) / 2.0,
+            VAlign::Bottom => bounds.bottom() - block,
+        };
+
+        let mut path = Path::new();
+        for (i, line) in lines.iter().enumerate() {
+            let mut x = match style.align {
+                Align::Left => bounds.x(),
+                Align::Center => bounds.x() + (bounds.w() - line.width) / 2.0,
+                Align::Right => bounds.right() - line.width,
+            };
+            let baseline = top + i as f32 * line_height + self.ascent();
+            for glyph in &line.glyphs {
+                let origin = Point::new(x + glyph.offset.x, baseline - glyph.offset.y);
+                face.outline_glyph(GlyphId(glyph.id), &mut Outline { path: &mut path, origin, scale });
+                x += glyph.advance;
+            }
+        }
+        path
+    }
+}
+
+/// `outline` thickened by `width`, to embolden fonts without a bold face.
+pub fn embolden(outline: &Path, width: f32) -> Path {
+    let mut stroke = outline.stroke(&Stroke::new(width));
+    // Fonts wind their outer contours either way, the stroke has to match so
+    // the two add up instead of cancelling.
+    let winding: f32 = outline.contours().iter().map(|c| signed_area(&c.points)).sum();
+    if winding < 0.0 {
+        stroke.reverse();
+    }
+    let mut bold = outline.clone();
+    bold.extend(stroke);
+    bold
+}
+
+fn shape(font: &Font, size: f32, text: &str) -> Vec<Glyph> {
+    let face = font.face();
+    let scale = size / face.units_per_em() as f32;
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    let shaped = rustybuzz::shape(&face, &[], buffer);
+
+    shaped.glyph_infos().iter().zip(shaped.glyph_positions()).map(|(info, pos)| Glyph {
+        id: info.glyph_id as u16,
+        advance: pos.x_advance as f32 * scale,
+        offset: Point::new(pos.x_offset as f32 * scale, pos.y_offset as f32 * scale),
+        space: text[info.cluster as usize..].chars().next().is_some_and(char::is_whitespace),
+    }).collect()
+}
+
+// Greedy line breaking: as many words as fit, words wider than a line on
+// their own broken between glyphs. Spaces at the start of wrapped lines are
+// dropped.
+fn wrap(glyphs: &[Glyph], width: f32, lines: &mut Vec<Line>) {
+    let mut start = 0;
+    loop {
+        let mut used = 0.0;
+        let mut last_break = None;
+        let mut i = start;
+        while i < glyphs.len() {
+            if i > start && !glyphs[i].space && used + glyphs[i].advance > width {
+                break;
+            }
+            used += glyphs[i].advance;
+            if glyphs[i].space {
+                last_break = Some(i + 1);
+            }
+            i += 1;
+        }
+
+        if i == glyphs.len() {
+            lines.push(Line::new(&glyphs[start..]));
+            return;
+        }
+        let end = last_break.unwrap_or(i);
+        lines.push(Line::new(&glyphs[start..end]));
+        start = end;
+        while start < glyphs.len() && glyphs[start].space {
+            start += 1;
+        }
+    }
+}
+
+// Adds glyph outlines, given in font units with y pointing up, to a path.
+struct Outline<'a> {
+    path: &'a mut Path,
+    origin: Point,
+    scale: f32,
+}
+
+impl Outline<'_> {
+    fn point(&self, x: f32, y: f32) -> Point {
+        Point::new(self.origin.x + x * self.scale, self.origin.y - y * self.scale)
+    }
+}
+
+impl OutlineBuilder for Outline<'_> {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.path.move_to(p);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.path.line_to(p);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (c, p) = (self.point(x1, y1), self.point(x, y));
+        self.path.quad_to(c, p);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (c1, c2, p) = (self.point(x1, y1), self.point(x2, y2), self.point(x, y));
+        self.path.cubic_to(c1, c2, p);
+    }
+
+    fn close(&mut self) {
+        self.path.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::font::tests::fixtures;
+
+    fn text(s: &str) -> ShapedText {
+        ShapedText::new(&Font::load(fixtures().join("DejaVuSans.ttf"), 0).unwrap(), 20.0, s)
+    }
+
+    fn style(wrap: bool, ellipsis: bool) -> TextStyle {
+        TextStyle { wrap, ellipsis, ..TextStyle::default() }
+    }
+
+    #[test]
+    fn wraps_at_spaces_then_inside_words() {
+        let one = text("one").lines(&style(false, false), None, None)[0].width;
+        let shaped = text("one two three\nfour");
+        assert_eq!(shaped.lines(&style(false, false), Some(10.0), None).len(), 2);
+
+        let lines = shaped.lines(&style(true, false), Some(one * 2.6), None);
+        assert_eq!(lines.iter().map(|l| l.glyphs.len()).collect::<Vec<_>>(), vec![8, 5, 4]);
+        assert!(lines.iter().all(|l| l.width <= one * 2.6));
+
+        let lines = text("abcdefgh").lines(&style(true, false), Some(one), None);
+        assert!(lines.len() > 2 && lines.iter().all(|l| l.width <= one), "{:?}", lines);
+    }
+
+    #[test]
+    fn cuts_with_ellipsis() {
+        let shaped = text("a rather long line of text");
+        let full = shaped.lines(&style(false, false), None, None)[0].width;
+
+        let lines = shaped.lines(&style(false, true), Some(full / 2.0), None);
+        assert!(lines[0].width <= full / 2.0 && lines[0].width > full / 3.0);
+        assert_eq!(lines[0].glyphs.last().map(|g| g.id), shaped.ellipsis.first().map(|g| g.id));
+
+        let line_height = shaped.line_height(&TextStyle::default());
+        let lines = shaped.lines(&style(true, true), Some(full / 3.0), Some(line_height * 2.5));
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].glyphs.last().map(|g| g.id), shaped.ellipsis.first().map(|g| g.id));
+    }
+
+    #[test]
+    fn places_lines() {
+        let shaped = text("ab\nabcd");
+        let s = TextStyle { align: Align::Right, valign: VAlign::Bottom, ..TextStyle::default() };
+        let lines = shaped.lines(&s, None, None);
+        let (w, h) = shaped.extent(&lines, &s);
+        assert_eq!(w, lines[1].width);
+
+        let outline = shaped.outline(&lines, Rectangle::new(0.0, 0.0, 100.0, 100.0), &s);
+        let b = outline.bounds().unwrap();
+        assert!(b.right() <= 100.0 && b.right() > 98.0, "{:?}", b);
+        assert!(b.y() >= 100.0 - h && b.bottom() <= 100.0, "{:?}", b);
+
+        let bold = embolden(&outline, 2.0);
+        assert!(bold.bounds().unwrap().w() > b.w());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::canvas::path::Point;
+
+/// An affine transform, mapping `(x, y)` to
+/// `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    pub fn translate(x: f32, y: f32) -> Transform {
+        Transform { e: x, f: y, ..Transform::IDENTITY }
+    }
+
+    pub fn scale(x: f32, y: f32) -> Transform {
+        Transform { a: x, d: y, ..Transform::IDENTITY }
+    }
+
+    /// Skew by `x` radians along the x axis and `y` radians along the y
+    /// axis, like SVG's `skewX` and `skewY`.
+    pub fn skew(x: f32, y: f32) -> Transform {
+        Transform { b: y.tan(), c: x.tan(), ..Transform::IDENTITY }
+    }
+
+    /// Rotation by `angle` radians around the origin, clockwise on screen.
+    pub fn rotate(angle: f32) -> Transform {
+        let (sin, cos) = angle.sin_cos();
+        Transform { a: cos, b: sin, c: -sin, d: cos, e: 0.0, f: 0.0 }
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(self, next: Transform) -> Transform {
+        Transform {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    pub fn apply(&self, p: Point) -> Point {
+        Point::new(self.a * p.x + self.c * p.y + self.e, self.b * p.x + self.d * p.y + self.f)
+    }
+
+    /// The transform undoing this one, `None` if it flattens the plane.
+    pub fn invert(&self) -> Option<Transform> {
+        let det = self.a * self.d - self.b * self.c;
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let (a, b, c, d) = (self.d / det, -self.b / det, -self.c / det, self.a / det);
+        Some(Transform {
+            a,
+            b,
+            c,
+            d,
+            e: -(a * self.e + c * self.f),
+            f: -(b * self.e + d * self.f),
+        })
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::IDENTITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    fn close(a: Point, b: Point) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    #[test]
+    fn composes_and_inverts() {
+        let t = Transform::translate(-1.0, 0.0).then(Transform::rotate(FRAC_PI_2)).then(Transform::scale(2.0, 3.0));
+        assert!(close(t.apply(Point::new(3.0, 0.0)), Point::new(0.0, 6.0)));
+        assert!(close(t.invert().unwrap().apply(Point::new(0.0, 6.0)), Point::new(3.0, 0.0)));
+        assert!(Transform::scale(0.0, 1.0).invert().is_none());
+
+        let skew = Transform::skew(FRAC_PI_4, 0.0);
+        assert!(close(skew.apply(Point::new(0.0, 1.0)), Point::new(1.0, 1.0)));
+        assert!(close(skew.apply(Point::new(1.0, 0.0)), Point::new(1.0, 0.0)));
+    }
+}
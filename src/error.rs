@@ -1,6 +1,7 @@
-use winapi::shared::windef::HWND;
+use std::{error, fmt, io};
+use std::path::{Path, PathBuf};
 
-use std::{error, fmt};
+use crate::platform::WindowId;
 
 pub type ExtError = Box<dyn error::Error + Send + Sync + 'static>;
 
@@ -8,30 +9,82 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
-    WindowFound((HWND, u32)),
-    NoWindowFound((HWND, u32)),
+    WindowFound((WindowId, u32)),
+    NoWindowFound((WindowId, u32)),
     Unimplemented,
+    Parse {
+        path: Option<PathBuf>,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Io(io::Error),
     SysError(String),
     Error(ExtError)
 }
 
-impl error::Error for Error {}
+impl Error {
+    /// Builds a `Parse` error for the byte `offset` into `source`, moved back
+    /// to the start of its character. Line and column are 1 based.
+    pub fn parse<S: Into<String>>(source: &str, offset: usize, message: S) -> Error {
+        let mut offset = offset.min(source.len());
+        while !source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = match before.rfind('\n') {
+            Some(i) => before[i + 1..].chars().count() + 1,
+            None => before.chars().count() + 1,
+        };
+
+        Error::Parse {
+            path: None,
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    pub fn from_toml(source: &str, err: toml::de::Error) -> Error {
+        let offset = err.span().map(|s| s.start).unwrap_or(0);
+        Error::parse(source, offset, err.message())
+    }
+
+    /// Attaches the file a `Parse` error came from. Other errors are returned
+    /// untouched.
+    pub fn with_path<P: AsRef<Path>>(self, file: P) -> Error {
+        match self {
+            Error::Parse { path: None, line, column, message } => Error::Parse {
+                path: Some(file.as_ref().to_path_buf()),
+                line,
+                column,
+                message,
+            },
+            e => e,
+        }
+    }
+}
 
-unsafe impl Send for Error {}
-unsafe impl Sync for Error {}
+impl error::Error for Error {}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::WindowFound(ref key) => {
-                write!(f, "window already exists in handler and over written: ({:?}, {})", key.0, key.1)
+                write!(f, "window already exists in handler and over written: ({}, {})", key.0, key.1)
             }
             Error::NoWindowFound(ref key) => {
-                write!(f, "no window found for provided keys in handler: ({:?}, {})", key.0, key.1)
+                write!(f, "no window found for provided keys in handler: ({}, {})", key.0, key.1)
             },
             Error::Unimplemented => {
                 write!(f, "unimplemented")
             },
+            Error::Parse { ref path, line, column, ref message } => match path {
+                Some(p) => write!(f, "{}:{}:{}: {}", p.display(), line, column, message),
+                None => write!(f, "{}:{}: {}", line, column, message),
+            },
+            Error::Io(ref e) => write!(f, "IO error: {}", e),
             Error::SysError(ref e) => write!(f, "{}", e),
             Error::Error(ref e) => write!(f, "Error: {}", e),
         }
@@ -54,4 +107,10 @@ impl From<u32> for Error {
     fn from(err: u32) -> Error {
         Error::SysError(format!("System error: {}", err))
     }
-}
\ No newline at end of file
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
@@ -2,14 +2,11 @@ use std::collections::HashMap;
 use std::cell::RefCell;
 use std::sync::mpsc::{Sender, Receiver, channel};
 
-use winapi::{
-    shared::windef::HWND,
-};
-
+use crate::platform::WindowId;
 use crate::window::Window;
 use crate::error::{Error, Result};
 
-thread_local!(pub static EVENT_HANDLER: RefCell<Option<Handler>> = RefCell::new(None));
+thread_local!(pub static EVENT_HANDLER: RefCell<Option<Handler>> = const { RefCell::new(None) });
 
 pub type Callback = Box<dyn Fn() -> Result<()> + Send + Sync + 'static>;
 
@@ -20,13 +17,44 @@ where
     Box::new(f)
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    Escape,
+    Char(char),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    Click { window: WindowId, button: MouseButton, x: i32, y: i32 },
+    Key { window: WindowId, key: Key },
+    /// The user finished dragging a window to a new screen position.
+    Moved { window: WindowId, x: i32, y: i32 },
+    Destroyed(WindowId),
+}
+
+/// Sends `event` through the current thread's handler, if one is installed.
+pub fn dispatch(event: Event) {
+    EVENT_HANDLER.with(|handler| {
+        if let Some(h) = handler.borrow().as_ref() {
+            h.sender.send(event).ok();
+        }
+    });
+}
+
 pub struct Handler {
-    pub events: HashMap<(HWND, u32), Window>,
-    pub sender: Sender<HWND>,
+    pub events: HashMap<(WindowId, u32), Window>,
+    pub sender: Sender<Event>,
 }
 
 impl Handler {
-    pub fn new() -> (Handler, Receiver<HWND>) {
+    pub fn new() -> (Handler, Receiver<Event>) {
         let(sender, receiver) = channel();
         (Handler {
             events: HashMap::new(),
@@ -34,29 +62,29 @@ impl Handler {
         }, receiver)
     }
 
-    pub fn insert<F>(&mut self, hwnd: HWND, item_index: u32, window: Window) -> Result<()> {  
-        match self.events.insert((hwnd, item_index), window) {
-            Some(_) => Err(Error::WindowFound((hwnd, item_index))),
+    pub fn insert(&mut self, id: WindowId, item_index: u32, window: Window) -> Result<()> {  
+        match self.events.insert((id, item_index), window) {
+            Some(_) => Err(Error::WindowFound((id, item_index))),
             None => Ok(())
         }
     }
 
-    pub fn remove(&mut self, hwnd: HWND, item_index: u32) -> Result<()> {
-        match self.events.remove(&(hwnd, item_index)) {
+    pub fn remove(&mut self, id: WindowId, item_index: u32) -> Result<()> {
+        match self.events.remove(&(id, item_index)) {
             Some(_) => Ok(()),
-            None => Err(Error::NoWindowFound((hwnd, item_index)))
+            None => Err(Error::NoWindowFound((id, item_index)))
         }
     }
 
-    pub fn run(&mut self, hwnd: HWND, item_index: u32) -> Result<()> {
-        match self.events.get_mut(&(hwnd, item_index)) {
+    pub fn run(&mut self, id: WindowId, item_index: u32) -> Result<()> {
+        match self.events.get_mut(&(id, item_index)) {
             Some(w) => {
                 if let Some(f) = &w.callback {
                     return f()
                 }
                 Ok(())
             },
-            None => Err(Error::NoWindowFound((hwnd, item_index))),
+            None => Err(Error::NoWindowFound((id, item_index))),
         }
     }
-}
\ No newline at end of file
+}
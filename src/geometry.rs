@@ -1,4 +1,6 @@
-#[derive(Debug, Copy, Clone)]
+use std::ops::Add;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Rectangle<T> {
     position: [T; 2],
     wh: [T; 2],
@@ -27,4 +29,19 @@ where T: Copy
     pub fn h(self) -> T {
         self.wh[1]
     }
-}
\ No newline at end of file
+}
+
+impl<T> Rectangle<T>
+where T: Copy + PartialOrd + Add<Output = T>
+{
+    pub fn right(self) -> T {
+        self.x() + self.w()
+    }
+    pub fn bottom(self) -> T {
+        self.y() + self.h()
+    }
+
+    pub fn contains(self, x: T, y: T) -> bool {
+        x >= self.x() && x < self.right() && y >= self.y() && y < self.bottom()
+    }
+}
@@ -0,0 +1,199 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::library::SkinRef;
+use crate::platform::ZPosition;
+use crate::util;
+use crate::error::{Error, Result};
+
+/// Saved state of one active skin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutEntry {
+    pub skin: String,
+    pub variant: String,
+    pub position: [i32; 2],
+    pub alpha: u8,
+    #[serde(default)]
+    pub z: ZPosition,
+    #[serde(default)]
+    pub locked: bool,
+}
+
+impl LayoutEntry {
+    pub fn source(&self) -> SkinRef {
+        SkinRef::new(&self.skin, &self.variant)
+    }
+}
+
+/// The set of active skins and where they are on screen.
+///
+/// ```toml
+/// [[skin]]
+/// skin = "Clock"
+/// variant = "small"
+/// position = [50, 50]
+/// alpha = 255
+/// z = "topmost"
+/// locked = false
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layout {
+    #[serde(default, rename = "skin")]
+    pub skins: Vec<LayoutEntry>,
+}
+
+impl Layout {
+    /// Reads a layout file. A missing file is an empty layout.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Layout> {
+        let path = path.as_ref();
+        let source = match fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Layout::default()),
+            Err(e) => return Err(Error::from(e)),
+        };
+        toml::from_str(&source).map_err(|e| Error::from_toml(&source, e).with_path(path))
+    }
+
+    /// Writes the layout to a temporary file next to `path` and renames it
+    /// into place, so a crash never leaves a half written layout behind.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let contents = toml::to_string(self).map_err(|e| Error::from(e.to_string()))?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn entry(&self, source: &SkinRef) -> Option<&LayoutEntry> {
+        self.skins.iter().find(|e| e.skin == source.skin && e.variant == source.variant)
+    }
+}
+
+/// Where layouts live on disk: the current state in `layout.toml` and named
+/// layouts in `layouts/<name>.toml`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutStore {
+    dir: PathBuf,
+}
+
+impl LayoutStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> LayoutStore {
+        LayoutStore {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Store in luna's configuration directory.
+    pub fn default_store() -> Option<LayoutStore> {
+        util::config_dir().map(LayoutStore::new)
+    }
+
+    pub fn state_path(&self) -> PathBuf {
+        self.dir.join("layout.toml")
+    }
+
+    pub fn load_state(&self) -> Result<Layout> {
+        Layout::load(self.state_path())
+    }
+
+    pub fn save_state(&self, layout: &Layout) -> Result<()> {
+        layout.save(self.state_path())
+    }
+
+    pub fn named_path(&self, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == ' ');
+        if !valid {
+            return Err(Error::from(format!("invalid layout name: {:?}", name)));
+        }
+        Ok(self.dir.join("layouts").join(format!("{}.toml", name)))
+    }
+
+    pub fn load_named(&self, name: &str) -> Result<Layout> {
+        let path = self.named_path(name)?;
+        if !path.is_file() {
+            return Err(Error::from(format!("no layout named {:?}", name)));
+        }
+        Layout::load(path)
+    }
+
+    pub fn save_named(&self, name: &str, layout: &Layout) -> Result<()> {
+        layout.save(self.named_path(name)?)
+    }
+
+    /// Names of the saved layouts, sorted.
+    pub fn names(&self) -> Result<Vec<String>> {
+        let dir = self.dir.join("layouts");
+        let mut names = Vec::new();
+        if dir.is_dir() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("toml") {
+                    continue;
+                }
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    names.push(stem.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout() -> Layout {
+        Layout {
+            skins: vec![LayoutEntry {
+                skin: "Clock".to_string(),
+                variant: "small".to_string(),
+                position: [10, -20],
+                alpha: 200,
+                z: ZPosition::Topmost,
+                locked: true,
+            }],
+        }
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LayoutStore::new(dir.path());
+
+        assert_eq!(store.load_state().unwrap(), Layout::default());
+
+        store.save_state(&layout()).unwrap();
+        assert_eq!(store.load_state().unwrap(), layout());
+        assert!(!dir.path().join("layout.toml.tmp").exists());
+        assert!(layout().entry(&SkinRef::new("Clock", "small")).is_some());
+    }
+
+    #[test]
+    fn named_layouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LayoutStore::new(dir.path());
+
+        store.save_named("work", &layout()).unwrap();
+        store.save_named("home", &Layout::default()).unwrap();
+
+        assert_eq!(store.names().unwrap(), vec!["home", "work"]);
+        assert_eq!(store.load_named("work").unwrap(), layout());
+        assert!(store.load_named("play").is_err());
+        assert!(store.save_named("../escape", &layout()).is_err());
+    }
+}
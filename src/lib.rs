@@ -3,11 +3,21 @@
 pub mod app;
 pub mod window;
 pub mod tray;
+#[cfg(windows)]
 pub mod tray_old;
 pub mod event;
+pub mod platform;
 pub mod geometry;
 pub mod util;
 pub mod error;
+pub mod skin;
+pub mod library;
+pub mod layout;
+pub mod measure;
+pub mod meter;
+pub mod canvas;
+pub mod variables;
+pub mod action;
 
 #[cfg(test)]
 mod tests {
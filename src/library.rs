@@ -0,0 +1,278 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::Deserialize;
+
+use crate::util;
+use crate::error::{Error, Result};
+
+/// Name of the optional per skin manifest. Every other `.toml` file in a skin
+/// directory is a variant.
+pub const MANIFEST: &str = "manifest.toml";
+
+/// Metadata shipped alongside a skin for the library.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    /// Preview image, relative to the skin directory.
+    pub preview: Option<PathBuf>,
+}
+
+impl Manifest {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Manifest> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        toml::from_str(&source).map_err(|e| Error::from_toml(&source, e).with_path(path))
+    }
+}
+
+/// `<SkinName>/<variant>` reference to a skin in the library.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SkinRef {
+    pub skin: String,
+    pub variant: String,
+}
+
+impl SkinRef {
+    pub fn new(skin: &str, variant: &str) -> SkinRef {
+        SkinRef {
+            skin: skin.to_string(),
+            variant: variant.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SkinRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.skin, self.variant)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkinEntry {
+    /// Directory name, used to activate the skin.
+    pub name: String,
+    pub path: PathBuf,
+    pub manifest: Manifest,
+    /// Sorted by name.
+    pub variants: Vec<Variant>,
+}
+
+impl SkinEntry {
+    pub fn variant(&self, name: &str) -> Option<&Variant> {
+        self.variants.iter().find(|v| v.name == name)
+    }
+
+    /// Name to show to the user, the manifest name if there is one.
+    pub fn display_name(&self) -> &str {
+        self.manifest.name.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn preview(&self) -> Option<PathBuf> {
+        self.manifest.preview.as_ref().map(|p| self.path.join(p))
+    }
+}
+
+/// Index of the skins found under a skins root laid out as
+/// `<root>/<SkinName>/<variant>.toml`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SkinLibrary {
+    root: PathBuf,
+    skins: Vec<SkinEntry>,
+}
+
+impl SkinLibrary {
+    /// Scans `root` for skins. A missing root is an empty library, a skin
+    /// with a broken manifest is still listed with empty metadata, and skin
+    /// directories that can't be read are skipped.
+    pub fn scan<P: AsRef<Path>>(root: P) -> Result<SkinLibrary> {
+        let root = root.as_ref();
+        let mut skins = Vec::new();
+
+        if root.is_dir() {
+            for entry in fs::read_dir(root)? {
+                let path = entry?.path();
+                if !path.is_dir() {
+                    continue;
+                }
+                match scan_skin(&path) {
+                    Ok(Some(skin)) => skins.push(skin),
+                    Ok(None) => (),
+                    Err(e) => warn!("skipping skin {}: {}", path.display(), e),
+                }
+            }
+        }
+        skins.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(SkinLibrary {
+            root: root.to_path_buf(),
+            skins,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn skins(&self) -> &[SkinEntry] {
+        &self.skins
+    }
+
+    pub fn skin(&self, name: &str) -> Option<&SkinEntry> {
+        self.skins.iter().find(|s| s.name == name)
+    }
+
+    /// Looks up a variant by skin and variant name. Without a variant name the
+    /// skin's first variant is used.
+    pub fn variant(&self, skin: &str, variant: Option<&str>) -> Option<&Variant> {
+        let skin = self.skin(skin)?;
+        match variant {
+            Some(name) => skin.variant(name),
+            None => skin.variants.first(),
+        }
+    }
+
+    /// Like `variant`, but returns the full reference or a descriptive error.
+    pub fn resolve(&self, skin: &str, variant: Option<&str>) -> Result<(SkinRef, &Variant)> {
+        match self.variant(skin, variant) {
+            Some(v) => Ok((SkinRef::new(skin, &v.name), v)),
+            None => Err(Error::from(format!(
+                "no skin {}/{} in {}", skin, variant.unwrap_or("*"), self.root.display()
+            ))),
+        }
+    }
+}
+
+fn scan_skin(path: &Path) -> Result<Option<SkinEntry>> {
+    let name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(n) => n.to_string(),
+        None => return Ok(None),
+    };
+
+    let mut manifest = Manifest::default();
+    let mut variants = Vec::new();
+
+    for entry in fs::read_dir(path)? {
+        let file = entry?.path();
+        if !file.is_file() || file.extension().and_then(|e| e.to_str()) != Some("toml") {
+            continue;
+        }
+
+        if file.file_name().and_then(|n| n.to_str()) == Some(MANIFEST) {
+            manifest = Manifest::load(&file).unwrap_or_else(|e| {
+                warn!("ignoring skin manifest: {}", e);
+                Manifest::default()
+            });
+            continue;
+        }
+
+        if let Some(stem) = file.file_stem().and_then(|s| s.to_str()) {
+            variants.push(Variant {
+                name: stem.to_string(),
+                path: file.clone(),
+            });
+        }
+    }
+
+    if variants.is_empty() {
+        return Ok(None);
+    }
+    variants.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(Some(SkinEntry {
+        name,
+        path: path.to_path_buf(),
+        manifest,
+        variants,
+    }))
+}
+
+/// Skins root for the current user, `skins` in the config directory.
+pub fn default_root() -> Option<PathBuf> {
+    util::config_dir().map(|c| c.join("skins"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, file: &str, contents: &str) {
+        let path = root.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn scans_skins_and_variants() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "Clock/small.toml", "");
+        write(dir.path(), "Clock/large.toml", "");
+        write(dir.path(), "Clock/preview.png", "");
+        write(dir.path(), "Clock/manifest.toml", r#"
+            name = "Big Clock"
+            author = "luna"
+            version = "1.0.0"
+            description = "A clock"
+            preview = "preview.png"
+        "#);
+        write(dir.path(), "Broken/manifest.toml", "name = 1");
+        write(dir.path(), "Broken/main.toml", "");
+        write(dir.path(), "Empty/readme.txt", "");
+        write(dir.path(), "stray.toml", "");
+
+        let library = SkinLibrary::scan(dir.path()).unwrap();
+        let names: Vec<_> = library.skins().iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Broken", "Clock"]);
+
+        let clock = library.skin("Clock").unwrap();
+        assert_eq!(clock.display_name(), "Big Clock");
+        assert_eq!(clock.manifest.author.as_deref(), Some("luna"));
+        assert_eq!(clock.preview(), Some(dir.path().join("Clock/preview.png")));
+        let variants: Vec<_> = clock.variants.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(variants, vec!["large", "small"]);
+
+        assert_eq!(library.variant("Clock", None).unwrap().name, "large");
+        assert_eq!(library.variant("Clock", Some("small")).unwrap().path, dir.path().join("Clock/small.toml"));
+        assert!(library.variant("Clock", Some("medium")).is_none());
+
+        assert_eq!(library.skin("Broken").unwrap().manifest, Manifest::default());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_unreadable_skins() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "Clock/main.toml", "");
+        write(dir.path(), "Locked/main.toml", "");
+        let locked = dir.path().join("Locked");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // Permissions don't stop root.
+        let readable = fs::read_dir(&locked).is_ok();
+
+        let library = SkinLibrary::scan(dir.path());
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        let names: Vec<_> = library.unwrap().skins().iter().map(|s| s.name.clone()).collect();
+        assert_eq!(names, if readable { vec!["Clock", "Locked"] } else { vec!["Clock"] });
+    }
+
+    #[test]
+    fn missing_root_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = SkinLibrary::scan(dir.path().join("nope")).unwrap();
+        assert!(library.skins().is_empty());
+    }
+}
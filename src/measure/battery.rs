@@ -0,0 +1,282 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::measure::{self, Measure, MeasureValue, time};
+use crate::skin::MeasureDefinition;
+use crate::error::{Error, Result};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    Charging,
+    Discharging,
+    Full,
+    NotCharging,
+    Unknown,
+}
+
+impl Status {
+    fn parse(s: &str) -> Status {
+        match s {
+            "Charging" => Status::Charging,
+            "Discharging" => Status::Discharging,
+            "Full" => Status::Full,
+            "Not charging" => Status::NotCharging,
+            _ => Status::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Status::Charging => "Charging",
+            Status::Discharging => "Discharging",
+            Status::Full => "Full",
+            Status::NotCharging => "Not charging",
+            Status::Unknown => "Unknown",
+        };
+        f.write_str(s)
+    }
+}
+
+/// State of one battery in `/sys/class/power_supply`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryInfo {
+    pub name: String,
+    pub status: Status,
+    /// Charge in percent.
+    pub capacity: f64,
+    /// Seconds until empty when discharging or full when charging, if the
+    /// battery reports its power draw.
+    pub remaining: Option<u64>,
+}
+
+/// Everything the measure needs from the power supply class.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PowerSupplies {
+    /// System batteries sorted by name. Peripheral batteries are left out.
+    pub batteries: Vec<BatteryInfo>,
+    pub ac_online: bool,
+}
+
+fn attribute(dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(dir.join(name)).ok().map(|s| s.trim().to_string())
+}
+
+fn number(dir: &Path, name: &str) -> Option<f64> {
+    attribute(dir, name).and_then(|s| s.parse().ok())
+}
+
+/// Reads the supplies under `root`, usually `/sys/class/power_supply`.
+pub fn read_supplies(root: &Path) -> Result<PowerSupplies> {
+    let mut supplies = PowerSupplies::default();
+
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(root)? {
+        dirs.push(entry?.path());
+    }
+    dirs.sort();
+
+    for dir in dirs {
+        let name = dir.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string();
+        match attribute(&dir, "type").as_deref() {
+            Some("Mains") | Some("USB") => {
+                supplies.ac_online |= attribute(&dir, "online").as_deref() == Some("1");
+            },
+            Some("Battery") => {
+                // Mice, headsets and the like report a `Device` scope.
+                if attribute(&dir, "scope").as_deref() == Some("Device") || attribute(&dir, "present").as_deref() == Some("0") {
+                    continue;
+                }
+                supplies.batteries.push(read_battery(&dir, name));
+            },
+            _ => {},
+        }
+    }
+
+    Ok(supplies)
+}
+
+fn read_battery(dir: &Path, name: String) -> BatteryInfo {
+    let status = Status::parse(&attribute(dir, "status").unwrap_or_default());
+
+    // Batteries report either energy (µWh, µW) or charge (µAh, µA). Only the
+    // ratios matter, so either works.
+    let level = match (number(dir, "energy_now"), number(dir, "energy_full")) {
+        (Some(now), Some(full)) => Some((now, full, number(dir, "power_now"))),
+        _ => match (number(dir, "charge_now"), number(dir, "charge_full")) {
+            (Some(now), Some(full)) => Some((now, full, number(dir, "current_now"))),
+            _ => None,
+        },
+    };
+
+    let capacity = number(dir, "capacity")
+        .or_else(|| level.and_then(|(now, full, _)| if full > 0.0 { Some(now / full * 100.0) } else { None }))
+        .unwrap_or(0.0)
+        .clamp(0.0, 100.0);
+
+    let remaining = level.and_then(|(now, full, rate)| {
+        let rate = rate.filter(|r| *r > 0.0)?;
+        let hours = match status {
+            Status::Discharging => now / rate,
+            Status::Charging => (full - now).max(0.0) / rate,
+            _ => return None,
+        };
+        Some((hours * 3600.0).round() as u64)
+    });
+
+    BatteryInfo {
+        name,
+        status,
+        capacity,
+        remaining,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Value {
+    Percent,
+    Charging,
+    Ac,
+    Remaining,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    /// Battery to report, like `BAT0`. The first one found if unset.
+    battery: Option<String>,
+    #[serde(default = "default_value")]
+    value: Value,
+    #[serde(default = "default_root")]
+    root: PathBuf,
+}
+
+fn default_value() -> Value {
+    Value::Percent
+}
+
+// Seconds the time remaining ranges over.
+const FULL_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+fn default_root() -> PathBuf {
+    PathBuf::from("/sys/class/power_supply")
+}
+
+/// Battery charge and power state. `sub` holds the charge percent, whether it
+/// is charging (with the status as the string), whether AC power is online
+/// and the estimated seconds remaining, in that order. `value` picks which
+/// of them is the main reading. The time remaining ranges over a day, so
+/// bars fill up to 24 hours left.
+///
+/// ```toml
+/// [[measure]]
+/// name = "Battery"
+/// type = "battery"
+/// battery = "BAT0"
+/// value = "remaining"
+/// ```
+pub struct Battery {
+    battery: Option<String>,
+    value: Value,
+    root: PathBuf,
+}
+
+impl Battery {
+    pub fn new<P: AsRef<Path>>(battery: Option<&str>, value: Value, root: P) -> Battery {
+        Battery {
+            battery: battery.map(str::to_string),
+            value,
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn from_definition(def: &MeasureDefinition) -> Result<Battery> {
+        let options: Options = measure::options(def)?;
+        Ok(Battery::new(options.battery.as_deref(), options.value, options.root))
+    }
+
+    pub fn sample(&self, supplies: &PowerSupplies) -> Result<MeasureValue> {
+        let battery = match self.battery {
+            Some(ref name) => supplies.batteries.iter().find(|b| &b.name == name),
+            None => supplies.batteries.first(),
+        };
+        let battery = battery.ok_or_else(|| match self.battery {
+            Some(ref name) => Error::from(format!("no battery {}", name)),
+            None => Error::from("no battery found".to_string()),
+        })?;
+
+        let flag = |on: bool| if on { 1.0 } else { 0.0 };
+        let remaining = match battery.remaining {
+            Some(s) => MeasureValue::new(s as f64, 0.0, FULL_DAY).with_string(time::format_duration("%h:%M", s as i64)),
+            None => MeasureValue::new(0.0, 0.0, FULL_DAY).with_string(""),
+        };
+
+        let sub = vec![
+            MeasureValue::new(battery.capacity, 0.0, 100.0).with_string(format!("{}%", battery.capacity.round())),
+            MeasureValue::new(flag(battery.status == Status::Charging), 0.0, 1.0).with_string(battery.status.to_string()),
+            MeasureValue::new(flag(supplies.ac_online), 0.0, 1.0),
+            remaining,
+        ];
+
+        let main = match self.value {
+            Value::Percent => &sub[0],
+            Value::Charging => &sub[1],
+            Value::Ac => &sub[2],
+            Value::Remaining => &sub[3],
+        };
+        Ok(main.clone().with_sub(sub))
+    }
+}
+
+impl Measure for Battery {
+    fn update(&mut self) -> Result<MeasureValue> {
+        let supplies = read_supplies(&self.root)?;
+        self.sample(&supplies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/power_supply").join(name)
+    }
+
+    #[test]
+    fn discharging_energy() {
+        let supplies = read_supplies(&fixture("discharging")).unwrap();
+        assert!(!supplies.ac_online);
+        assert_eq!(supplies.batteries, vec![BatteryInfo {
+            name: "BAT0".to_string(),
+            status: Status::Discharging,
+            capacity: 75.0,
+            remaining: Some(9000),
+        }]);
+
+        let value = Battery::new(None, Value::Percent, fixture("discharging")).sample(&supplies).unwrap();
+        assert_eq!(value.string, "75%");
+        assert_eq!(value.sub[1].string, "Discharging");
+        assert_eq!(value.sub[3].string, "2:30");
+    }
+
+    #[test]
+    fn charging_from_charge_counters() {
+        let mut battery = Battery::new(Some("BAT1"), Value::Remaining, fixture("charging"));
+        let value = battery.update().unwrap();
+        assert_eq!(value.number, 7200.0);
+        assert_eq!(value.percent(), 7200.0 / 86400.0);
+        assert_eq!(value.sub[0].number, 37.5);
+        assert_eq!(value.sub[1].number, 1.0);
+        assert_eq!(value.sub[2].number, 1.0);
+
+        // The mouse battery is not a system battery.
+        assert!(Battery::new(Some("hidpp_battery_0"), Value::Percent, fixture("charging")).update().is_err());
+        assert!(Battery::new(None, Value::Ac, fixture("missing")).update().is_err());
+    }
+}
@@ -0,0 +1,715 @@
+use std::collections::HashMap;
+use std::f64::consts;
+
+use serde::Deserialize;
+
+use crate::measure::{self, Measure, MeasureValue};
+use crate::skin::MeasureDefinition;
+use crate::error::{Error, Result};
+
+/// A syntax error at byte `offset` into the formula.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl SyntaxError {
+    fn new<S: Into<String>>(offset: usize, message: S) -> SyntaxError {
+        SyntaxError {
+            offset,
+            message: message.into(),
+        }
+    }
+
+    /// The error as a `Parse` error in `source`, where the formula starts at
+    /// byte `start`.
+    pub fn at(&self, source: &str, start: usize) -> Error {
+        Error::parse(source, start + self.offset, self.message.clone())
+    }
+}
+
+type ParseResult<T> = std::result::Result<T, SyntaxError>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Number(f64),
+    Ident(String),
+    Op(&'static str),
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: Kind,
+    offset: usize,
+}
+
+// Longest first, so `<=` is not read as `<` and `=`.
+const OPERATORS: [&str; 22] = [
+    "==", "!=", "<=", ">=", "&&", "||",
+    "(", ")", "[", "]", ",", "?", ":", "+", "-", "*", "/", "%", "^", "!", "<", ">",
+];
+
+fn tokenize(source: &str) -> ParseResult<Vec<Token>> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        if c.is_ascii_digit() || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit)) {
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                i += 1;
+            }
+            // Exponent, only if digits follow so `2e` is a number and a name.
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                let mut j = i + 1;
+                if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+                    j += 1;
+                }
+                if bytes.get(j).is_some_and(u8::is_ascii_digit) {
+                    i = j;
+                    while i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text = &source[start..i];
+            let number = text.parse().map_err(|_| SyntaxError::new(start, format!("invalid number {:?}", text)))?;
+            tokens.push(Token { kind: Kind::Number(number), offset: start });
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push(Token { kind: Kind::Ident(source[start..i].to_string()), offset: start });
+        } else {
+            let op = OPERATORS.iter().find(|op| source[i..].starts_with(*op));
+            match op {
+                Some(op) => {
+                    i += op.len();
+                    tokens.push(Token { kind: Kind::Op(op), offset: start });
+                },
+                None => {
+                    let c = source[i..].chars().next().unwrap_or('?');
+                    return Err(SyntaxError::new(start, format!("unexpected character {:?}", c)));
+                },
+            }
+        }
+    }
+
+    tokens.push(Token { kind: Kind::End, offset: source.len() });
+    Ok(tokens)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Function {
+    Abs,
+    Sqrt,
+    Floor,
+    Ceil,
+    Round,
+    Trunc,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Ln,
+    Log10,
+    Exp,
+    Min,
+    Max,
+    Clamp,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        Some(match name {
+            "abs" => Function::Abs,
+            "sqrt" => Function::Sqrt,
+            "floor" => Function::Floor,
+            "ceil" => Function::Ceil,
+            "round" => Function::Round,
+            "trunc" => Function::Trunc,
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "tan" => Function::Tan,
+            "asin" => Function::Asin,
+            "acos" => Function::Acos,
+            "atan" => Function::Atan,
+            "atan2" => Function::Atan2,
+            "ln" => Function::Ln,
+            "log10" => Function::Log10,
+            "exp" => Function::Exp,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "clamp" => Function::Clamp,
+            _ => return None,
+        })
+    }
+
+    /// Smallest and largest number of arguments.
+    fn arity(self) -> (usize, usize) {
+        match self {
+            Function::Round => (1, 2),
+            Function::Atan2 => (2, 2),
+            Function::Clamp => (3, 3),
+            Function::Min | Function::Max => (1, usize::MAX),
+            _ => (1, 1),
+        }
+    }
+
+    fn apply(self, args: &[f64]) -> f64 {
+        let x = args[0];
+        match self {
+            Function::Abs => x.abs(),
+            Function::Sqrt => x.sqrt(),
+            Function::Floor => x.floor(),
+            Function::Ceil => x.ceil(),
+            Function::Round => match args.get(1) {
+                Some(&digits) => {
+                    let scale = 10f64.powi(digits as i32);
+                    (x * scale).round() / scale
+                },
+                None => x.round(),
+            },
+            Function::Trunc => x.trunc(),
+            Function::Sin => x.sin(),
+            Function::Cos => x.cos(),
+            Function::Tan => x.tan(),
+            Function::Asin => x.asin(),
+            Function::Acos => x.acos(),
+            Function::Atan => x.atan(),
+            Function::Atan2 => x.atan2(args[1]),
+            Function::Ln => x.ln(),
+            Function::Log10 => x.log10(),
+            Function::Exp => x.exp(),
+            Function::Min => args.iter().copied().fold(f64::INFINITY, f64::min),
+            Function::Max => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Function::Clamp => x.max(args[1]).min(args[2]),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    fn from_op(op: &str) -> Option<BinaryOp> {
+        Some(match op {
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            "*" => BinaryOp::Mul,
+            "/" => BinaryOp::Div,
+            "%" => BinaryOp::Rem,
+            "^" => BinaryOp::Pow,
+            "==" => BinaryOp::Eq,
+            "!=" => BinaryOp::Ne,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::Le,
+            ">" => BinaryOp::Gt,
+            ">=" => BinaryOp::Ge,
+            "&&" => BinaryOp::And,
+            "||" => BinaryOp::Or,
+            _ => return None,
+        })
+    }
+
+    /// Binding power, higher binds tighter. Everything is left associative
+    /// except `^`, which `Parser::power` takes care of.
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq | BinaryOp::Ne => 3,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 4,
+            BinaryOp::Add | BinaryOp::Sub => 5,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 6,
+            BinaryOp::Pow => 8,
+        }
+    }
+}
+
+// Unary minus and not sit between the products and `^`, so `-2^2` is -4.
+const UNARY_PRECEDENCE: u8 = 7;
+
+// How deep formulas may nest, so that parsing and evaluating them can't run
+// out of stack.
+const MAX_DEPTH: usize = 256;
+
+/// A parsed formula.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    /// A measure value, or one of its sub-values with an index.
+    Reference {
+        name: String,
+        index: Option<usize>,
+        offset: usize,
+    },
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != Kind::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        match self.peek().kind {
+            Kind::Op(o) if o == op => {
+                self.pos += 1;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> ParseResult<Token> {
+        let token = self.next();
+        match token.kind {
+            Kind::Op(o) if o == op => Ok(token),
+            _ => Err(SyntaxError::new(token.offset, format!("expected `{}`, found {}", op, describe(&token)))),
+        }
+    }
+
+    // Goes one level deeper, failing past `MAX_DEPTH`.
+    fn deeper(&mut self) -> ParseResult<()> {
+        if self.depth == MAX_DEPTH {
+            return Err(SyntaxError::new(self.peek().offset, "formula nests too deeply"));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn expression(&mut self) -> ParseResult<Expr> {
+        self.deeper()?;
+        let expr = self.conditional();
+        self.depth -= 1;
+        expr
+    }
+
+    fn conditional(&mut self) -> ParseResult<Expr> {
+        let condition = self.binary(1)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.expression()?;
+        self.expect(":")?;
+        let otherwise = self.expression()?;
+        Ok(Expr::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)))
+    }
+
+    fn binary(&mut self, min: u8) -> ParseResult<Expr> {
+        let depth = self.depth;
+        let mut left = self.unary()?;
+        while let Some(op) = self.binary_op(min) {
+            // Each operator of a chain nests the ones before it.
+            self.deeper()?;
+            self.pos += 1;
+            let right = self.binary(op.precedence() + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn binary_op(&self, min: u8) -> Option<BinaryOp> {
+        match self.peek().kind {
+            Kind::Op(o) => BinaryOp::from_op(o).filter(|op| op.precedence() >= min),
+            _ => None,
+        }
+    }
+
+    fn unary(&mut self) -> ParseResult<Expr> {
+        let op = match self.peek().kind {
+            Kind::Op("-") => Some(UnaryOp::Neg),
+            Kind::Op("!") => Some(UnaryOp::Not),
+            Kind::Op("+") => None,
+            _ => return self.power(),
+        };
+        self.deeper()?;
+        self.pos += 1;
+        let operand = self.binary(UNARY_PRECEDENCE)?;
+        self.depth -= 1;
+        Ok(match op {
+            Some(op) => Expr::Unary(op, Box::new(operand)),
+            None => operand,
+        })
+    }
+
+    // A primary, with any `^` to its right.
+    fn power(&mut self) -> ParseResult<Expr> {
+        let base = self.primary()?;
+        if !self.eat("^") {
+            return Ok(base);
+        }
+        self.deeper()?;
+        let exponent = self.unary()?;
+        self.depth -= 1;
+        Ok(Expr::Binary(BinaryOp::Pow, Box::new(base), Box::new(exponent)))
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr> {
+        let token = self.next();
+        match token.kind {
+            Kind::Number(n) => Ok(Expr::Number(n)),
+            Kind::Op("(") => {
+                let inner = self.expression()?;
+                self.expect(")")?;
+                Ok(inner)
+            },
+            Kind::Ident(ref name) if self.peek().kind == Kind::Op("(") => self.call(name, token.offset),
+            Kind::Ident(ref name) if name == "pi" => Ok(Expr::Number(consts::PI)),
+            Kind::Ident(ref name) if name == "e" => Ok(Expr::Number(consts::E)),
+            Kind::Ident(name) => {
+                let index = if self.eat("[") {
+                    let index = self.next();
+                    let i = match index.kind {
+                        Kind::Number(n) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+                        _ => return Err(SyntaxError::new(index.offset, format!("expected an index, found {}", describe(&index)))),
+                    };
+                    self.expect("]")?;
+                    Some(i)
+                } else {
+                    None
+                };
+                Ok(Expr::Reference { name, index, offset: token.offset })
+            },
+            _ => Err(SyntaxError::new(token.offset, format!("expected a value, found {}", describe(&token)))),
+        }
+    }
+
+    fn call(&mut self, name: &str, offset: usize) -> ParseResult<Expr> {
+        let function = Function::from_name(name)
+            .ok_or_else(|| SyntaxError::new(offset, format!("unknown function {}", name)))?;
+        self.expect("(")?;
+
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.expression()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+
+        let (least, most) = function.arity();
+        if args.len() < least || args.len() > most {
+            let expected = match (least, most) {
+                (l, m) if l == m => format!("{}", l),
+                (l, usize::MAX) => format!("at least {}", l),
+                (l, m) => format!("{} to {}", l, m),
+            };
+            return Err(SyntaxError::new(offset, format!("{} takes {} arguments, got {}", name, expected, args.len())));
+        }
+        Ok(Expr::Call(function, args))
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token.kind {
+        Kind::Number(n) => format!("number {}", n),
+        Kind::Ident(ref name) => format!("`{}`", name),
+        Kind::Op(op) => format!("`{}`", op),
+        Kind::End => "end of formula".to_string(),
+    }
+}
+
+/// Parses a formula.
+///
+/// Numbers, measure references (`Name` or `Name[index]`), the constants `pi`
+/// and `e`, parentheses, `+ - * / % ^`, comparisons and `&& || !` giving 1
+/// or 0, `cond ? a : b`, and the functions `abs sqrt floor ceil round trunc
+/// sin cos tan asin acos atan atan2 ln log10 exp min max clamp`.
+pub fn parse(source: &str) -> ParseResult<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+        depth: 0,
+    };
+    let expr = parser.expression()?;
+
+    let rest = parser.next();
+    if rest.kind != Kind::End {
+        return Err(SyntaxError::new(rest.offset, format!("expected an operator, found {}", describe(&rest))));
+    }
+    Ok(expr)
+}
+
+impl Expr {
+    /// Names of the measures the formula reads, in order of appearance.
+    pub fn references(&self) -> Vec<&str> {
+        self.reference_offsets().into_iter().map(|(name, _)| name).collect()
+    }
+
+    /// Like `references`, along with the offset of each name's first use.
+    pub fn reference_offsets(&self) -> Vec<(&str, usize)> {
+        let mut names = Vec::new();
+        self.collect_references(&mut names);
+        names
+    }
+
+    fn collect_references<'a>(&'a self, names: &mut Vec<(&'a str, usize)>) {
+        match *self {
+            Expr::Number(_) => {},
+            Expr::Reference { ref name, offset, .. } => {
+                if !names.iter().any(|(n, _)| n == name) {
+                    names.push((name, offset));
+                }
+            },
+            Expr::Unary(_, ref e) => e.collect_references(names),
+            Expr::Binary(_, ref a, ref b) => {
+                a.collect_references(names);
+                b.collect_references(names);
+            },
+            Expr::Conditional(ref c, ref a, ref b) => {
+                c.collect_references(names);
+                a.collect_references(names);
+                b.collect_references(names);
+            },
+            Expr::Call(_, ref args) => args.iter().for_each(|a| a.collect_references(names)),
+        }
+    }
+
+    /// Evaluates the formula, reading measure values from `values`.
+    pub fn evaluate(&self, values: &HashMap<String, MeasureValue>) -> Result<f64> {
+        let truth = |b: bool| if b { 1.0 } else { 0.0 };
+
+        Ok(match *self {
+            Expr::Number(n) => n,
+            Expr::Reference { ref name, index, .. } => {
+                let value = values.get(name)
+                    .ok_or_else(|| Error::from(format!("{} has no value", name)))?;
+                match index {
+                    Some(i) => value.sub.get(i)
+                        .ok_or_else(|| Error::from(format!("{} has no value {}", name, i)))?
+                        .number,
+                    None => value.number,
+                }
+            },
+            Expr::Unary(UnaryOp::Neg, ref e) => -e.evaluate(values)?,
+            Expr::Unary(UnaryOp::Not, ref e) => truth(e.evaluate(values)? == 0.0),
+            Expr::Binary(BinaryOp::And, ref a, ref b) => truth(a.evaluate(values)? != 0.0 && b.evaluate(values)? != 0.0),
+            Expr::Binary(BinaryOp::Or, ref a, ref b) => truth(a.evaluate(values)? != 0.0 || b.evaluate(values)? != 0.0),
+            Expr::Binary(op, ref a, ref b) => {
+                let (a, b) = (a.evaluate(values)?, b.evaluate(values)?);
+                match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div | BinaryOp::Rem if b == 0.0 => return Err(Error::from("division by zero".to_string())),
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Rem => a % b,
+                    BinaryOp::Pow => a.powf(b),
+                    BinaryOp::Eq => truth(a == b),
+                    BinaryOp::Ne => truth(a != b),
+                    BinaryOp::Lt => truth(a < b),
+                    BinaryOp::Le => truth(a <= b),
+                    BinaryOp::Gt => truth(a > b),
+                    BinaryOp::Ge => truth(a >= b),
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            },
+            Expr::Conditional(ref c, ref a, ref b) => {
+                if c.evaluate(values)? != 0.0 { a.evaluate(values)? } else { b.evaluate(values)? }
+            },
+            Expr::Call(function, ref args) => {
+                let args = args.iter().map(|a| a.evaluate(values)).collect::<Result<Vec<_>>>()?;
+                function.apply(&args)
+            },
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    formula: String,
+    #[serde(default)]
+    min: f64,
+    #[serde(default = "default_max")]
+    max: f64,
+}
+
+fn default_max() -> f64 {
+    100.0
+}
+
+/// The result of a formula over other measures, see `parse` for the syntax.
+/// Calc measures update after the measures they read.
+///
+/// ```toml
+/// [[measure]]
+/// name = "MemPercent"
+/// type = "calc"
+/// formula = "round(MemUsed / MemTotal * 100, 1)"
+/// ```
+pub struct Calc {
+    expr: Expr,
+    min: f64,
+    max: f64,
+}
+
+impl Calc {
+    pub fn new(formula: &str) -> Result<Calc> {
+        let expr = parse(formula).map_err(|e| e.at(formula, 0))?;
+        Ok(Calc {
+            expr,
+            min: 0.0,
+            max: default_max(),
+        })
+    }
+
+    pub fn from_definition(def: &MeasureDefinition) -> Result<Calc> {
+        let options: Options = measure::options(def)?;
+        let mut calc = Calc::new(&options.formula)?;
+        calc.min = options.min;
+        calc.max = options.max;
+        Ok(calc)
+    }
+}
+
+impl Measure for Calc {
+    fn update(&mut self) -> Result<MeasureValue> {
+        self.update_with(&HashMap::new())
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        self.expr.references().into_iter().map(str::to_string).collect()
+    }
+
+    fn update_with(&mut self, values: &HashMap<String, MeasureValue>) -> Result<MeasureValue> {
+        let number = self.expr.evaluate(values)?;
+        Ok(MeasureValue::new(number, self.min, self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(formula: &str) -> f64 {
+        let mut values = HashMap::new();
+        values.insert("Used".to_string(), MeasureValue::new(2.0, 0.0, 8.0));
+        values.insert("Total".to_string(), MeasureValue::new(8.0, 0.0, 8.0));
+        let cores = vec![MeasureValue::new(10.0, 0.0, 100.0), MeasureValue::new(30.0, 0.0, 100.0)];
+        values.insert("Cpu".to_string(), MeasureValue::new(20.0, 0.0, 100.0).with_sub(cores));
+
+        parse(formula).unwrap().evaluate(&values).unwrap()
+    }
+
+    fn error(formula: &str) -> (usize, String) {
+        let e = parse(formula).unwrap_err();
+        (e.offset, e.message)
+    }
+
+    #[test]
+    fn arithmetic_and_precedence() {
+        assert_eq!(eval("(Used / Total) * 100"), 25.0);
+        assert_eq!(eval("1 + 2 * 3 - 4 / 2"), 5.0);
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(eval("-2 ^ 2"), -4.0);
+        assert_eq!(eval("2 ^ -1"), 0.5);
+        assert_eq!(eval("7 % 4 + .5 + 1e2"), 103.5);
+        assert_eq!(eval("Cpu[1] - Cpu[0] + Cpu"), 40.0);
+    }
+
+    #[test]
+    fn logic_and_functions() {
+        assert_eq!(eval("Used < Total && !(Used == 3)"), 1.0);
+        assert_eq!(eval("Used > Total || 0"), 0.0);
+        assert_eq!(eval("Used > 1 ? Used > 5 ? 3 : 2 : 1"), 2.0);
+        assert_eq!(eval("min(4, Used, 9) + max(1, 2)"), 4.0);
+        assert_eq!(eval("clamp(150, 0, 100)"), 100.0);
+        assert_eq!(eval("round(2.71828, 2) + round(0.4)"), 2.72);
+        assert_eq!(eval("round(sin(pi / 2) + cos(0) + ln(e), 6)"), 3.0);
+        assert_eq!(eval("atan2(1, 1) * 4"), consts::PI);
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error("1 + * 2"), (4, "expected a value, found `*`".to_string()));
+        assert_eq!(error("(1 + 2"), (6, "expected `)`, found end of formula".to_string()));
+        assert_eq!(error("1 2"), (2, "expected an operator, found number 2".to_string()));
+        assert_eq!(error("a ? b"), (5, "expected `:`, found end of formula".to_string()));
+        assert_eq!(error("2 $ 3"), (2, "unexpected character '$'".to_string()));
+        assert_eq!(error("foo(1)").1, "unknown function foo");
+        assert_eq!(error("clamp(1, 2)").1, "clamp takes 3 arguments, got 2");
+        assert_eq!(error("Cpu[x]").0, 4);
+
+        let nested = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(error(&nested).1, "formula nests too deeply");
+        assert_eq!(error(&"-".repeat(100_000)).1, "formula nests too deeply");
+        assert_eq!(error(&"2^".repeat(100_000)).1, "formula nests too deeply");
+        assert_eq!(error(&"1 ? 2 : ".repeat(100_000)).1, "formula nests too deeply");
+        assert_eq!(error(&format!("1{}", " + 1".repeat(100_000))).1, "formula nests too deeply");
+        assert_eq!(eval(&format!("{}1{}", "(".repeat(100), ")".repeat(100))), 1.0);
+        assert_eq!(eval(&format!("1{}", " + 1".repeat(200))), 201.0);
+
+        match Calc::new("1 +\n 2 +").err().unwrap() {
+            Error::Parse { line, column, .. } => assert_eq!((line, column), (2, 5)),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn references_and_runtime_errors() {
+        let expr = parse("Used / Total + Used + Cpu[1] + pi").unwrap();
+        assert_eq!(expr.references(), vec!["Used", "Total", "Cpu"]);
+
+        let mut calc = Calc::new("Missing * 2").unwrap();
+        assert_eq!(calc.dependencies(), vec!["Missing".to_string()]);
+        assert!(calc.update().is_err());
+        assert!(Calc::new("1 / (2 - 2)").unwrap().update().is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use std::io::Read;
+use std::process::{Child, Command as Process, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::measure::{self, Measure, MeasureValue, file};
+use crate::skin::MeasureDefinition;
+use crate::error::{Error, Result};
+
+/// What a finished command left behind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub stdout: String,
+    /// Exit code, `None` if the command was killed by a signal.
+    pub code: Option<i32>,
+}
+
+/// Runs `program` with `args` and waits for it at most `timeout`, killing it
+/// after that. Standard input and error are not connected.
+///
+/// On Unix the command gets its own process group, and a timeout kills the
+/// whole group, so processes it started don't outlive it. Processes that
+/// leave the group, and anything a command starts on other systems, can
+/// still hold its output open after a timeout. The thread reading it is
+/// then left behind until they close it.
+pub fn run(program: &str, args: &[String], timeout: Duration) -> Result<Output> {
+    let mut process = Process::new(program);
+    process.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut process, 0);
+    let mut child = process.spawn()
+        .map_err(|e| Error::from(format!("failed to run {}: {}", program, e)))?;
+
+    // Read on another thread so a chatty command can not fill the pipe and
+    // block before it exits.
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        stdout.read_to_end(&mut buf).map(|_| buf)
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            kill(&mut child);
+            let _ = child.wait();
+            // Not joined, whatever still holds the output would block it.
+            drop(reader);
+            return Err(Error::from(format!("{} timed out after {} ms", program, timeout.as_millis())));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let stdout = reader.join()
+        .map_err(|_| Error::from(format!("failed to read the output of {}", program)))??;
+    Ok(Output {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        code: status.code(),
+    })
+}
+
+// Kills `child` and the rest of its process group.
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    // A negative pid signals the whole group, whose id is the child's.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Value {
+    Output,
+    Exit,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+    /// Milliseconds before the command is killed.
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+    #[serde(default = "default_value")]
+    value: Value,
+    #[serde(default)]
+    min: f64,
+    #[serde(default = "default_max")]
+    max: f64,
+}
+
+fn default_timeout() -> u64 {
+    5000
+}
+
+fn default_value() -> Value {
+    Value::Output
+}
+
+fn default_max() -> f64 {
+    100.0
+}
+
+/// Output of a local command, run again every update. `sub` holds the
+/// trimmed standard output, as a number when it is one, and the exit code.
+/// `value` picks which of them is the main reading.
+///
+/// The command runs in the background so a slow one does not hold up the
+/// skin. Each update reports the last finished run and starts a new one if
+/// none is in flight, so the first update reads empty.
+///
+/// ```toml
+/// [[measure]]
+/// name = "Updates"
+/// type = "command"
+/// command = "sh"
+/// args = ["-c", "checkupdates | wc -l"]
+/// timeout = 10000
+/// ```
+pub struct Command {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+    value: Value,
+    min: f64,
+    max: f64,
+    running: Option<Receiver<Result<Output>>>,
+    last: MeasureValue,
+}
+
+impl Command {
+    pub fn new(program: &str, args: &[&str], timeout: Duration) -> Command {
+        Command {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            timeout,
+            value: Value::Output,
+            min: 0.0,
+            max: default_max(),
+            running: None,
+            last: MeasureValue::default(),
+        }
+    }
+
+    pub fn from_definition(def: &MeasureDefinition) -> Result<Command> {
+        let options: Options = measure::options(def)?;
+        let mut command = Command::new(&options.command, &[], Duration::from_millis(options.timeout));
+        command.args = options.args;
+        command.value = options.value;
+        command.min = options.min;
+        command.max = options.max;
+        Ok(command)
+    }
+
+    pub fn sample(&self, output: &Output) -> MeasureValue {
+        let stdout = output.stdout.trim();
+        let code = output.code.map_or(-1.0, f64::from);
+
+        let sub = vec![
+            MeasureValue::new(file::parse_number(stdout), self.min, self.max).with_string(stdout),
+            MeasureValue::new(code, 0.0, 255.0),
+        ];
+        let main = match self.value {
+            Value::Output => &sub[0],
+            Value::Exit => &sub[1],
+        };
+        main.clone().with_sub(sub)
+    }
+
+    fn start(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        let (program, args, timeout) = (self.program.clone(), self.args.clone(), self.timeout);
+        thread::spawn(move || {
+            let _ = sender.send(run(&program, &args, timeout));
+        });
+        self.running = Some(receiver);
+    }
+}
+
+impl Measure for Command {
+    fn update(&mut self) -> Result<MeasureValue> {
+        let finished = match self.running {
+            Some(ref receiver) => match receiver.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => return Ok(self.last.clone()),
+                Err(TryRecvError::Disconnected) => Some(Err(Error::from(format!("{} stopped unexpectedly", self.program)))),
+            },
+            None => None,
+        };
+
+        self.start();
+        if let Some(result) = finished {
+            self.last = self.sample(&result?);
+        }
+        Ok(self.last.clone())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn sh(script: &str) -> Command {
+        Command::new("sh", &["-c", script], Duration::from_secs(5))
+    }
+
+    // Updates until the first run is reported.
+    fn settle(command: &mut Command) -> Result<MeasureValue> {
+        assert_eq!(command.update().unwrap(), MeasureValue::default());
+        let start = Instant::now();
+        loop {
+            thread::sleep(Duration::from_millis(20));
+            let value = command.update()?;
+            if value != MeasureValue::default() || start.elapsed() > Duration::from_secs(5) {
+                return Ok(value);
+            }
+        }
+    }
+
+    #[test]
+    fn runs_with_timeout() {
+        let output = run("sh", &["-c".to_string(), "echo hi; exit 3".to_string()], Duration::from_secs(5)).unwrap();
+        assert_eq!(output, Output { stdout: "hi\n".to_string(), code: Some(3) });
+
+        let start = Instant::now();
+        assert!(run("sleep", &["5".to_string()], Duration::from_millis(100)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        assert!(run("/does/not/exist", &[], Duration::from_secs(1)).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn timeouts_kill_what_commands_start() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let script = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+
+        let start = Instant::now();
+        assert!(run("sh", &["-c".to_string(), script], Duration::from_millis(300)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // The shell's child holds the output pipe too. It is gone, or left
+        // unreaped, once the group was killed.
+        let pid = fs::read_to_string(&pid_file).unwrap();
+        let stat = Path::new("/proc").join(pid.trim()).join("stat");
+        let deadline = Instant::now() + Duration::from_secs(2);
+        loop {
+            match fs::read_to_string(&stat) {
+                Err(_) => break,
+                Ok(s) if s.rsplit(')').next().unwrap_or("").trim_start().starts_with('Z') => break,
+                Ok(_) => assert!(Instant::now() < deadline, "sleep outlived the timeout"),
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn reports_in_the_background() {
+        let mut command = sh("echo ' 42 '");
+        let value = settle(&mut command).unwrap();
+        assert_eq!(value.number, 42.0);
+        assert_eq!(value.string, "42");
+        assert_eq!(value.sub[1].number, 0.0);
+
+        let mut exit = sh("exit 7");
+        exit.value = Value::Exit;
+        assert_eq!(settle(&mut exit).unwrap().number, 7.0);
+
+        let mut slow = sh("sleep 5");
+        slow.timeout = Duration::from_millis(50);
+        assert!(settle(&mut slow).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::measure::{self, Measure, MeasureValue};
+use crate::skin::MeasureDefinition;
+use crate::error::{Error, Result};
+
+/// Cumulative jiffies spent idle and in total by one CPU line of `/proc/stat`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct CpuTimes {
+    pub idle: u64,
+    pub total: u64,
+}
+
+impl CpuTimes {
+    /// Busy percentage over the time between `prev` and `self`.
+    pub fn usage_since(&self, prev: &CpuTimes) -> f64 {
+        let total = self.total.saturating_sub(prev.total);
+        let idle = self.idle.saturating_sub(prev.idle);
+        if total == 0 {
+            return 0.0;
+        }
+        (total.saturating_sub(idle)) as f64 / total as f64 * 100.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CpuStat {
+    pub total: CpuTimes,
+    pub cores: Vec<CpuTimes>,
+}
+
+/// Parses the `cpu` lines of `/proc/stat`.
+pub fn parse_stat(text: &str) -> Result<CpuStat> {
+    let mut stat = CpuStat::default();
+    let mut found = false;
+
+    for (offset, line) in measure::line_offsets(text) {
+        let mut fields = line.split_whitespace();
+        let label = match fields.next() {
+            Some(l) if l.starts_with("cpu") => l,
+            _ => continue,
+        };
+
+        let values = fields
+            .map(|f| f.parse::<u64>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::parse(text, offset, format!("invalid {} line: {}", label, e)))?;
+        if values.len() < 4 {
+            return Err(Error::parse(text, offset, format!("{} line has {} fields, expected at least 4", label, values.len())));
+        }
+
+        // user nice system idle iowait irq softirq steal; guest time is
+        // already counted in user and nice.
+        let times = CpuTimes {
+            idle: values[3] + values.get(4).copied().unwrap_or(0),
+            total: values.iter().take(8).sum(),
+        };
+
+        if label == "cpu" {
+            stat.total = times;
+            found = true;
+        } else {
+            stat.cores.push(times);
+        }
+    }
+
+    if !found {
+        return Err(Error::parse(text, 0, "no cpu line in stat"));
+    }
+    Ok(stat)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    /// Core to report as the main value, 0 based. The total if unset.
+    core: Option<usize>,
+    #[serde(default = "default_path")]
+    path: PathBuf,
+}
+
+fn default_path() -> PathBuf {
+    PathBuf::from("/proc/stat")
+}
+
+/// CPU utilization in percent, computed from the change since the last
+/// update. Per core values are in `sub`.
+///
+/// ```toml
+/// [[measure]]
+/// name = "Cpu"
+/// type = "cpu"
+/// core = 0
+/// ```
+pub struct Cpu {
+    core: Option<usize>,
+    path: PathBuf,
+    prev: Option<CpuStat>,
+}
+
+impl Cpu {
+    pub fn new(core: Option<usize>) -> Cpu {
+        Cpu {
+            core,
+            path: default_path(),
+            prev: None,
+        }
+    }
+
+    pub fn from_definition(def: &MeasureDefinition) -> Result<Cpu> {
+        let options: Options = measure::options(def)?;
+        Ok(Cpu {
+            core: options.core,
+            path: options.path,
+            prev: None,
+        })
+    }
+
+    /// Computes the reading for `stat`, relative to the previous one passed
+    /// in. The first reading is the average since boot.
+    pub fn sample(&mut self, stat: CpuStat) -> Result<MeasureValue> {
+        let prev = self.prev.take().unwrap_or_default();
+
+        let cores: Vec<_> = stat.cores.iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let usage = c.usage_since(prev.cores.get(i).unwrap_or(&CpuTimes::default()));
+                MeasureValue::new(usage, 0.0, 100.0)
+            })
+            .collect();
+
+        let main = match self.core {
+            Some(i) => cores.get(i)
+                .map(|v| v.number)
+                .ok_or_else(|| Error::from(format!("no cpu core {}, found {}", i, cores.len())))?,
+            None => stat.total.usage_since(&prev.total),
+        };
+
+        self.prev = Some(stat);
+        Ok(MeasureValue::new(main, 0.0, 100.0).with_sub(cores))
+    }
+}
+
+impl Measure for Cpu {
+    fn update(&mut self) -> Result<MeasureValue> {
+        let text = fs::read_to_string(&self.path)?;
+        let stat = parse_stat(&text).map_err(|e| e.with_path(&self.path))?;
+        self.sample(stat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAT_1: &str = include_str!("../../tests/fixtures/proc/stat.1");
+    const STAT_2: &str = include_str!("../../tests/fixtures/proc/stat.2");
+
+    #[test]
+    fn parses_stat() {
+        let stat = parse_stat(STAT_1).unwrap();
+        assert_eq!(stat.total, CpuTimes { idle: 3722, total: 9390 });
+        assert_eq!(stat.cores.len(), 4);
+        assert_eq!(stat.cores[1], CpuTimes { idle: 2105, total: 3319 });
+    }
+
+    #[test]
+    fn usage_from_deltas() {
+        let mut cpu = Cpu::new(None);
+        cpu.sample(parse_stat(STAT_1).unwrap()).unwrap();
+        let value = cpu.sample(parse_stat(STAT_2).unwrap()).unwrap();
+
+        assert_eq!(value.number, 60.0);
+        let cores: Vec<_> = value.sub.iter().map(|v| v.number).collect();
+        assert_eq!(cores, vec![100.0, 0.0, 100.0, 50.0]);
+
+        let mut core = Cpu::new(Some(3));
+        core.sample(parse_stat(STAT_1).unwrap()).unwrap();
+        assert_eq!(core.sample(parse_stat(STAT_2).unwrap()).unwrap().number, 50.0);
+
+        let mut missing = Cpu::new(Some(4));
+        assert!(missing.sample(parse_stat(STAT_1).unwrap()).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_stat() {
+        match parse_stat("cpu  1 2 3 4\ncpu0 1 x 3 4\n").unwrap_err() {
+            Error::Parse { line, .. } => assert_eq!(line, 2),
+            e => panic!("unexpected error: {}", e),
+        }
+        assert!(parse_stat("intr 1 2 3\n").is_err());
+    }
+}
@@ -0,0 +1,329 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::measure::{self, Measure, MeasureValue, Reading};
+use crate::skin::MeasureDefinition;
+use crate::error::{Error, Result};
+
+/// Size of the sectors counted in `/proc/diskstats`, whatever the device uses.
+const SECTOR: u64 = 512;
+
+/// Space on a file system, in bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Space {
+    pub total: u64,
+    pub free: u64,
+    /// Free space usable without root, `free` minus reserved blocks.
+    pub available: u64,
+}
+
+impl Space {
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+}
+
+/// Space of the file system mounted at `path`.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+pub fn statvfs<P: AsRef<Path>>(path: P) -> Result<Space> {
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_ref().as_os_str().as_bytes())
+        .map_err(|e| Error::from(format!("invalid mount point: {}", e)))?;
+
+    unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(c_path.as_ptr(), &mut stat) != 0 {
+            return Err(Error::from(io::Error::last_os_error()));
+        }
+
+        let block = stat.f_frsize as u64;
+        Ok(Space {
+            total: stat.f_blocks as u64 * block,
+            free: stat.f_bfree as u64 * block,
+            available: stat.f_bavail as u64 * block,
+        })
+    }
+}
+
+#[cfg(not(unix))]
+pub fn statvfs<P: AsRef<Path>>(_path: P) -> Result<Space> {
+    Err(Error::Unimplemented)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpaceOptions {
+    #[serde(default = "default_mount")]
+    mount: PathBuf,
+    #[serde(default = "default_reading")]
+    value: Reading,
+    #[serde(default)]
+    percent: bool,
+}
+
+fn default_mount() -> PathBuf {
+    PathBuf::from("/")
+}
+
+fn default_reading() -> Reading {
+    Reading::Used
+}
+
+/// Space of a mount point, see `measure::capacity` for the values. Free is
+/// the space available to unprivileged users.
+///
+/// ```toml
+/// [[measure]]
+/// name = "Root"
+/// type = "disk"
+/// mount = "/"
+/// value = "free"
+/// ```
+pub struct DiskSpace {
+    mount: PathBuf,
+    reading: Reading,
+    percent: bool,
+}
+
+impl DiskSpace {
+    pub fn new<P: AsRef<Path>>(mount: P, reading: Reading, percent: bool) -> DiskSpace {
+        DiskSpace {
+            mount: mount.as_ref().to_path_buf(),
+            reading,
+            percent,
+        }
+    }
+
+    pub fn from_definition(def: &MeasureDefinition) -> Result<DiskSpace> {
+        let options: SpaceOptions = measure::options(def)?;
+        Ok(DiskSpace::new(options.mount, options.value, options.percent))
+    }
+
+    pub fn sample(&self, space: &Space) -> MeasureValue {
+        let free = space.available.min(space.total);
+        measure::capacity(self.reading, self.percent, space.used(), free, space.total)
+    }
+}
+
+impl Measure for DiskSpace {
+    fn update(&mut self) -> Result<MeasureValue> {
+        let space = statvfs(&self.mount)?;
+        Ok(self.sample(&space))
+    }
+}
+
+/// Cumulative counters of one block device in `/proc/diskstats`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DiskStats {
+    pub name: String,
+    pub read_bytes: u64,
+    pub written_bytes: u64,
+}
+
+/// Parses `/proc/diskstats`.
+pub fn parse_diskstats(text: &str) -> Result<Vec<DiskStats>> {
+    let mut disks = Vec::new();
+
+    for (start, line) in measure::line_offsets(text) {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        if fields.len() < 10 {
+            return Err(Error::parse(text, start, format!("expected at least 10 fields, found {}", fields.len())));
+        }
+
+        let counter = |i: usize| -> Result<u64> {
+            fields[i].parse()
+                .map_err(|e| Error::parse(text, start, format!("invalid counter for {}: {}", fields[2], e)))
+        };
+
+        disks.push(DiskStats {
+            name: fields[2].to_string(),
+            read_bytes: counter(5)? * SECTOR,
+            written_bytes: counter(9)? * SECTOR,
+        });
+    }
+
+    Ok(disks)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Read,
+    Write,
+    Total,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IoOptions {
+    device: String,
+    #[serde(default = "default_direction")]
+    direction: Direction,
+    /// Top of the range in bytes per second. Grows with the highest rate seen
+    /// when unset.
+    max: Option<f64>,
+    #[serde(default = "default_diskstats")]
+    path: PathBuf,
+}
+
+fn default_direction() -> Direction {
+    Direction::Total
+}
+
+fn default_diskstats() -> PathBuf {
+    PathBuf::from("/proc/diskstats")
+}
+
+/// Throughput of a block device in bytes per second, with the auto scaled
+/// rate as the string. `sub` holds the read rate, write rate, total bytes
+/// read and total bytes written, in that order. The totals range over both
+/// together.
+///
+/// ```toml
+/// [[measure]]
+/// name = "SdaWrite"
+/// type = "diskio"
+/// device = "sda"
+/// direction = "write"
+/// ```
+pub struct DiskIo {
+    device: String,
+    direction: Direction,
+    max: Option<f64>,
+    peak: f64,
+    path: PathBuf,
+    prev: Option<(Instant, DiskStats)>,
+}
+
+impl DiskIo {
+    pub fn new(device: &str, direction: Direction) -> DiskIo {
+        DiskIo {
+            device: device.to_string(),
+            direction,
+            max: None,
+            peak: 0.0,
+            path: default_diskstats(),
+            prev: None,
+        }
+    }
+
+    pub fn from_definition(def: &MeasureDefinition) -> Result<DiskIo> {
+        let options: IoOptions = measure::options(def)?;
+        let mut io = DiskIo::new(&options.device, options.direction);
+        io.max = options.max;
+        io.path = options.path;
+        Ok(io)
+    }
+
+    /// Computes the reading for `stats`, taken `elapsed` after `prev`. Rates
+    /// are zero without a previous sample.
+    pub fn sample(&mut self, stats: &[DiskStats], prev: Option<&DiskStats>, elapsed: Duration) -> Result<(DiskStats, MeasureValue)> {
+        let disk = stats.iter()
+            .find(|d| d.name == self.device)
+            .ok_or_else(|| Error::from(format!("no block device {}", self.device)))?;
+
+        let seconds = elapsed.as_secs_f64();
+        let (read, write) = match prev {
+            Some(p) if seconds > 0.0 => (
+                disk.read_bytes.saturating_sub(p.read_bytes) as f64 / seconds,
+                disk.written_bytes.saturating_sub(p.written_bytes) as f64 / seconds,
+            ),
+            _ => (0.0, 0.0),
+        };
+
+        let number = match self.direction {
+            Direction::Read => read,
+            Direction::Write => write,
+            Direction::Total => read + write,
+        };
+        let value = measure::throughput(number, [read, write], [disk.read_bytes, disk.written_bytes], self.max, &mut self.peak);
+        Ok((disk.clone(), value))
+    }
+}
+
+impl Measure for DiskIo {
+    fn update(&mut self) -> Result<MeasureValue> {
+        let text = fs::read_to_string(&self.path)?;
+        let stats = parse_diskstats(&text).map_err(|e| e.with_path(&self.path))?;
+
+        let now = Instant::now();
+        let prev = self.prev.take();
+        let (disk, value) = match prev {
+            Some((at, ref p)) => self.sample(&stats, Some(p), now - at)?,
+            None => self.sample(&stats, None, Duration::from_secs(0))?,
+        };
+
+        self.prev = Some((now, disk));
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISKSTATS_1: &str = include_str!("../../tests/fixtures/proc/diskstats.1");
+    const DISKSTATS_2: &str = include_str!("../../tests/fixtures/proc/diskstats.2");
+
+    #[test]
+    fn parses_diskstats() {
+        let disks = parse_diskstats(DISKSTATS_1).unwrap();
+        assert_eq!(disks.len(), 4);
+        assert_eq!(disks[1], DiskStats { name: "sda".to_string(), read_bytes: 20_000 * 512, written_bytes: 40_000 * 512 });
+        assert_eq!(disks[3].name, "nvme0n1");
+
+        match parse_diskstats("8 0 sda 1 2 x 4 5 6 7 8\n").unwrap_err() {
+            Error::Parse { line, .. } => assert_eq!(line, 1),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn io_rates() {
+        let first = parse_diskstats(DISKSTATS_1).unwrap();
+        let second = parse_diskstats(DISKSTATS_2).unwrap();
+
+        let mut io = DiskIo::new("sda", Direction::Total);
+        let (prev, value) = io.sample(&first, None, Duration::from_secs(0)).unwrap();
+        assert_eq!(value.number, 0.0);
+
+        let (_, value) = io.sample(&second, Some(&prev), Duration::from_secs(2)).unwrap();
+        assert_eq!(value.sub[0].number, 1_048_576.0);
+        assert_eq!(value.sub[1].string, "512.0 KB/s");
+        assert_eq!(value.number, 1_048_576.0 + 524_288.0);
+        assert_eq!(value.sub[3].max, value.sub[2].number + value.sub[3].number);
+
+        assert!(DiskIo::new("sdz", Direction::Read).sample(&first, None, Duration::from_secs(0)).is_err());
+    }
+
+    #[test]
+    fn space_readings() {
+        let space = Space { total: 1000, free: 300, available: 200 };
+
+        let used = DiskSpace::new("/", Reading::Used, true).sample(&space);
+        assert_eq!(used.number, 70.0);
+
+        let free = DiskSpace::new("/", Reading::Free, false).sample(&space);
+        assert_eq!(free.number, 200.0);
+        assert_eq!(free.max, 1000.0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn statvfs_root() {
+        let space = statvfs("/").unwrap();
+        assert!(space.total > 0);
+        assert!(space.available <= space.free);
+        assert!(statvfs("/does/not/exist").is_err());
+    }
+}
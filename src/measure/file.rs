@@ -0,0 +1,185 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::measure::{self, Measure, MeasureValue};
+use crate::skin::MeasureDefinition;
+use crate::error::{Error, Result};
+
+/// Parses `text` as a number, ignoring surrounding whitespace. Text that is
+/// not a number reads as zero.
+pub fn parse_number(text: &str) -> f64 {
+    text.trim().parse().unwrap_or(0.0)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    path: PathBuf,
+    /// Line to read, 1 based. The whole file if unset.
+    line: Option<usize>,
+    regex: Option<String>,
+    /// Capture group to report. The first group if the regex has any,
+    /// otherwise the whole match.
+    group: Option<usize>,
+    #[serde(default)]
+    min: f64,
+    #[serde(default = "default_max")]
+    max: f64,
+}
+
+fn default_max() -> f64 {
+    100.0
+}
+
+/// Contents of a text file, read again on every update. The text is the
+/// string and, when it is a number, the number.
+///
+/// `line` narrows it down to one line and `regex` to a match within it. With
+/// a regex `sub` holds every capture group, starting with the whole match.
+///
+/// ```toml
+/// [[measure]]
+/// name = "Temperature"
+/// type = "file"
+/// path = "/sys/class/thermal/thermal_zone0/temp"
+/// max = 100000
+///
+/// [[measure]]
+/// name = "Load"
+/// type = "file"
+/// path = "/proc/loadavg"
+/// regex = '^(\S+) (\S+) (\S+)'
+/// ```
+pub struct File {
+    path: PathBuf,
+    line: Option<usize>,
+    regex: Option<Regex>,
+    group: Option<usize>,
+    min: f64,
+    max: f64,
+}
+
+impl File {
+    pub fn new<P: AsRef<Path>>(path: P) -> File {
+        File {
+            path: path.as_ref().to_path_buf(),
+            line: None,
+            regex: None,
+            group: None,
+            min: 0.0,
+            max: default_max(),
+        }
+    }
+
+    pub fn from_definition(def: &MeasureDefinition) -> Result<File> {
+        let options: Options = measure::options(def)?;
+
+        let mut file = File::new(options.path);
+        if options.line == Some(0) {
+            return Err(Error::from(format!("measure {}: lines start at 1", def.name)));
+        }
+        file.line = options.line;
+        if let Some(ref pattern) = options.regex {
+            let regex = Regex::new(pattern).map_err(|e| Error::from(format!("measure {}: {}", def.name, e)))?;
+            if let Some(group) = options.group {
+                if group >= regex.captures_len() {
+                    return Err(Error::from(format!("measure {}: regex has no group {}", def.name, group)));
+                }
+            }
+            file.regex = Some(regex);
+        }
+        file.group = options.group;
+        file.min = options.min;
+        file.max = options.max;
+        Ok(file)
+    }
+
+    /// Picks the configured part out of the file contents.
+    pub fn sample(&self, text: &str) -> Result<MeasureValue> {
+        let text = match self.line {
+            Some(n) => text.lines()
+                .nth(n - 1)
+                .ok_or_else(|| Error::from(format!("{} has no line {}", self.path.display(), n)))?,
+            None => text,
+        };
+
+        let regex = match self.regex {
+            Some(ref r) => r,
+            None => return Ok(self.value(text.trim())),
+        };
+
+        let captures = regex.captures(text)
+            .ok_or_else(|| Error::from(format!("no match for {} in {}", regex, self.path.display())))?;
+        let groups: Vec<_> = captures.iter()
+            .map(|m| self.value(m.map_or("", |m| m.as_str())))
+            .collect();
+
+        let group = self.group.unwrap_or(if groups.len() > 1 { 1 } else { 0 });
+        Ok(groups[group].clone().with_sub(groups))
+    }
+
+    fn value(&self, text: &str) -> MeasureValue {
+        MeasureValue::new(parse_number(text), self.min, self.max).with_string(text)
+    }
+}
+
+impl Measure for File {
+    fn update(&mut self) -> Result<MeasureValue> {
+        let text = fs::read_to_string(&self.path)?;
+        self.sample(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(options: &str) -> MeasureDefinition {
+        MeasureDefinition {
+            name: "f".to_string(),
+            kind: "file".to_string(),
+            options: options.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn whole_file_and_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("temp");
+        fs::write(&path, "42000\nsecond line\n").unwrap();
+
+        let mut file = File::new(&path);
+        let value = file.update().unwrap();
+        assert_eq!(value.string, "42000\nsecond line");
+        assert_eq!(value.number, 0.0);
+
+        file.line = Some(1);
+        assert_eq!(file.update().unwrap().number, 42000.0);
+        file.line = Some(3);
+        assert!(file.update().is_err());
+
+        fs::remove_file(&path).unwrap();
+        assert!(file.update().is_err());
+    }
+
+    #[test]
+    fn regex_captures() {
+        let load = File::from_definition(&def("path = \"/proc/loadavg\"\nregex = '^(\\S+) (\\S+) (\\S+)'")).unwrap();
+        let value = load.sample("0.52 0.58 0.59 1/389 12345\n").unwrap();
+        assert_eq!(value.number, 0.52);
+        assert_eq!(value.sub.len(), 4);
+        assert_eq!(value.sub[0].string, "0.52 0.58 0.59");
+        assert_eq!(value.sub[3].number, 0.59);
+
+        let second = File::from_definition(&def("path = \"x\"\nline = 2\nregex = 'v=(\\d+)'\ngroup = 0")).unwrap();
+        assert_eq!(second.sample("v=1\nv=2\n").unwrap().string, "v=2");
+        assert!(second.sample("v=1\nnone\n").is_err());
+
+        assert!(File::from_definition(&def("path = \"x\"\nregex = '('")).is_err());
+        assert!(File::from_definition(&def("path = \"x\"\nregex = 'a'\ngroup = 1")).is_err());
+        assert!(File::from_definition(&def("path = \"x\"\nline = 0")).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::measure::{self, Measure, MeasureValue, Reading};
+use crate::skin::MeasureDefinition;
+use crate::error::{Error, Result};
+
+/// Memory figures from `/proc/meminfo`, in bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct MemInfo {
+    pub total: u64,
+    pub free: u64,
+    pub available: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+}
+
+impl MemInfo {
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.available)
+    }
+
+    pub fn swap_used(&self) -> u64 {
+        self.swap_total.saturating_sub(self.swap_free)
+    }
+}
+
+/// Parses `/proc/meminfo`. Kernels without `MemAvailable` fall back to free
+/// plus buffers and page cache.
+pub fn parse_meminfo(text: &str) -> Result<MemInfo> {
+    let mut info = MemInfo::default();
+    let mut available = None;
+    let mut reclaimable: u64 = 0;
+    let mut has_total = false;
+
+    for (start, line) in measure::line_offsets(text) {
+        let mut parts = line.splitn(2, ':');
+        let key = parts.next().unwrap_or("").trim();
+        let rest = match parts.next() {
+            Some(r) => r.trim(),
+            None => continue,
+        };
+
+        let mut fields = rest.split_whitespace();
+        let value: u64 = fields.next()
+            .unwrap_or("")
+            .parse()
+            .map_err(|e| Error::parse(text, start, format!("invalid {} value: {}", key, e)))?;
+        let bytes = match fields.next() {
+            Some("kB") => value.checked_mul(1024)
+                .ok_or_else(|| Error::parse(text, start, format!("{} value too large", key)))?,
+            _ => value,
+        };
+
+        match key {
+            "MemTotal" => {
+                info.total = bytes;
+                has_total = true;
+            },
+            "MemFree" => info.free = bytes,
+            "MemAvailable" => available = Some(bytes),
+            "Buffers" | "Cached" => reclaimable = reclaimable.saturating_add(bytes),
+            "SwapTotal" => info.swap_total = bytes,
+            "SwapFree" => info.swap_free = bytes,
+            _ => (),
+        }
+    }
+
+    if !has_total {
+        return Err(Error::parse(text, 0, "no MemTotal in meminfo"));
+    }
+    info.available = available.unwrap_or(info.free.saturating_add(reclaimable)).min(info.total);
+    Ok(info)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Physical,
+    Swap,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    #[serde(default = "default_source")]
+    source: Source,
+    #[serde(default = "default_reading")]
+    value: Reading,
+    #[serde(default)]
+    percent: bool,
+    #[serde(default = "default_path")]
+    path: PathBuf,
+}
+
+fn default_source() -> Source {
+    Source::Physical
+}
+
+fn default_reading() -> Reading {
+    Reading::Used
+}
+
+fn default_path() -> PathBuf {
+    PathBuf::from("/proc/meminfo")
+}
+
+/// Physical memory or swap, see `measure::capacity` for the values.
+///
+/// ```toml
+/// [[measure]]
+/// name = "MemUsed"
+/// type = "memory"
+/// source = "physical"
+/// value = "used"
+/// ```
+pub struct Memory {
+    source: Source,
+    reading: Reading,
+    percent: bool,
+    path: PathBuf,
+}
+
+impl Memory {
+    pub fn new(source: Source, reading: Reading, percent: bool) -> Memory {
+        Memory {
+            source,
+            reading,
+            percent,
+            path: default_path(),
+        }
+    }
+
+    pub fn from_definition(def: &MeasureDefinition) -> Result<Memory> {
+        let options: Options = measure::options(def)?;
+        Ok(Memory {
+            source: options.source,
+            reading: options.value,
+            percent: options.percent,
+            path: options.path,
+        })
+    }
+
+    pub fn sample(&self, info: &MemInfo) -> MeasureValue {
+        let (used, free, total) = match self.source {
+            Source::Physical => (info.used(), info.total - info.used(), info.total),
+            Source::Swap => (info.swap_used(), info.swap_free.min(info.swap_total), info.swap_total),
+        };
+        measure::capacity(self.reading, self.percent, used, free, total)
+    }
+}
+
+impl Measure for Memory {
+    fn update(&mut self) -> Result<MeasureValue> {
+        let text = fs::read_to_string(&self.path)?;
+        let info = parse_meminfo(&text).map_err(|e| e.with_path(&self.path))?;
+        Ok(self.sample(&info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMINFO: &str = include_str!("../../tests/fixtures/proc/meminfo");
+
+    const KB: u64 = 1024;
+
+    #[test]
+    fn parses_meminfo() {
+        let info = parse_meminfo(MEMINFO).unwrap();
+        assert_eq!(info, MemInfo {
+            total: 8_000_000 * KB,
+            free: 2_000_000 * KB,
+            available: 6_000_000 * KB,
+            swap_total: 4_000_000 * KB,
+            swap_free: 3_000_000 * KB,
+        });
+        assert_eq!(info.used(), 2_000_000 * KB);
+        assert_eq!(info.swap_used(), 1_000_000 * KB);
+
+        let old = parse_meminfo("MemTotal: 100 kB\nMemFree: 10 kB\nBuffers: 5 kB\nCached: 20 kB\n").unwrap();
+        assert_eq!(old.available, 35 * KB);
+
+        assert!(parse_meminfo("MemFree: 10 kB\n").is_err());
+        match parse_meminfo("MemTotal: 100 kB\nMemFree: lots\n").unwrap_err() {
+            Error::Parse { line, .. } => assert_eq!(line, 2),
+            e => panic!("unexpected error: {}", e),
+        }
+        match parse_meminfo("MemTotal: 100 kB\nCached: 18014398509481984 kB\n").unwrap_err() {
+            Error::Parse { line, ref message, .. } => assert_eq!((line, message.as_str()), (2, "Cached value too large")),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn readings() {
+        let info = parse_meminfo(MEMINFO).unwrap();
+
+        let used = Memory::new(Source::Physical, Reading::Used, false).sample(&info);
+        assert_eq!(used.number, (2_000_000 * KB) as f64);
+        assert_eq!(used.percent(), 0.25);
+        assert_eq!(used.sub[1].number, (6_000_000 * KB) as f64);
+        assert_eq!(used.string, "1.9 GB");
+
+        let swap = Memory::new(Source::Swap, Reading::Free, true).sample(&info);
+        assert_eq!(swap.number, 75.0);
+        assert_eq!(swap.sub[2].number, (4_000_000 * KB) as f64);
+
+        let none = MemInfo { total: 1, available: 1, ..MemInfo::default() };
+        assert_eq!(Memory::new(Source::Swap, Reading::Used, true).sample(&none).number, 0.0);
+    }
+}
@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::warn;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::skin::MeasureDefinition;
+use crate::error::{Error, Result};
+
+pub mod battery;
+pub mod calc;
+pub mod command;
+pub mod cpu;
+pub mod disk;
+pub mod file;
+pub mod memory;
+pub mod network;
+pub mod process;
+pub mod time;
+
+/// Reading of a measure. `number` is expected to sit between `min` and `max`
+/// so meters can show it as a fraction of the range.
+///
+/// Measures that report a breakdown (per core, per process, ...) put it in
+/// `sub`, which meters reach as `Name[index]`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MeasureValue {
+    pub number: f64,
+    pub string: String,
+    pub min: f64,
+    pub max: f64,
+    pub sub: Vec<MeasureValue>,
+}
+
+impl MeasureValue {
+    pub fn new(number: f64, min: f64, max: f64) -> MeasureValue {
+        MeasureValue {
+            number,
+            string: format_number(number),
+            min,
+            max,
+            sub: Vec::new(),
+        }
+    }
+
+    pub fn with_sub(mut self, sub: Vec<MeasureValue>) -> MeasureValue {
+        self.sub = sub;
+        self
+    }
+
+    pub fn with_string<S: Into<String>>(mut self, string: S) -> MeasureValue {
+        self.string = string.into();
+        self
+    }
+
+    /// `number` scaled into `0.0..=1.0` over the measure's range.
+    pub fn percent(&self) -> f64 {
+        if self.max <= self.min {
+            return 0.0;
+        }
+        ((self.number - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+}
+
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{:.2}", n)
+    }
+}
+
+/// Which figure of a capacity style measure (memory, disk space) to report.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reading {
+    Used,
+    Free,
+    Total,
+}
+
+/// Value for a capacity in bytes: the `reading` in bytes over `0..total`, or
+/// in percent of total. `sub` holds used, free and total bytes, in that order.
+pub fn capacity(reading: Reading, percent: bool, used: u64, free: u64, total: u64) -> MeasureValue {
+    let bytes = match reading {
+        Reading::Used => used,
+        Reading::Free => free,
+        Reading::Total => total,
+    };
+
+    let sub = [used, free, total].iter()
+        .map(|&b| MeasureValue::new(b as f64, 0.0, total as f64).with_string(scale_bytes(b as f64)))
+        .collect();
+
+    let value = if percent {
+        let p = if total == 0 { 0.0 } else { bytes as f64 / total as f64 * 100.0 };
+        MeasureValue::new(p, 0.0, 100.0)
+    } else {
+        MeasureValue::new(bytes as f64, 0.0, total as f64).with_string(scale_bytes(bytes as f64))
+    };
+    value.with_sub(sub)
+}
+
+/// Value for a throughput in bytes per second over `0..max`, or over the
+/// highest rate seen so far, kept in `peak`, without one. `sub` holds both
+/// `rates` and then both running `totals`. Totals range over their sum, so
+/// their percent is their share of all bytes moved.
+pub fn throughput(number: f64, rates: [f64; 2], totals: [u64; 2], max: Option<f64>, peak: &mut f64) -> MeasureValue {
+    *peak = peak.max(number);
+    let max = max.unwrap_or(*peak);
+    let all = totals.iter().sum::<u64>() as f64;
+
+    let per_second = |n: f64| MeasureValue::new(n, 0.0, max).with_string(format!("{}/s", scale_bytes(n)));
+    let total = |n: u64| MeasureValue::new(n as f64, 0.0, all).with_string(scale_bytes(n as f64));
+
+    per_second(number).with_sub(vec![
+        per_second(rates[0]),
+        per_second(rates[1]),
+        total(totals[0]),
+        total(totals[1]),
+    ])
+}
+
+/// The lines of `text` with the byte offset each starts at, for parse
+/// errors to point at.
+pub fn line_offsets(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len() + 1;
+        Some((start, line))
+    })
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 MB`.
+pub fn scale_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+    while value.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", value.round() as i64)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// A data source for skins.
+pub trait Measure {
+    /// Takes a new reading. Called once per update interval of the skin.
+    fn update(&mut self) -> Result<MeasureValue>;
+
+    /// Wall clock boundary the measure wants to be updated on, like a whole
+    /// second for a clock showing seconds. `None` when the update interval
+    /// alone is fine.
+    fn alignment(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Names of the measures this one reads. They are updated before it.
+    fn dependencies(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Takes a new reading with the values of the skin's other measures at
+    /// hand. Only measures with dependencies need more than `update`.
+    fn update_with(&mut self, values: &HashMap<String, MeasureValue>) -> Result<MeasureValue> {
+        let _ = values;
+        self.update()
+    }
+}
+
+/// Builds the measure described by `def`.
+pub fn create(def: &MeasureDefinition) -> Result<Box<dyn Measure>> {
+    match def.kind.as_str() {
+        "cpu" => Ok(Box::new(cpu::Cpu::from_definition(def)?)),
+        "memory" => Ok(Box::new(memory::Memory::from_definition(def)?)),
+        "network" => Ok(Box::new(network::Network::from_definition(def)?)),
+        "disk" => Ok(Box::new(disk::DiskSpace::from_definition(def)?)),
+        "diskio" => Ok(Box::new(disk::DiskIo::from_definition(def)?)),
+        "battery" => Ok(Box::new(battery::Battery::from_definition(def)?)),
+        "process" => Ok(Box::new(process::Processes::from_definition(def)?)),
+        "file" => Ok(Box::new(file::File::from_definition(def)?)),
+        "command" => Ok(Box::new(command::Command::from_definition(def)?)),
+        "calc" => Ok(Box::new(calc::Calc::from_definition(def)?)),
+        "time" => Ok(Box::new(time::Time::from_definition(def)?)),
+        kind => Err(Error::from(format!("measure {}: unknown measure type {:?}", def.name, kind))),
+    }
+}
+
+/// Deserializes the type specific options of a measure definition.
+pub fn options<T: DeserializeOwned>(def: &MeasureDefinition) -> Result<T> {
+    toml::Value::Table(def.options.clone())
+        .try_into()
+        .map_err(|e: toml::de::Error| Error::from(format!("measure {}: {}", def.name, e.message())))
+}
+
+/// Splits `Name[index]` into its name and index.
+pub fn parse_reference(reference: &str) -> (&str, Option<usize>) {
+    let reference = reference.trim();
+    if let (Some(open), true) = (reference.find('['), reference.ends_with(']')) {
+        if let Ok(i) = reference[open + 1..reference.len() - 1].trim().parse() {
+            return (reference[..open].trim(), Some(i));
+        }
+    }
+    (reference, None)
+}
+
+struct Entry {
+    name: String,
+    measure: Box<dyn Measure>,
+}
+
+/// The measures of one skin, updated together every `interval`.
+pub struct MeasureSet {
+    interval: Duration,
+    next: Option<Instant>,
+    measures: Vec<Entry>,
+    values: HashMap<String, MeasureValue>,
+}
+
+impl MeasureSet {
+    pub fn new(interval: Duration) -> MeasureSet {
+        MeasureSet {
+            interval,
+            next: None,
+            measures: Vec::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn from_definitions(defs: &[MeasureDefinition], interval: Duration) -> Result<MeasureSet> {
+        let mut set = MeasureSet::new(interval);
+        for def in defs {
+            set.insert(&def.name, create(def)?);
+        }
+        set.order_by_dependencies()?;
+        Ok(set)
+    }
+
+    /// Adds a measure. Measures update in the order they were inserted, see
+    /// `order_by_dependencies` for measures that read others.
+    pub fn insert(&mut self, name: &str, measure: Box<dyn Measure>) {
+        self.measures.push(Entry {
+            name: name.to_string(),
+            measure,
+        });
+    }
+
+    /// Swaps the measure called `name` for `measure`, keeping its place and
+    /// last value. Returns false if there is no such measure.
+    pub fn replace(&mut self, name: &str, measure: Box<dyn Measure>) -> bool {
+        match self.measures.iter_mut().find(|e| e.name == name) {
+            Some(entry) => {
+                entry.measure = measure;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Swaps in every measure of `replacements` like `replace`, then orders
+    /// the set again. If that fails the old measures are put back, leaving
+    /// the set as it was.
+    pub fn replace_ordered(&mut self, replacements: Vec<(String, Box<dyn Measure>)>) -> Result<()> {
+        let mut old = Vec::new();
+        for (name, measure) in replacements {
+            if let Some(entry) = self.measures.iter_mut().find(|e| e.name == name) {
+                old.push((name, std::mem::replace(&mut entry.measure, measure)));
+            }
+        }
+        if let Err(e) = self.order_by_dependencies() {
+            for (name, measure) in old {
+                self.replace(&name, measure);
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.measures.len()
+    }
+
+    /// Moves measures after the ones they depend on, keeping the insertion
+    /// order otherwise. Fails on unknown dependencies and cycles.
+    pub fn order_by_dependencies(&mut self) -> Result<()> {
+        #[derive(Copy, Clone, PartialEq)]
+        enum State {
+            New,
+            Visiting,
+            Done,
+        }
+
+        fn visit(i: usize, measures: &[Entry], state: &mut [State], order: &mut Vec<usize>, path: &mut Vec<usize>) -> Result<()> {
+            match state[i] {
+                State::Done => return Ok(()),
+                State::Visiting => {
+                    let start = path.iter().position(|&p| p == i).unwrap_or(0);
+                    let mut cycle: Vec<_> = path[start..].iter().map(|&p| measures[p].name.as_str()).collect();
+                    cycle.push(&measures[i].name);
+                    return Err(Error::from(format!("measures depend on each other: {}", cycle.join(" -> "))));
+                },
+                State::New => {},
+            }
+
+            state[i] = State::Visiting;
+            path.push(i);
+            for dependency in measures[i].measure.dependencies() {
+                let j = measures.iter()
+                    .position(|m| m.name == dependency)
+                    .ok_or_else(|| Error::from(format!("measure {}: unknown measure {:?}", measures[i].name, dependency)))?;
+                visit(j, measures, state, order, path)?;
+            }
+            path.pop();
+            state[i] = State::Done;
+            order.push(i);
+            Ok(())
+        }
+
+        let mut state = vec![State::New; self.measures.len()];
+        let mut order = Vec::with_capacity(self.measures.len());
+        for i in 0..self.measures.len() {
+            visit(i, &self.measures, &mut state, &mut order, &mut Vec::new())?;
+        }
+
+        let mut entries: Vec<_> = self.measures.drain(..).map(Some).collect();
+        self.measures = order.into_iter().filter_map(|i| entries[i].take()).collect();
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.measures.is_empty()
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn value(&self, name: &str) -> Option<&MeasureValue> {
+        self.values.get(name)
+    }
+
+    /// Resolves `Name` or `Name[index]` to a value or sub-value.
+    pub fn lookup(&self, reference: &str) -> Option<&MeasureValue> {
+        match parse_reference(reference) {
+            (name, None) => self.value(name),
+            (name, Some(i)) => self.value(name).and_then(|v| v.sub.get(i)),
+        }
+    }
+
+    pub fn values(&self) -> &HashMap<String, MeasureValue> {
+        &self.values
+    }
+
+    /// When the next update is due, `None` if there is nothing to update.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.measures.is_empty() {
+            return None;
+        }
+        Some(self.next.unwrap_or_else(Instant::now))
+    }
+
+    /// Updates every measure. A measure that fails keeps its last value.
+    pub fn update(&mut self) {
+        for entry in self.measures.iter_mut() {
+            match entry.measure.update_with(&self.values) {
+                Ok(v) => {
+                    self.values.insert(entry.name.clone(), v);
+                },
+                Err(e) => warn!("measure {} failed to update: {}", entry.name, e),
+            }
+        }
+    }
+
+    /// Updates the measures if they are due at `now`. Returns whether an
+    /// update happened.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        if self.measures.is_empty() {
+            return false;
+        }
+        // Never updated yet, so due straight away.
+        if let Some(next) = self.next {
+            if next > now {
+                return false;
+            }
+        }
+
+        self.update();
+        self.next = Some(now + self.delay(SystemTime::now()));
+        true
+    }
+
+    /// Time from `wall` until the next update. Usually the interval, pulled
+    /// in to the last boundary before it when a measure asks for alignment.
+    fn delay(&self, wall: SystemTime) -> Duration {
+        let since_epoch = wall.duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.measures.iter()
+            .filter_map(|e| e.measure.alignment())
+            .map(|a| aligned_delay(since_epoch, self.interval, a))
+            .fold(self.interval, Duration::min)
+    }
+}
+
+// Delay from `now` to the last multiple of `alignment` before `now + interval`,
+// or the plain interval when there is no such boundary still ahead.
+fn aligned_delay(now: Duration, interval: Duration, alignment: Duration) -> Duration {
+    let step = alignment.as_nanos();
+    if step == 0 {
+        return interval;
+    }
+
+    let start = now.as_nanos();
+    let target = start + interval.as_nanos();
+    let boundary = target - target % step;
+    if boundary > start {
+        Duration::from_nanos((boundary - start) as u64)
+    } else {
+        interval
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Counts up by one every update.
+    pub struct Counter(pub f64);
+
+    impl Measure for Counter {
+        fn update(&mut self) -> Result<MeasureValue> {
+            self.0 += 1.0;
+            Ok(MeasureValue::new(self.0, 0.0, 10.0))
+        }
+    }
+
+    struct Failing;
+
+    impl Measure for Failing {
+        fn update(&mut self) -> Result<MeasureValue> {
+            Err(Error::Unimplemented)
+        }
+    }
+
+    #[test]
+    fn percent_is_clamped_to_range() {
+        assert_eq!(MeasureValue::new(5.0, 0.0, 10.0).percent(), 0.5);
+        assert_eq!(MeasureValue::new(20.0, 0.0, 10.0).percent(), 1.0);
+        assert_eq!(MeasureValue::new(5.0, 10.0, 10.0).percent(), 0.0);
+        assert_eq!(MeasureValue::new(5.0, 0.0, 10.0).string, "5");
+        assert_eq!(MeasureValue::new(0.256, 0.0, 1.0).string, "0.26");
+    }
+
+    #[test]
+    fn scales_bytes() {
+        assert_eq!(scale_bytes(512.0), "512 B");
+        assert_eq!(scale_bytes(1536.0), "1.5 KB");
+        assert_eq!(scale_bytes(3.0 * 1024.0 * 1024.0 * 1024.0), "3.0 GB");
+    }
+
+    #[test]
+    fn ticks_on_interval() {
+        let mut set = MeasureSet::new(Duration::from_secs(1));
+        assert_eq!(set.deadline(), None);
+
+        set.insert("count", Box::new(Counter(0.0)));
+        set.insert("broken", Box::new(Failing));
+
+        let start = Instant::now();
+        assert!(set.tick(start));
+        assert!(!set.tick(start + Duration::from_millis(500)));
+        assert!(set.tick(start + Duration::from_secs(1)));
+
+        assert_eq!(set.value("count").unwrap().number, 2.0);
+        assert!(set.value("broken").is_none());
+        assert_eq!(set.deadline(), Some(start + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn aligns_to_wall_clock() {
+        let ms = Duration::from_millis;
+        let second = Duration::from_secs(1);
+        let minute = Duration::from_secs(60);
+
+        // 12:00:00.300 with a one second interval ticks at 12:00:01.000.
+        let now = Duration::from_secs(43_200) + ms(300);
+        assert_eq!(aligned_delay(now, second, second), ms(700));
+        assert_eq!(aligned_delay(now, ms(5000), second), ms(4700));
+
+        // Minute alignment only shortens the interval that crosses the minute.
+        assert_eq!(aligned_delay(now, second, minute), second);
+        assert_eq!(aligned_delay(now + ms(59_000), second, minute), ms(700));
+
+        assert_eq!(aligned_delay(now, second, Duration::from_secs(0)), second);
+    }
+
+    #[test]
+    fn lookup_sub_values() {
+        struct Cores;
+        impl Measure for Cores {
+            fn update(&mut self) -> Result<MeasureValue> {
+                let cores = vec![MeasureValue::new(1.0, 0.0, 1.0), MeasureValue::new(2.0, 0.0, 1.0)];
+                Ok(MeasureValue::new(3.0, 0.0, 1.0).with_sub(cores))
+            }
+        }
+
+        let mut set = MeasureSet::new(Duration::from_secs(1));
+        set.insert("Cpu", Box::new(Cores));
+        set.update();
+
+        assert_eq!(set.lookup("Cpu").unwrap().number, 3.0);
+        assert_eq!(set.lookup("Cpu[1]").unwrap().number, 2.0);
+        assert!(set.lookup("Cpu[2]").is_none());
+        assert_eq!(parse_reference(" Cpu[ 0 ] "), ("Cpu", Some(0)));
+        assert_eq!(parse_reference("Cpu[x]"), ("Cpu[x]", None));
+    }
+
+    #[test]
+    fn calc_updates_after_inputs() {
+        let def = |name: &str, kind: &str, options: &str| MeasureDefinition {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            options: options.parse().unwrap(),
+        };
+
+        let mut set = MeasureSet::new(Duration::from_secs(1));
+        set.insert("Double", create(&def("Double", "calc", "formula = \"Half * 4\"")).unwrap());
+        set.insert("Half", create(&def("Half", "calc", "formula = \"Count / 2\"")).unwrap());
+        set.insert("Count", Box::new(Counter(0.0)));
+        set.order_by_dependencies().unwrap();
+
+        set.update();
+        assert_eq!(set.value("Double").unwrap().number, 2.0);
+        set.update();
+        assert_eq!(set.value("Double").unwrap().number, 4.0);
+
+        let cycle = [def("A", "calc", "formula = \"B + 1\""), def("B", "calc", "formula = \"A\"")];
+        let err = MeasureSet::from_definitions(&cycle, Duration::from_secs(1)).err().unwrap();
+        assert!(err.to_string().contains("A -> B -> A"));
+
+        let unknown = [def("A", "calc", "formula = \"Nope\"")];
+        assert!(MeasureSet::from_definitions(&unknown, Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn unknown_measure_type() {
+        let def = MeasureDefinition {
+            name: "x".to_string(),
+            kind: "nope".to_string(),
+            options: toml::Table::new(),
+        };
+        assert!(create(&def).is_err());
+    }
+}
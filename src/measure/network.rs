@@ -0,0 +1,269 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::measure::{self, Measure, MeasureValue};
+use crate::skin::MeasureDefinition;
+use crate::error::{Error, Result};
+
+/// Cumulative byte counters of one interface in `/proc/net/dev`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct InterfaceStats {
+    pub name: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// Parses `/proc/net/dev`.
+pub fn parse_net_dev(text: &str) -> Result<Vec<InterfaceStats>> {
+    let mut interfaces = Vec::new();
+
+    for (start, line) in measure::line_offsets(text) {
+        // The two header lines use `|` as separator and have no `:`.
+        let mut parts = line.splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim();
+        let rest = match parts.next() {
+            Some(r) if !line.contains('|') => r,
+            _ => continue,
+        };
+
+        let fields = rest.split_whitespace()
+            .map(|f| f.parse::<u64>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::parse(text, start, format!("invalid counters for {}: {}", name, e)))?;
+        if fields.len() < 9 {
+            return Err(Error::parse(text, start, format!("{} has {} counters, expected at least 9", name, fields.len())));
+        }
+
+        interfaces.push(InterfaceStats {
+            name: name.to_string(),
+            rx_bytes: fields[0],
+            tx_bytes: fields[8],
+        });
+    }
+
+    Ok(interfaces)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum Interface {
+    /// The non loopback interface that moved the most data since the last
+    /// update.
+    Best,
+    /// Every interface besides loopback, summed.
+    All,
+    Named(String),
+}
+
+impl From<String> for Interface {
+    fn from(s: String) -> Interface {
+        match s.as_str() {
+            "best" => Interface::Best,
+            "all" => Interface::All,
+            _ => Interface::Named(s),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    In,
+    Out,
+    Total,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    #[serde(default = "default_interface")]
+    interface: Interface,
+    #[serde(default = "default_direction")]
+    direction: Direction,
+    /// Top of the range in bytes per second. Grows with the highest rate seen
+    /// when unset.
+    max: Option<f64>,
+    #[serde(default = "default_path")]
+    path: PathBuf,
+}
+
+fn default_interface() -> Interface {
+    Interface::Best
+}
+
+fn default_direction() -> Direction {
+    Direction::Total
+}
+
+fn default_path() -> PathBuf {
+    PathBuf::from("/proc/net/dev")
+}
+
+/// Network throughput in bytes per second, with the auto scaled rate as the
+/// string. `sub` holds the in rate, out rate, total bytes in and total bytes
+/// out of the chosen interface, in that order. The totals range over both
+/// together.
+///
+/// ```toml
+/// [[measure]]
+/// name = "Download"
+/// type = "network"
+/// interface = "eth0"
+/// direction = "in"
+/// ```
+pub struct Network {
+    interface: Interface,
+    direction: Direction,
+    max: Option<f64>,
+    peak: f64,
+    path: PathBuf,
+    prev: Option<(Instant, Vec<InterfaceStats>)>,
+}
+
+impl Network {
+    pub fn new(interface: Interface, direction: Direction) -> Network {
+        Network {
+            interface,
+            direction,
+            max: None,
+            peak: 0.0,
+            path: default_path(),
+            prev: None,
+        }
+    }
+
+    pub fn from_definition(def: &MeasureDefinition) -> Result<Network> {
+        let options: Options = measure::options(def)?;
+        let mut network = Network::new(options.interface, options.direction);
+        network.max = options.max;
+        network.path = options.path;
+        Ok(network)
+    }
+
+    /// Computes the reading for `stats`, taken `elapsed` after `prev`. Rates
+    /// are zero without a previous sample.
+    pub fn sample(&mut self, stats: &[InterfaceStats], prev: Option<&[InterfaceStats]>, elapsed: Duration) -> Result<MeasureValue> {
+        let delta = |s: &InterfaceStats| -> (u64, u64) {
+            match prev.and_then(|p| p.iter().find(|o| o.name == s.name)) {
+                Some(o) => (s.rx_bytes.saturating_sub(o.rx_bytes), s.tx_bytes.saturating_sub(o.tx_bytes)),
+                None => (0, 0),
+            }
+        };
+
+        let external = stats.iter().filter(|s| s.name != "lo");
+        let picked: Vec<&InterfaceStats> = match self.interface {
+            Interface::All => external.collect(),
+            Interface::Named(ref name) => match stats.iter().find(|s| &s.name == name) {
+                Some(s) => vec![s],
+                None => return Err(Error::from(format!("no network interface {}", name))),
+            },
+            Interface::Best => external
+                .max_by_key(|s| {
+                    let (rx, tx) = delta(s);
+                    (rx + tx, s.rx_bytes + s.tx_bytes)
+                })
+                .into_iter()
+                .collect(),
+        };
+
+        let (mut rx, mut tx, mut rx_total, mut tx_total) = (0, 0, 0, 0);
+        for s in picked {
+            let (r, t) = delta(s);
+            rx += r;
+            tx += t;
+            rx_total += s.rx_bytes;
+            tx_total += s.tx_bytes;
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        let rate = |bytes: u64| if prev.is_some() && seconds > 0.0 { bytes as f64 / seconds } else { 0.0 };
+        let (rate_in, rate_out) = (rate(rx), rate(tx));
+
+        let number = match self.direction {
+            Direction::In => rate_in,
+            Direction::Out => rate_out,
+            Direction::Total => rate_in + rate_out,
+        };
+        Ok(measure::throughput(number, [rate_in, rate_out], [rx_total, tx_total], self.max, &mut self.peak))
+    }
+}
+
+impl Measure for Network {
+    fn update(&mut self) -> Result<MeasureValue> {
+        let text = fs::read_to_string(&self.path)?;
+        let stats = parse_net_dev(&text).map_err(|e| e.with_path(&self.path))?;
+
+        let now = Instant::now();
+        let prev = self.prev.take();
+        let value = match prev {
+            Some((at, ref p)) => self.sample(&stats, Some(p), now - at),
+            None => self.sample(&stats, None, Duration::from_secs(0)),
+        };
+
+        self.prev = Some((now, stats));
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NET_DEV_1: &str = include_str!("../../tests/fixtures/proc/net_dev.1");
+    const NET_DEV_2: &str = include_str!("../../tests/fixtures/proc/net_dev.2");
+
+    fn samples() -> (Vec<InterfaceStats>, Vec<InterfaceStats>) {
+        (parse_net_dev(NET_DEV_1).unwrap(), parse_net_dev(NET_DEV_2).unwrap())
+    }
+
+    #[test]
+    fn parses_net_dev() {
+        let (first, _) = samples();
+        let names: Vec<_> = first.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["lo", "eth0", "wlan0"]);
+        assert_eq!(first[1], InterfaceStats { name: "eth0".to_string(), rx_bytes: 1_000_000, tx_bytes: 200_000 });
+
+        match parse_net_dev("eth0: 1 2 3\n").unwrap_err() {
+            Error::Parse { line, ref message, .. } => assert_eq!((line, message.as_str()), (1, "eth0 has 3 counters, expected at least 9")),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn named_interface_rates() {
+        let (first, second) = samples();
+        let mut net = Network::new(Interface::Named("eth0".to_string()), Direction::In);
+
+        assert_eq!(net.sample(&first, None, Duration::from_secs(0)).unwrap().number, 0.0);
+
+        let value = net.sample(&second, Some(&first), Duration::from_secs(2)).unwrap();
+        assert_eq!(value.number, 1024.0);
+        assert_eq!(value.string, "1.0 KB/s");
+        assert_eq!(value.sub[1].number, 512.0);
+        assert_eq!(value.sub[2].number, 1_002_048.0);
+        assert_eq!(value.sub[3].string, "196.3 KB");
+        assert_eq!(value.sub[2].max, value.sub[2].number + value.sub[3].number);
+        assert!(value.sub[2].percent() > 0.8 && value.sub[2].percent() < 1.0);
+
+        let mut missing = Network::new(Interface::Named("eth9".to_string()), Direction::In);
+        assert!(missing.sample(&first, None, Duration::from_secs(0)).is_err());
+    }
+
+    #[test]
+    fn best_and_all() {
+        let (first, second) = samples();
+
+        let mut best = Network::new(Interface::Best, Direction::In);
+        assert_eq!(best.sample(&first, None, Duration::from_secs(0)).unwrap().sub[2].number, 1_000_000.0);
+        let value = best.sample(&second, Some(&first), Duration::from_secs(2)).unwrap();
+        assert_eq!(value.string, "1.0 MB/s");
+
+        let mut all = Network::new(Interface::All, Direction::Total);
+        let value = all.sample(&second, Some(&first), Duration::from_secs(2)).unwrap();
+        assert_eq!(value.number, 1024.0 + 512.0 + 1_048_576.0 + 12_144.0);
+        assert_eq!(value.percent(), 1.0);
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::Deserialize;
+
+use crate::measure::{self, Measure, MeasureValue, cpu, memory};
+use crate::skin::MeasureDefinition;
+use crate::error::{Error, Result};
+
+/// One process, read from `/proc/<pid>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    /// User plus system time in clock ticks.
+    pub ticks: u64,
+    /// Resident memory in bytes.
+    pub rss: u64,
+}
+
+/// Parses `/proc/<pid>/stat` into the pid, command name and cpu ticks.
+pub fn parse_pid_stat(text: &str) -> Result<(u32, String, u64)> {
+    // The name is in parentheses and may itself contain spaces and `)`.
+    let (open, close) = match (text.find('('), text.rfind(')')) {
+        (Some(o), Some(c)) if o < c => (o, c),
+        _ => return Err(Error::parse(text, 0, "no command name in stat")),
+    };
+
+    let pid = text[..open].trim()
+        .parse()
+        .map_err(|e| Error::parse(text, 0, format!("invalid pid: {}", e)))?;
+    let name = text[open + 1..close].to_string();
+
+    // Fields after the name start at field 3 (state); utime and stime are
+    // fields 14 and 15.
+    let fields: Vec<_> = text[close + 1..].split_whitespace().collect();
+    let field = |n: usize| -> Result<u64> {
+        fields.get(n - 3)
+            .ok_or_else(|| Error::parse(text, close + 1, format!("stat has {} fields after the name", fields.len())))?
+            .parse()
+            .map_err(|e| Error::parse(text, close + 1, format!("invalid field {}: {}", n, e)))
+    };
+
+    Ok((pid, name, field(14)? + field(15)?))
+}
+
+/// Resident memory in bytes from `/proc/<pid>/status`. Kernel threads have
+/// none.
+pub fn parse_status_rss(text: &str) -> Result<u64> {
+    for (start, line) in measure::line_offsets(text) {
+        if let Some(rest) = line.strip_prefix("VmRSS:") {
+            let kb: u64 = rest.trim().trim_end_matches("kB").trim()
+                .parse()
+                .map_err(|e| Error::parse(text, start, format!("invalid VmRSS: {}", e)))?;
+            return kb.checked_mul(1024).ok_or_else(|| Error::parse(text, start, "VmRSS too large"));
+        }
+    }
+    Ok(0)
+}
+
+/// Reads every process under `root`, usually `/proc`. Processes that exit
+/// while being read, or whose files can't be parsed, are skipped.
+pub fn read_processes(root: &Path) -> Result<Vec<ProcessInfo>> {
+    let mut processes = Vec::new();
+
+    for entry in fs::read_dir(root)? {
+        let dir = entry?.path();
+        let is_pid = dir.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.bytes().all(|b| b.is_ascii_digit()));
+        if !is_pid {
+            continue;
+        }
+
+        let (stat, status) = match (fs::read_to_string(dir.join("stat")), fs::read_to_string(dir.join("status"))) {
+            (Ok(stat), Ok(status)) => (stat, status),
+            _ => continue,
+        };
+        let process = parse_pid_stat(&stat)
+            .map_err(|e| e.with_path(dir.join("stat")))
+            .and_then(|(pid, name, ticks)| {
+                let rss = parse_status_rss(&status).map_err(|e| e.with_path(dir.join("status")))?;
+                Ok(ProcessInfo { pid, name, ticks, rss })
+            });
+        match process {
+            Ok(process) => processes.push(process),
+            Err(e) => warn!("skipping process: {}", e),
+        }
+    }
+
+    Ok(processes)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    Cpu,
+    Memory,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    #[serde(default = "default_sort")]
+    sort: Sort,
+    #[serde(default = "default_count")]
+    count: usize,
+    #[serde(default = "default_path")]
+    path: PathBuf,
+}
+
+fn default_sort() -> Sort {
+    Sort::Cpu
+}
+
+fn default_count() -> usize {
+    5
+}
+
+fn default_path() -> PathBuf {
+    PathBuf::from("/proc")
+}
+
+/// The processes using the most CPU or memory. Each entry of `sub` has the
+/// process name as its string and its CPU percent or resident bytes as its
+/// number, so `Top[0]` is the busiest process. The main value is the number
+/// of processes, with the busiest name as the string.
+///
+/// CPU is the share of the whole machine since the last update, so the
+/// first reading has every process at zero.
+///
+/// ```toml
+/// [[measure]]
+/// name = "Top"
+/// type = "process"
+/// sort = "memory"
+/// count = 3
+/// ```
+pub struct Processes {
+    sort: Sort,
+    count: usize,
+    path: PathBuf,
+    prev: Option<(u64, HashMap<u32, u64>)>,
+}
+
+impl Processes {
+    pub fn new(sort: Sort, count: usize) -> Processes {
+        Processes {
+            sort,
+            count,
+            path: default_path(),
+            prev: None,
+        }
+    }
+
+    pub fn from_definition(def: &MeasureDefinition) -> Result<Processes> {
+        let options: Options = measure::options(def)?;
+        let mut processes = Processes::new(options.sort, options.count);
+        processes.path = options.path;
+        Ok(processes)
+    }
+
+    /// Ranks `processes`. `cpu_total` is the machine's total jiffies from
+    /// `/proc/stat` and `memory_total` the size of physical memory, used as
+    /// the top of the range for memory values.
+    pub fn sample(&mut self, processes: &[ProcessInfo], cpu_total: u64, memory_total: u64) -> MeasureValue {
+        let prev = self.prev.take();
+
+        let mut ranked: Vec<(f64, &ProcessInfo)> = processes.iter()
+            .map(|p| {
+                let value = match self.sort {
+                    Sort::Memory => p.rss as f64,
+                    Sort::Cpu => match prev {
+                        Some((total, ref ticks)) if cpu_total > total => {
+                            let before = ticks.get(&p.pid).copied().unwrap_or(p.ticks);
+                            p.ticks.saturating_sub(before) as f64 / (cpu_total - total) as f64 * 100.0
+                        },
+                        _ => 0.0,
+                    },
+                };
+                (value, p)
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.pid.cmp(&b.1.pid)));
+
+        let top: Vec<_> = ranked.iter()
+            .take(self.count)
+            .map(|&(value, p)| match self.sort {
+                Sort::Cpu => MeasureValue::new(value, 0.0, 100.0).with_string(p.name.clone()),
+                Sort::Memory => MeasureValue::new(value, 0.0, memory_total as f64).with_string(p.name.clone()),
+            })
+            .collect();
+
+        self.prev = Some((cpu_total, processes.iter().map(|p| (p.pid, p.ticks)).collect()));
+
+        let busiest = top.first().map(|v| v.string.clone()).unwrap_or_default();
+        let count = processes.len() as f64;
+        MeasureValue::new(count, 0.0, count).with_string(busiest).with_sub(top)
+    }
+}
+
+impl Measure for Processes {
+    fn update(&mut self) -> Result<MeasureValue> {
+        let processes = read_processes(&self.path)?;
+
+        let stat_path = self.path.join("stat");
+        let stat = fs::read_to_string(&stat_path)?;
+        let cpu_total = cpu::parse_stat(&stat).map_err(|e| e.with_path(&stat_path))?.total.total;
+
+        let meminfo_path = self.path.join("meminfo");
+        let meminfo = fs::read_to_string(&meminfo_path)?;
+        let memory_total = memory::parse_meminfo(&meminfo).map_err(|e| e.with_path(&meminfo_path))?.total;
+
+        Ok(self.sample(&processes, cpu_total, memory_total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/procfs")
+    }
+
+    #[test]
+    fn parses_pid_files() {
+        let stat = fs::read_to_string(fixture().join("42/stat")).unwrap();
+        assert_eq!(parse_pid_stat(&stat).unwrap(), (42, "Web Content".to_string(), 1200));
+        assert_eq!(parse_pid_stat("7 (a) b)) S 1 2 3 4 5 6 7 8 9 10 11 12 13\n").unwrap(), (7, "a) b)".to_string(), 23));
+        assert!(parse_pid_stat("7 (short) S 1 2\n").is_err());
+
+        let status = fs::read_to_string(fixture().join("1/status")).unwrap();
+        assert_eq!(parse_status_rss(&status).unwrap(), 12_980 * 1024);
+        assert_eq!(parse_status_rss("Name:\tkthreadd\n").unwrap(), 0);
+        match parse_status_rss("Name:\tx\nVmRSS:\tlots kB\n").unwrap_err() {
+            Error::Parse { line, .. } => assert_eq!(line, 2),
+            e => panic!("unexpected error: {}", e),
+        }
+        assert!(parse_status_rss("VmRSS:\t18014398509481984 kB\n").is_err());
+    }
+
+    #[test]
+    fn skips_broken_processes() {
+        let processes = read_processes(&fixture()).unwrap();
+        assert_eq!(processes.len(), 3);
+        assert!(processes.iter().all(|p| p.pid != 99));
+    }
+
+    #[test]
+    fn top_by_memory() {
+        let mut top = Processes::new(Sort::Memory, 2);
+        top.path = fixture();
+        let value = top.update().unwrap();
+
+        assert_eq!(value.number, 3.0);
+        assert_eq!(value.string, "Web Content");
+        let names: Vec<_> = value.sub.iter().map(|v| v.string.as_str()).collect();
+        assert_eq!(names, vec!["Web Content", "systemd"]);
+        assert_eq!(value.sub[1].number, 12_980.0 * 1024.0);
+        assert_eq!(value.sub[1].max, 8_000_000.0 * 1024.0);
+    }
+
+    #[test]
+    fn top_by_cpu() {
+        let first = read_processes(&fixture()).unwrap();
+        let mut second = first.clone();
+        for p in second.iter_mut() {
+            p.ticks += match p.pid {
+                1 => 10,
+                42 => 50,
+                _ => 0,
+            };
+        }
+        second.push(ProcessInfo { pid: 90, name: "new".to_string(), ticks: 30, rss: 0 });
+
+        let mut top = Processes::new(Sort::Cpu, 5);
+        assert!(top.sample(&first, 1000, 0).sub.iter().all(|v| v.number == 0.0));
+
+        let value = top.sample(&second, 1200, 0);
+        let ranked: Vec<_> = value.sub.iter().map(|v| (v.string.as_str(), v.number)).collect();
+        assert_eq!(ranked, vec![("Web Content", 25.0), ("systemd", 5.0), ("kworker/0:1", 0.0), ("new", 0.0)]);
+    }
+}
@@ -0,0 +1,380 @@
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use chrono::format::{Item, StrftimeItems};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+use crate::measure::{self, Measure, MeasureValue};
+use crate::skin::MeasureDefinition;
+use crate::error::{Error, Result};
+
+/// Time zone a time is shown in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Zone {
+    Local,
+    Utc,
+    /// An IANA zone such as `Europe/Paris`.
+    Named(Tz),
+}
+
+impl TryFrom<String> for Zone {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Zone, String> {
+        match s.as_str() {
+            "local" => Ok(Zone::Local),
+            "utc" | "UTC" => Ok(Zone::Utc),
+            _ => s.parse().map(Zone::Named).map_err(|_| format!("unknown time zone {:?}", s)),
+        }
+    }
+}
+
+impl Zone {
+    /// `time` in this zone, formatted with `format`, along with its hour,
+    /// minute and second.
+    fn render(&self, time: DateTime<Utc>, format: &str) -> (String, [u32; 3]) {
+        match *self {
+            Zone::Local => render(time.with_timezone(&Local), format),
+            Zone::Utc => render(time, format),
+            Zone::Named(tz) => render(time.with_timezone(&tz), format),
+        }
+    }
+
+    /// Reads a wall clock time in this zone. An RFC 3339 time carries its
+    /// own offset and is taken as is.
+    pub fn parse(&self, s: &str) -> Result<DateTime<Utc>> {
+        if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+            return Ok(t.with_timezone(&Utc));
+        }
+
+        let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+            .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
+            .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_time(Default::default())))
+            .map_err(|_| Error::from(format!("invalid date {:?}, expected YYYY-MM-DD HH:MM:SS", s)))?;
+
+        let local = match *self {
+            Zone::Local => Local.from_local_datetime(&naive).earliest().map(|t| t.with_timezone(&Utc)),
+            Zone::Utc => Some(Utc.from_utc_datetime(&naive)),
+            Zone::Named(tz) => tz.from_local_datetime(&naive).earliest().map(|t| t.with_timezone(&Utc)),
+        };
+        local.ok_or_else(|| Error::from(format!("{:?} does not exist in the time zone", s)))
+    }
+}
+
+fn render<Z: TimeZone>(time: DateTime<Z>, format: &str) -> (String, [u32; 3])
+where
+    Z::Offset: fmt::Display,
+{
+    let text = time.format(format).to_string();
+    (text, [time.hour(), time.minute(), time.second()])
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Value {
+    /// The current time.
+    Time,
+    /// Time since the system booted.
+    Uptime,
+    /// Time since `since`, negative before it.
+    Elapsed,
+}
+
+/// Wall clock boundary to update on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    None,
+    Second,
+    Minute,
+}
+
+impl Align {
+    fn duration(self) -> Option<Duration> {
+        match self {
+            Align::None => None,
+            Align::Second => Some(Duration::from_secs(1)),
+            Align::Minute => Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    #[serde(default = "default_value")]
+    value: Value,
+    /// strftime pattern for the time, or a duration pattern (see
+    /// `format_duration`) for uptime and elapsed.
+    format: Option<String>,
+    #[serde(default = "default_zone")]
+    timezone: Zone,
+    since: Option<String>,
+    #[serde(default = "default_align")]
+    align: Align,
+    /// Only used for uptime.
+    #[serde(default = "default_path")]
+    path: PathBuf,
+}
+
+fn default_value() -> Value {
+    Value::Time
+}
+
+fn default_zone() -> Zone {
+    Zone::Local
+}
+
+fn default_align() -> Align {
+    Align::Second
+}
+
+fn default_path() -> PathBuf {
+    PathBuf::from("/proc/uptime")
+}
+
+const TIME_FORMAT: &str = "%H:%M:%S";
+const DURATION_FORMAT: &str = "%dd %H:%M:%S";
+
+/// Current time, uptime or time elapsed since a date, as a formatted string.
+///
+/// For the time, `number` is the seconds since midnight in the chosen zone
+/// and `sub` holds the hour, minute and second. For durations `number` is the
+/// total seconds and `sub` holds days, hours, minutes and seconds.
+///
+/// ```toml
+/// [[measure]]
+/// name = "Tokyo"
+/// type = "time"
+/// format = "%a %H:%M"
+/// timezone = "Asia/Tokyo"
+/// align = "minute"
+///
+/// [[measure]]
+/// name = "NewYear"
+/// type = "time"
+/// value = "elapsed"
+/// since = "2027-01-01 00:00:00"
+/// ```
+pub struct Time {
+    value: Value,
+    format: String,
+    zone: Zone,
+    since: Option<DateTime<Utc>>,
+    align: Align,
+    path: PathBuf,
+}
+
+impl Time {
+    pub fn new(value: Value, format: Option<&str>, zone: Zone) -> Result<Time> {
+        let format = match format {
+            Some(f) => f.to_string(),
+            None if value == Value::Time => TIME_FORMAT.to_string(),
+            None => DURATION_FORMAT.to_string(),
+        };
+        if value == Value::Time && StrftimeItems::new(&format).any(|i| i == Item::Error) {
+            return Err(Error::from(format!("invalid time format {:?}", format)));
+        }
+
+        Ok(Time {
+            value,
+            format,
+            zone,
+            since: None,
+            align: Align::Second,
+            path: default_path(),
+        })
+    }
+
+    pub fn from_definition(def: &MeasureDefinition) -> Result<Time> {
+        let options: Options = measure::options(def)?;
+        let error = |e: Error| Error::from(format!("measure {}: {}", def.name, e));
+
+        let mut time = Time::new(options.value, options.format.as_deref(), options.timezone).map_err(error)?;
+        time.align = options.align;
+        time.path = options.path;
+        time.since = match options.since {
+            Some(ref s) => Some(options.timezone.parse(s).map_err(error)?),
+            None if options.value == Value::Elapsed => {
+                return Err(Error::from(format!("measure {}: elapsed needs a since date", def.name)));
+            },
+            None => None,
+        };
+        Ok(time)
+    }
+
+    pub fn since(mut self, since: DateTime<Utc>) -> Time {
+        self.since = Some(since);
+        self
+    }
+
+    /// Reading of the time or elapsed value at `now`.
+    pub fn sample(&self, now: DateTime<Utc>) -> MeasureValue {
+        match self.since {
+            Some(since) if self.value == Value::Elapsed => {
+                self.sample_duration((now - since).num_seconds())
+            },
+            _ => {
+                let (text, [h, m, s]) = self.zone.render(now, &self.format);
+                let seconds = (h * 3600 + m * 60 + s) as f64;
+                MeasureValue::new(seconds, 0.0, 86_400.0)
+                    .with_string(text)
+                    .with_sub(vec![
+                        MeasureValue::new(h as f64, 0.0, 24.0),
+                        MeasureValue::new(m as f64, 0.0, 60.0),
+                        MeasureValue::new(s as f64, 0.0, 60.0),
+                    ])
+            },
+        }
+    }
+
+    /// Reading of a duration of `seconds`.
+    pub fn sample_duration(&self, seconds: i64) -> MeasureValue {
+        let parts = split(seconds.unsigned_abs());
+        let total = seconds as f64;
+        MeasureValue::new(total, 0.0, total.abs())
+            .with_string(format_duration(&self.format, seconds))
+            .with_sub(vec![
+                MeasureValue::new(parts[0] as f64, 0.0, parts[0] as f64),
+                MeasureValue::new(parts[1] as f64, 0.0, 24.0),
+                MeasureValue::new(parts[2] as f64, 0.0, 60.0),
+                MeasureValue::new(parts[3] as f64, 0.0, 60.0),
+            ])
+    }
+}
+
+impl Measure for Time {
+    fn update(&mut self) -> Result<MeasureValue> {
+        match self.value {
+            Value::Uptime => {
+                let text = fs::read_to_string(&self.path)?;
+                Ok(self.sample_duration(parse_uptime(&text).map_err(|e| e.with_path(&self.path))?))
+            },
+            _ => Ok(self.sample(Utc::now())),
+        }
+    }
+
+    fn alignment(&self) -> Option<Duration> {
+        self.align.duration()
+    }
+}
+
+/// Parses `/proc/uptime` into whole seconds.
+pub fn parse_uptime(text: &str) -> Result<i64> {
+    let first = text.split_whitespace().next().unwrap_or("");
+    first.parse::<f64>()
+        .map(|s| s as i64)
+        .map_err(|e| Error::parse(text, 0, format!("invalid uptime {:?}: {}", first, e)))
+}
+
+fn split(seconds: u64) -> [u64; 4] {
+    [seconds / 86_400, seconds / 3600 % 24, seconds / 60 % 60, seconds % 60]
+}
+
+/// Formats a duration of `seconds`. `%d` is whole days, `%H`, `%M` and `%S`
+/// the zero padded hours, minutes and seconds left over, `%h` the total hours
+/// and `%%` a percent sign. Negative durations get a leading `-`.
+pub fn format_duration(format: &str, seconds: i64) -> String {
+    let total = seconds.unsigned_abs();
+    let [days, hours, minutes, secs] = split(total);
+
+    let mut out = String::new();
+    if seconds < 0 {
+        out.push('-');
+    }
+
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let _ = match chars.next() {
+            Some('d') => write!(out, "{}", days),
+            Some('h') => write!(out, "{}", total / 3600),
+            Some('H') => write!(out, "{:02}", hours),
+            Some('M') => write!(out, "{:02}", minutes),
+            Some('S') => write!(out, "{:02}", secs),
+            Some('%') => write!(out, "%"),
+            Some(other) => write!(out, "%{}", other),
+            None => write!(out, "%"),
+        };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noon() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 18, 12, 30, 15).unwrap()
+    }
+
+    #[test]
+    fn formats_in_zones() {
+        let utc = Time::new(Value::Time, Some("%Y-%m-%d %H:%M:%S"), Zone::Utc).unwrap();
+        let value = utc.sample(noon());
+        assert_eq!(value.string, "2026-10-18 12:30:15");
+        assert_eq!(value.number, 45_015.0);
+        assert_eq!(value.sub[1].number, 30.0);
+
+        let zone = Zone::try_from("Asia/Tokyo".to_string()).unwrap();
+        let tokyo = Time::new(Value::Time, None, zone).unwrap();
+        assert_eq!(tokyo.sample(noon()).string, "21:30:15");
+        assert_eq!(tokyo.sample(noon()).sub[0].number, 21.0);
+
+        assert!(Zone::try_from("Mars/Olympus".to_string()).is_err());
+        assert!(Time::new(Value::Time, Some("%Q"), Zone::Utc).is_err());
+    }
+
+    #[test]
+    fn elapsed_and_countdown() {
+        let start = Zone::Named(chrono_tz::Europe::Paris).parse("2026-10-18 13:00:00").unwrap();
+        assert_eq!(start, Utc.with_ymd_and_hms(2026, 10, 18, 11, 0, 0).unwrap());
+
+        let elapsed = Time::new(Value::Elapsed, None, Zone::Utc).unwrap().since(start);
+        let value = elapsed.sample(noon());
+        assert_eq!(value.number, 5415.0);
+        assert_eq!(value.string, "0d 01:30:15");
+
+        let later = Zone::Utc.parse("2026-10-20T00:00:00Z").unwrap();
+        let countdown = Time::new(Value::Elapsed, Some("%h:%M"), Zone::Utc).unwrap().since(later);
+        assert_eq!(countdown.sample(noon()).string, "-35:29");
+
+        assert!(Zone::Utc.parse("yesterday").is_err());
+    }
+
+    #[test]
+    fn uptime() {
+        assert_eq!(parse_uptime("93784.52 180000.10\n").unwrap(), 93_784);
+        assert!(parse_uptime("").is_err());
+
+        let time = Time::new(Value::Uptime, None, Zone::Local).unwrap();
+        let value = time.sample_duration(93_784);
+        assert_eq!(value.string, "1d 02:03:04");
+        let parts: Vec<_> = value.sub.iter().map(|v| v.number).collect();
+        assert_eq!(parts, vec![1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(format_duration("%S%% %x", 5), "05% %x");
+    }
+
+    #[test]
+    fn definition_options() {
+        let def = |toml: &str| MeasureDefinition {
+            name: "t".to_string(),
+            kind: "time".to_string(),
+            options: toml.parse().unwrap(),
+        };
+
+        let time = Time::from_definition(&def("align = \"minute\"\ntimezone = \"utc\"")).unwrap();
+        assert_eq!(time.alignment(), Some(Duration::from_secs(60)));
+        assert!(Time::from_definition(&def("value = \"elapsed\"")).is_err());
+        assert!(Time::from_definition(&def("timezone = \"nowhere\"")).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use serde::Deserialize;
+
+use crate::canvas::{Canvas, Cap, Color, Gradient, Path, Point, Stroke};
+use crate::geometry::Rectangle;
+use crate::measure::MeasureValue;
+use crate::meter::{self, Meter};
+use crate::skin::MeterDefinition;
+use crate::error::{Error, Result};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    /// Degrees clockwise from 12 o'clock where the ring starts.
+    #[serde(default)]
+    start_angle: f32,
+    /// Degrees the full range covers, counter-clockwise when negative.
+    #[serde(default = "default_sweep")]
+    sweep: f32,
+    #[serde(default = "default_thickness")]
+    thickness: f32,
+    /// Radius of the ring's middle, fitted into the meter if unset.
+    radius: Option<f32>,
+    /// Center in the meter, its middle if unset.
+    center: Option<[f32; 2]>,
+    #[serde(default)]
+    cap: Cap,
+    #[serde(default = "default_color")]
+    color: Color,
+    /// Colors spread evenly over the full sweep, in place of `color`.
+    gradient: Option<Vec<Color>>,
+    /// Ring drawn under the full sweep.
+    track: Option<Color>,
+}
+
+fn default_sweep() -> f32 {
+    360.0
+}
+
+fn default_thickness() -> f32 {
+    4.0
+}
+
+fn default_color() -> Color {
+    Color::WHITE
+}
+
+/// A ring segment that grows around its center as its measure goes
+/// through its range, for circular gauges.
+///
+/// ```toml
+/// [[meter]]
+/// name = "CpuRing"
+/// type = "arc"
+/// measure = "Cpu"
+/// w = 64
+/// h = 64
+/// start_angle = -135
+/// sweep = 270
+/// thickness = 6
+/// cap = "round"
+/// gradient = ["#40c0ff", "#ff4040"]
+/// track = "#ffffff20"
+/// ```
+pub struct Arc {
+    start_angle: f32,
+    sweep: f32,
+    stroke: Stroke,
+    radius: Option<f32>,
+    center: Option<Point>,
+    color: Color,
+    gradient: Option<Vec<Color>>,
+    track: Option<Color>,
+    value: f64,
+}
+
+impl Arc {
+    pub fn from_definition(def: &MeterDefinition) -> Result<Arc> {
+        let options: Options = meter::options(def)?;
+        if options.thickness <= 0.0 {
+            return Err(Error::from(format!("meter {}: thickness has to be positive", def.name)));
+        }
+        if options.gradient.as_ref().is_some_and(|g| g.is_empty()) {
+            return Err(Error::from(format!("meter {}: gradient needs at least one color", def.name)));
+        }
+
+        Ok(Arc {
+            start_angle: options.start_angle,
+            sweep: options.sweep,
+            stroke: Stroke::new(options.thickness).with_cap(options.cap),
+            radius: options.radius,
+            center: options.center.map(|[x, y]| Point::new(x, y)),
+            color: options.color,
+            gradient: options.gradient,
+            track: options.track,
+            value: 0.0,
+        })
+    }
+
+    /// Center and radius of the ring in `bounds`. Unless set, the ring and
+    /// its caps fit inside.
+    fn circle(&self, bounds: Rectangle<i32>) -> (Point, f32) {
+        let center = self.center.unwrap_or_else(|| Point::new(bounds.w() as f32 / 2.0, bounds.h() as f32 / 2.0));
+        let center = center + Point::new(bounds.x() as f32, bounds.y() as f32);
+        let radius = self.radius.unwrap_or_else(|| {
+            (bounds.w().min(bounds.h()) as f32 / 2.0 - self.stroke.width / 2.0).max(0.0)
+        });
+        (center, radius)
+    }
+}
+
+impl Meter for Arc {
+    fn update(&mut self, values: &[Option<&MeasureValue>]) -> bool {
+        let value = values.first().copied().flatten().map_or(0.0, MeasureValue::percent);
+        if value == self.value {
+            return false;
+        }
+        self.value = value;
+        true
+    }
+
+    fn preferred_size(&self, _: Option<i32>) -> (i32, i32) {
+        match self.radius {
+            Some(radius) => {
+                let size = (2.0 * radius + self.stroke.width).ceil() as i32;
+                (size, size)
+            },
+            None => (0, 0),
+        }
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rectangle<i32>) {
+        let (center, radius) = self.circle(bounds);
+        // Angles are measured from 12 o'clock, the canvas measures from 3.
+        let start = (self.start_angle - 90.0).to_radians();
+        let full = self.sweep.to_radians();
+
+        if let Some(track) = self.track {
+            canvas.draw_arc(center, radius, start, full, &self.stroke, track);
+        }
+        let sweep = full * self.value as f32;
+        if sweep == 0.0 {
+            return;
+        }
+
+        match &self.gradient {
+            None => canvas.draw_arc(center, radius, start, sweep, &self.stroke, self.color),
+            Some(colors) => {
+                let mut arc = Path::new();
+                arc.arc(center, radius, radius, start, sweep);
+                canvas.fill_path_gradient(&arc.stroke(&self.stroke), &Gradient::conic(center, start, full, colors));
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(options: &str) -> MeterDefinition {
+        MeterDefinition {
+            name: "ring".to_string(),
+            kind: "arc".to_string(),
+            options: options.parse().unwrap(),
+        }
+    }
+
+    fn draw(options: &str, value: f64) -> Canvas {
+        let mut arc = Arc::from_definition(&def(options)).unwrap();
+        arc.update(&[Some(&MeasureValue::new(value, 0.0, 100.0))]);
+        let mut canvas = Canvas::new(20, 20);
+        arc.draw(&mut canvas, Rectangle::new(0, 0, 20, 20));
+        canvas
+    }
+
+    #[test]
+    fn sweeps_with_the_value() {
+        let half = draw("thickness = 4\ntrack = \"#f00\"", 50.0);
+        assert_eq!(half.pixel(10, 1), Color::WHITE);
+        assert_eq!(half.pixel(18, 10), Color::WHITE);
+        assert_eq!(half.pixel(10, 18), Color::WHITE);
+        assert_eq!(half.pixel(1, 10), Color::rgb(255, 0, 0));
+        assert_eq!(half.pixel(10, 10), Color::TRANSPARENT);
+
+        let back = draw("thickness = 4\nsweep = -360", 25.0);
+        assert_eq!((back.pixel(1, 8), back.pixel(18, 8)), (Color::WHITE, Color::TRANSPARENT));
+
+        let empty = draw("cap = \"round\"", 0.0);
+        assert!(empty.data().iter().all(|&b| b == 0));
+
+        assert!(Arc::from_definition(&def("thickness = 0")).is_err());
+        assert!(Arc::from_definition(&def("gradient = []")).is_err());
+        assert_eq!(Arc::from_definition(&def("radius = 10\nthickness = 3")).unwrap().preferred_size(None), (23, 23));
+    }
+
+    #[test]
+    fn colors_along_the_full_sweep() {
+        let ring = draw("thickness = 4\ngradient = [\"#000\", \"#fff\"]", 100.0);
+        let (top, right, left) = (ring.pixel(10, 1), ring.pixel(18, 10), ring.pixel(1, 10));
+        assert!(top.a == 255 && right.a == 255 && left.a == 255);
+        assert!(top.r < 10 && right.r.abs_diff(64) < 10 && left.r.abs_diff(191) < 10, "{:?} {:?} {:?}", top, right, left);
+    }
+}
@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::canvas::{self, Canvas, Color};
+use crate::canvas::bitmap::ImageFile;
+use crate::canvas::raster;
+use crate::geometry::Rectangle;
+use crate::measure::MeasureValue;
+use crate::meter::{self, Meter};
+use crate::skin::MeterDefinition;
+use crate::error::{Error, Result};
+
+/// Which way a bar runs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    /// Filled from the left.
+    #[default]
+    Horizontal,
+    /// Filled from the bottom.
+    Vertical,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    #[serde(default)]
+    orientation: Orientation,
+    /// Fill from the right or the top instead.
+    #[serde(default)]
+    reverse: bool,
+    #[serde(default = "default_color")]
+    color: Color,
+    /// Drawn over the whole meter and shown as far as the bar is filled, in
+    /// place of `color`. Relative to the skin.
+    image: Option<String>,
+}
+
+fn default_color() -> Color {
+    Color::WHITE
+}
+
+/// A bar filled as far as its measure is through its range.
+///
+/// ```toml
+/// [[meter]]
+/// name = "CpuBar"
+/// type = "bar"
+/// measure = "Cpu"
+/// w = 100
+/// h = 6
+/// color = "#40c0ff"
+/// background = "#ffffff30"
+/// ```
+pub struct Bar {
+    orientation: Orientation,
+    reverse: bool,
+    color: Color,
+    image: Option<ImageFile>,
+    value: f64,
+}
+
+impl Bar {
+    pub fn new(orientation: Orientation, color: Color) -> Bar {
+        Bar {
+            orientation,
+            reverse: false,
+            color,
+            image: None,
+            value: 0.0,
+        }
+    }
+
+    pub fn from_definition(def: &MeterDefinition, dir: &Path) -> Result<Bar> {
+        let options: Options = meter::options(def)?;
+        let mut bar = Bar::new(options.orientation, options.color);
+        bar.reverse = options.reverse;
+        if let Some(image) = options.image {
+            bar.image = Some(ImageFile::load(dir.join(image)).map_err(|e| Error::from(format!("meter {}: {}", def.name, e)))?);
+        }
+        Ok(bar)
+    }
+
+    /// The filled part of `bounds`.
+    fn filled(&self, bounds: Rectangle<f32>) -> Rectangle<f32> {
+        let p = self.value as f32;
+        let (x, y, w, h) = (bounds.x(), bounds.y(), bounds.w(), bounds.h());
+        match (self.orientation, self.reverse) {
+            (Orientation::Horizontal, false) => Rectangle::new(x, y, w * p, h),
+            (Orientation::Horizontal, true) => Rectangle::new(x + w * (1.0 - p), y, w * p, h),
+            (Orientation::Vertical, false) => Rectangle::new(x, y + h * (1.0 - p), w, h * p),
+            (Orientation::Vertical, true) => Rectangle::new(x, y, w, h * p),
+        }
+    }
+}
+
+impl Meter for Bar {
+    fn update(&mut self, values: &[Option<&MeasureValue>]) -> bool {
+        let value = values.first().copied().flatten().map_or(0.0, MeasureValue::percent);
+        if value == self.value {
+            return false;
+        }
+        self.value = value;
+        true
+    }
+
+    fn preferred_size(&self, _: Option<i32>) -> (i32, i32) {
+        match &self.image {
+            Some(image) => (image.size().0 as i32, image.size().1 as i32),
+            None => (0, 0),
+        }
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rectangle<i32>) {
+        let whole = Rectangle::new(bounds.x() as f32, bounds.y() as f32, bounds.w() as f32, bounds.h() as f32);
+        let filled = self.filled(whole);
+        let image = match &self.image {
+            Some(image) => image,
+            None => return canvas.fill_path(&canvas::Path::rect(filled), self.color),
+        };
+
+        // Images are cut at whole pixels.
+        let shown = Rectangle::new(
+            filled.x().round() as i32,
+            filled.y().round() as i32,
+            (filled.right().round() - filled.x().round()) as i32,
+            (filled.bottom().round() - filled.y().round()) as i32,
+        );
+        let clip = canvas.clip();
+        canvas.set_clip(raster::intersect(clip, shown));
+        let bitmap = image.best(bounds.w().max(0) as u32, bounds.h().max(0) as u32);
+        let src = Rectangle::new(0.0, 0.0, bitmap.width() as f32, bitmap.height() as f32);
+        canvas.draw_bitmap(bitmap, src, bounds, 255);
+        canvas.set_clip(clip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::bitmap::tests::encode;
+    use image::ImageFormat;
+    use std::fs;
+
+    fn def(options: &str) -> MeterDefinition {
+        MeterDefinition {
+            name: "bar".to_string(),
+            kind: "bar".to_string(),
+            options: options.parse().unwrap(),
+        }
+    }
+
+    fn draw(bar: &mut Bar, value: f64, w: i32, h: i32) -> Canvas {
+        let value = MeasureValue::new(value, 0.0, 10.0);
+        bar.update(&[Some(&value)]);
+        let mut canvas = Canvas::new(w as u32, h as u32);
+        bar.draw(&mut canvas, Rectangle::new(0, 0, w, h));
+        canvas
+    }
+
+    #[test]
+    fn fills_by_percent() {
+        let mut bar = Bar::from_definition(&def("color = \"#f00\""), Path::new("")).unwrap();
+        assert!(!bar.update(&[None]));
+        let canvas = draw(&mut bar, 2.5, 10, 1);
+        assert_eq!((canvas.pixel(1, 0), canvas.pixel(2, 0).a, canvas.pixel(3, 0)), (Color::rgb(255, 0, 0), 128, Color::TRANSPARENT));
+        assert!(!bar.update(&[Some(&MeasureValue::new(2.5, 0.0, 10.0))]));
+
+        let mut up = Bar::from_definition(&def("orientation = \"vertical\""), Path::new("")).unwrap();
+        let canvas = draw(&mut up, 50.0, 1, 4);
+        assert_eq!((canvas.pixel(0, 0), canvas.pixel(0, 3)), (Color::WHITE, Color::WHITE));
+        let canvas = draw(&mut up, 5.0, 1, 4);
+        assert_eq!((canvas.pixel(0, 1), canvas.pixel(0, 2)), (Color::TRANSPARENT, Color::WHITE));
+
+        let mut down = Bar::from_definition(&def("orientation = \"vertical\"\nreverse = true"), Path::new("")).unwrap();
+        let canvas = draw(&mut down, 5.0, 1, 4);
+        assert_eq!((canvas.pixel(0, 1), canvas.pixel(0, 2)), (Color::WHITE, Color::TRANSPARENT));
+    }
+
+    #[test]
+    fn reveals_images() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("fill.png"), encode(2, 1, &[0, 255, 0, 255, 0, 0, 255, 255], ImageFormat::Png)).unwrap();
+        let mut bar = Bar::from_definition(&def("image = \"fill.png\"\nreverse = true"), dir.path()).unwrap();
+        assert_eq!(bar.preferred_size(None), (2, 1));
+
+        let canvas = draw(&mut bar, 5.0, 4, 1);
+        let row: Vec<_> = (0..4).map(|x| canvas.pixel(x, 0)).collect();
+        assert_eq!(row, vec![Color::TRANSPARENT, Color::TRANSPARENT, Color::rgb(0, 64, 191), Color::rgb(0, 0, 255)]);
+        assert!(Bar::from_definition(&def("image = \"missing.png\""), dir.path()).is_err());
+    }
+}
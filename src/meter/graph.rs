@@ -0,0 +1,378 @@
+use std::collections::VecDeque;
+
+use serde::Deserialize;
+
+use crate::canvas::{Canvas, Color, Path, Point, Stroke};
+use crate::geometry::Rectangle;
+use crate::measure::MeasureValue;
+use crate::meter::{self, Common, Meter};
+use crate::skin::MeterDefinition;
+use crate::error::{Error, Result};
+
+/// How a graph shows its history.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Style {
+    /// A line through the values, optionally filled below.
+    Line,
+    /// One bar per value.
+    Histogram,
+}
+
+/// The last values of one measure, oldest first, dropping the oldest once
+/// full.
+#[derive(Debug, Clone, PartialEq)]
+pub struct History {
+    values: VecDeque<f64>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History {
+            values: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        if self.values.len() == self.capacity {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = f64> + '_ {
+        self.values.iter().copied()
+    }
+
+    /// The largest value kept, 0 if there are none.
+    pub fn max(&self) -> f64 {
+        self.iter().fold(0.0, f64::max)
+    }
+}
+
+/// Lines dividing the meter into equal rows and columns.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Grid {
+    #[serde(default = "default_grid_color")]
+    pub color: Color,
+    #[serde(default)]
+    pub rows: u32,
+    #[serde(default)]
+    pub columns: u32,
+}
+
+fn default_grid_color() -> Color {
+    Color::rgba(255, 255, 255, 48)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    /// Values kept per measure, the meter's width if set, one value per
+    /// pixel.
+    length: Option<usize>,
+    /// One per bound measure, the last one repeated for the rest.
+    #[serde(default = "default_colors")]
+    colors: Vec<Color>,
+    /// Top of the scale. Unset, each measure is scaled by its own range.
+    max: Option<f64>,
+    /// Scale to the largest value kept, at least `max`.
+    #[serde(default)]
+    auto_scale: bool,
+    /// Fill below lines, with `fill_colors` or the line colors at
+    /// `fill_opacity`.
+    #[serde(default)]
+    fill: bool,
+    #[serde(default)]
+    fill_colors: Vec<Color>,
+    #[serde(default = "default_fill_opacity")]
+    fill_opacity: f32,
+    #[serde(default = "default_line_width")]
+    line_width: f32,
+    grid: Option<Grid>,
+}
+
+fn default_colors() -> Vec<Color> {
+    vec![Color::WHITE]
+}
+
+fn default_fill_opacity() -> f32 {
+    0.3
+}
+
+fn default_line_width() -> f32 {
+    1.0
+}
+
+const DEFAULT_LENGTH: usize = 60;
+// Most values a graph keeps per measure, all allocated up front.
+const MAX_LENGTH: usize = 100_000;
+
+struct Series {
+    history: History,
+    // Range of the measure's last value, used without a fixed scale.
+    range: (f64, f64),
+    color: Color,
+    fill: Color,
+}
+
+/// The recent history of one or more measures, newest on the right, as
+/// lines (`type = "line"`) or bars (`type = "histogram"`).
+///
+/// ```toml
+/// [[meter]]
+/// name = "Network"
+/// type = "line"
+/// measures = ["Download", "Upload"]
+/// w = 120
+/// h = 40
+/// length = 60
+/// colors = ["#40c0ff", "#ff8040"]
+/// auto_scale = true
+/// max = 1024
+/// fill = true
+/// grid = { rows = 4, columns = 6 }
+/// ```
+pub struct Graph {
+    style: Style,
+    length: usize,
+    series: Vec<Series>,
+    max: Option<f64>,
+    auto_scale: bool,
+    fill: bool,
+    line_width: f32,
+    grid: Option<Grid>,
+}
+
+impl Graph {
+    pub fn from_definition(def: &MeterDefinition, style: Style) -> Result<Graph> {
+        let options: Options = meter::options(def)?;
+        let error = |msg: &str| Error::from(format!("meter {}: {}", def.name, msg));
+        let length = match options.length {
+            Some(length) => length,
+            None => Common::from_definition(def)?.w.map_or(DEFAULT_LENGTH, |w| w.max(0) as usize),
+        };
+        if length < 2 {
+            return Err(error("graphs need to keep at least 2 values"));
+        }
+        if length > MAX_LENGTH {
+            return Err(error(&format!("graphs can keep at most {} values", MAX_LENGTH)));
+        }
+        if options.colors.is_empty() {
+            return Err(error("colors can not be empty"));
+        }
+        if options.max.is_some_and(|max| max <= 0.0) {
+            return Err(error("max has to be positive"));
+        }
+
+        let count = def.measure_names().len().max(1);
+        let pick = |colors: &[Color], i: usize| colors.get(i).or(colors.last()).copied();
+        let series = (0..count).map(|i| {
+            let color = pick(&options.colors, i).unwrap_or(Color::WHITE);
+            Series {
+                history: History::new(length),
+                range: (0.0, 100.0),
+                color,
+                fill: pick(&options.fill_colors, i).unwrap_or_else(|| color.with_opacity(options.fill_opacity)),
+            }
+        }).collect();
+
+        Ok(Graph {
+            style,
+            length,
+            series,
+            max: options.max,
+            auto_scale: options.auto_scale,
+            fill: options.fill,
+            line_width: options.line_width,
+            grid: options.grid,
+        })
+    }
+
+    /// Maps a value of `series` onto 0 at the bottom to 1 at the top.
+    fn scaler(&self, series: &Series) -> impl Fn(f64) -> f32 {
+        let (min, max) = match (self.auto_scale, self.max) {
+            (true, max) => {
+                let largest = self.series.iter().map(|s| s.history.max()).fold(0.0, f64::max);
+                (0.0, largest.max(max.unwrap_or(0.0)))
+            },
+            (false, Some(max)) => (0.0, max),
+            (false, None) => series.range,
+        };
+        move |v| if max > min { ((v - min) / (max - min)).clamp(0.0, 1.0) as f32 } else { 0.0 }
+    }
+
+    fn draw_grid(&self, canvas: &mut Canvas, bounds: Rectangle<i32>) {
+        let grid = match self.grid {
+            Some(grid) => grid,
+            None => return,
+        };
+        for row in 1..grid.rows {
+            let y = bounds.y() + (bounds.h() as i64 * row as i64 / grid.rows as i64) as i32;
+            canvas.fill_rect(Rectangle::new(bounds.x(), y, bounds.w(), 1), grid.color);
+        }
+        for column in 1..grid.columns {
+            let x = bounds.x() + (bounds.w() as i64 * column as i64 / grid.columns as i64) as i32;
+            canvas.fill_rect(Rectangle::new(x, bounds.y(), 1, bounds.h()), grid.color);
+        }
+    }
+
+    fn draw_line(&self, canvas: &mut Canvas, series: &Series, bounds: Rectangle<f32>) {
+        let len = series.history.len();
+        if len == 0 {
+            return;
+        }
+        let step = bounds.w() / (self.length - 1) as f32;
+        let scale = self.scaler(series);
+        let points: Vec<_> = series.history.iter().enumerate().map(|(i, v)| Point::new(
+            bounds.right() - (len - 1 - i) as f32 * step,
+            bounds.bottom() - scale(v) * bounds.h(),
+        )).collect();
+
+        if self.fill {
+            let mut area = points.clone();
+            area.push(Point::new(points[len - 1].x, bounds.bottom()));
+            area.push(Point::new(points[0].x, bounds.bottom()));
+            canvas.fill_path(&Path::polygon(&area), series.fill);
+        }
+        let mut line = Path::new();
+        line.move_to(points[0]);
+        for &p in &points[1..] {
+            line.line_to(p);
+        }
+        canvas.stroke_path(&line, &Stroke::new(self.line_width), series.color);
+    }
+
+    fn draw_bars(&self, canvas: &mut Canvas, series: &Series, bounds: Rectangle<f32>) {
+        let len = series.history.len();
+        let width = bounds.w() / self.length as f32;
+        let scale = self.scaler(series);
+        for (i, v) in series.history.iter().enumerate() {
+            // Whole pixel sides, so neighbouring bars do not leave seams.
+            let left = (bounds.right() - (len - i) as f32 * width).round();
+            let right = (bounds.right() - (len - 1 - i) as f32 * width).round();
+            let height = scale(v) * bounds.h();
+            if right > left && height > 0.0 {
+                canvas.fill_path(&Path::rect(Rectangle::new(left, bounds.bottom() - height, right - left, height)), series.color);
+            }
+        }
+    }
+}
+
+impl Meter for Graph {
+    fn update(&mut self, values: &[Option<&MeasureValue>]) -> bool {
+        if values.iter().all(Option::is_none) {
+            return false;
+        }
+        for (i, series) in self.series.iter_mut().enumerate() {
+            match values.get(i).copied().flatten() {
+                Some(value) => {
+                    series.history.push(value.number);
+                    series.range = (value.min, value.max);
+                },
+                None => series.history.push(0.0),
+            }
+        }
+        true
+    }
+
+    fn preferred_size(&self, _: Option<i32>) -> (i32, i32) {
+        (self.length as i32, 0)
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rectangle<i32>) {
+        self.draw_grid(canvas, bounds);
+        let area = Rectangle::new(bounds.x() as f32, bounds.y() as f32, bounds.w() as f32, bounds.h() as f32);
+        for series in &self.series {
+            match self.style {
+                Style::Line => self.draw_line(canvas, series, area),
+                Style::Histogram => self.draw_bars(canvas, series, area),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(options: &str) -> MeterDefinition {
+        MeterDefinition {
+            name: "graph".to_string(),
+            kind: "line".to_string(),
+            options: options.parse().unwrap(),
+        }
+    }
+
+    fn push(graph: &mut Graph, numbers: &[f64]) {
+        let values: Vec<_> = numbers.iter().map(|&n| MeasureValue::new(n, 0.0, 10.0)).collect();
+        let values: Vec<_> = values.iter().map(Some).collect();
+        assert!(graph.update(&values));
+    }
+
+    #[test]
+    fn keeps_the_last_values() {
+        let mut history = History::new(3);
+        for v in [1.0, 5.0, 2.0, 3.0] {
+            history.push(v);
+        }
+        assert_eq!(history.iter().collect::<Vec<_>>(), vec![5.0, 2.0, 3.0]);
+        assert_eq!(history.max(), 5.0);
+
+        let graph = Graph::from_definition(&def("w = 30\nmeasures = [\"a\", \"b\"]\ncolors = [\"#f00\"]"), Style::Line).unwrap();
+        assert_eq!((graph.length, graph.series.len()), (30, 2));
+        assert_eq!(graph.series[1].color, Color::rgb(255, 0, 0));
+        assert_eq!(graph.series[1].fill, Color::rgba(255, 0, 0, 77));
+        assert!(Graph::from_definition(&def("length = 1"), Style::Line).is_err());
+        assert!(Graph::from_definition(&def("length = 100000000000"), Style::Line).is_err());
+        assert!(Graph::from_definition(&def("w = 200000"), Style::Line).is_err());
+        assert!(Graph::from_definition(&def("colors = []"), Style::Line).is_err());
+    }
+
+    #[test]
+    fn draws_bars_scaled() {
+        let mut graph = Graph::from_definition(&def("length = 4\nmeasures = [\"a\", \"b\"]\ncolors = [\"#fff\", \"#f00\"]"), Style::Histogram).unwrap();
+        assert!(!graph.update(&[None, None]));
+        push(&mut graph, &[10.0, 5.0]);
+        push(&mut graph, &[5.0, 0.0]);
+
+        let mut canvas = Canvas::new(4, 4);
+        graph.draw(&mut canvas, Rectangle::new(0, 0, 4, 4));
+        let column = |x| (0..4).map(|y| canvas.pixel(x, y)).collect::<Vec<_>>();
+        assert_eq!(column(1), vec![Color::TRANSPARENT; 4]);
+        let (w, r) = (Color::WHITE, Color::rgb(255, 0, 0));
+        assert_eq!(column(2), vec![w, w, r, r]);
+        assert_eq!(column(3), vec![Color::TRANSPARENT, Color::TRANSPARENT, w, w]);
+
+        let mut auto = Graph::from_definition(&def("length = 2\nauto_scale = true\nmax = 2"), Style::Histogram).unwrap();
+        push(&mut auto, &[1.0]);
+        assert_eq!((auto.scaler(&auto.series[0]))(1.0), 0.5);
+        push(&mut auto, &[4.0]);
+        assert_eq!((auto.scaler(&auto.series[0]))(1.0), 0.25);
+    }
+
+    #[test]
+    fn draws_lines_over_fill_and_grid() {
+        let options = "length = 5\nmax = 8\nfill = true\nfill_colors = [\"#00f\"]\ngrid = { rows = 2, color = \"#0f0\" }";
+        let mut graph = Graph::from_definition(&def(options), Style::Line).unwrap();
+        for _ in 0..5 {
+            push(&mut graph, &[2.5]);
+        }
+
+        let mut canvas = Canvas::new(8, 8);
+        graph.draw(&mut canvas, Rectangle::new(0, 0, 8, 8));
+        let column: Vec<_> = (0..8).map(|y| canvas.pixel(3, y)).collect();
+        let (clear, blue) = (Color::TRANSPARENT, Color::rgb(0, 0, 255));
+        assert_eq!(column, vec![clear, clear, clear, clear, Color::rgb(0, 255, 0), Color::WHITE, blue, blue]);
+    }
+}
@@ -0,0 +1,354 @@
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::Deserialize;
+
+use crate::canvas::{Bitmap, Canvas, Color};
+use crate::canvas::bitmap::ImageFile;
+use crate::geometry::Rectangle;
+use crate::measure::MeasureValue;
+use crate::meter::{self, Meter};
+use crate::skin::MeterDefinition;
+use crate::error::{Error, Result};
+
+/// How an image fills the meter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Scaled to the meter's size, ignoring the aspect ratio.
+    #[default]
+    Stretch,
+    /// Scaled to fit inside the meter and centered.
+    Fit,
+    /// Scaled to cover the meter and centered, the overflow cut off.
+    Fill,
+    /// Repeated at its own size from the top left.
+    Tile,
+}
+
+/// Borders of an image, in its pixels, that keep their size when the rest
+/// is stretched. Skins write one number for all sides or
+/// `[left, top, right, bottom]`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "SliceValue")]
+pub struct Slice {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SliceValue {
+    All(u32),
+    Sides([u32; 4]),
+}
+
+impl TryFrom<SliceValue> for Slice {
+    type Error = String;
+
+    fn try_from(v: SliceValue) -> std::result::Result<Slice, String> {
+        let [left, top, right, bottom] = match v {
+            SliceValue::All(n) => [n; 4],
+            SliceValue::Sides(sides) => sides,
+        };
+        Ok(Slice { left, top, right, bottom })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    /// File relative to the skin.
+    image: String,
+    #[serde(default)]
+    mode: Mode,
+    slice: Option<Slice>,
+    tint: Option<Color>,
+    #[serde(default)]
+    greyscale: bool,
+    #[serde(default = "default_alpha")]
+    alpha: u8,
+}
+
+fn default_alpha() -> u8 {
+    255
+}
+
+/// A PNG, JPEG, BMP or ICO file, with `%1` to `%9` in its path replaced by
+/// the bound measures' strings.
+///
+/// Without `w` and `h` the meter takes the image's size. With only `w` set
+/// the height follows the image's aspect ratio. Icons are drawn from the
+/// resolution closest to the meter's size.
+///
+/// ```toml
+/// [[meter]]
+/// name = "Cover"
+/// type = "image"
+/// measure = "CoverPath"
+/// image = "%1"
+/// w = 64
+/// h = 64
+/// mode = "fill"
+///
+/// [[meter]]
+/// name = "Frame"
+/// type = "image"
+/// image = "frame.png"
+/// slice = [4, 4, 4, 4]
+/// tint = "#80c0ff"
+/// alpha = 200
+/// ```
+pub struct Image {
+    template: String,
+    dir: PathBuf,
+    mode: Mode,
+    slice: Option<Slice>,
+    tint: Option<Color>,
+    greyscale: bool,
+    alpha: u8,
+    path: String,
+    file: Option<ImageFile>,
+}
+
+impl Image {
+    pub fn from_definition(def: &MeterDefinition, dir: &Path) -> Result<Image> {
+        let options: Options = meter::options(def)?;
+        let mut image = Image {
+            template: options.image,
+            dir: dir.to_path_buf(),
+            mode: options.mode,
+            slice: options.slice,
+            tint: options.tint,
+            greyscale: options.greyscale,
+            alpha: options.alpha,
+            path: String::new(),
+            file: None,
+        };
+
+        // A path that does not come from measures has to be there from the
+        // start. Others may not exist until the measures say so.
+        image.path = meter::substitute(&image.template, &[]);
+        if def.measure_names().is_empty() {
+            image.file = image.load().map_err(|e| Error::from(format!("meter {}: {}", def.name, e)))?;
+        }
+        Ok(image)
+    }
+
+    /// The path as currently substituted, before joining it to the skin's
+    /// directory.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn load(&self) -> Result<Option<ImageFile>> {
+        if self.path.trim().is_empty() {
+            return Ok(None);
+        }
+        let file = ImageFile::load(self.dir.join(&self.path))?;
+        if self.greyscale || self.tint.is_some() {
+            return Ok(Some(file.adjusted(self.greyscale, self.tint)));
+        }
+        Ok(Some(file))
+    }
+
+    fn draw_sliced(&self, canvas: &mut Canvas, bitmap: &Bitmap, slice: Slice, bounds: Rectangle<i32>) {
+        let (w, h) = (bitmap.width(), bitmap.height());
+        let xs = split(w, slice.left, slice.right, bounds.x(), bounds.w());
+        let ys = split(h, slice.top, slice.bottom, bounds.y(), bounds.h());
+
+        for row in 0..3 {
+            for column in 0..3 {
+                let (src_x, dest_x) = xs[column];
+                let (src_y, dest_y) = ys[row];
+                let (src_w, dest_w) = (xs[column + 1].0 - src_x, xs[column + 1].1 - dest_x);
+                let (src_h, dest_h) = (ys[row + 1].0 - src_y, ys[row + 1].1 - dest_y);
+                if src_w > 0 && src_h > 0 && dest_w > 0 && dest_h > 0 {
+                    let src = Rectangle::new(src_x as f32, src_y as f32, src_w as f32, src_h as f32);
+                    canvas.draw_bitmap(bitmap, src, Rectangle::new(dest_x, dest_y, dest_w, dest_h), self.alpha);
+                }
+            }
+        }
+    }
+}
+
+/// Source and destination edges of the three slices along one axis. The
+/// borders shrink alike when the destination is too small for them.
+fn split(len: u32, start: u32, end: u32, dest: i32, dest_len: i32) -> [(u32, i32); 4] {
+    let start = start.min(len);
+    let end = end.min(len - start);
+    let borders = (start + end) as f32;
+    let scale = if borders > dest_len as f32 { dest_len as f32 / borders } else { 1.0 };
+    let dest_start = (start as f32 * scale).round() as i32;
+    let dest_end = (end as f32 * scale).round() as i32;
+    [
+        (0, dest),
+        (start, dest + dest_start),
+        (len - end, dest + dest_len - dest_end),
+        (len, dest + dest_len),
+    ]
+}
+
+impl Meter for Image {
+    fn update(&mut self, values: &[Option<&MeasureValue>]) -> bool {
+        let path = meter::substitute(&self.template, values);
+        if path == self.path {
+            return false;
+        }
+        self.path = path;
+        self.file = self.load().unwrap_or_else(|e| {
+            warn!("{}", e);
+            None
+        });
+        true
+    }
+
+    fn preferred_size(&self, width: Option<i32>) -> (i32, i32) {
+        let (w, h) = match &self.file {
+            Some(file) => file.size(),
+            None => return (0, 0),
+        };
+        match width {
+            Some(width) if self.mode != Mode::Tile && self.slice.is_none() => {
+                (width, (width as f32 * h as f32 / w as f32).round() as i32)
+            },
+            _ => (w as i32, h as i32),
+        }
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rectangle<i32>) {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return,
+        };
+        if bounds.w() <= 0 || bounds.h() <= 0 {
+            return;
+        }
+        if let Some(slice) = self.slice {
+            return self.draw_sliced(canvas, file.largest(), slice, bounds);
+        }
+
+        let bitmap = match self.mode {
+            Mode::Tile => file.largest(),
+            _ => file.best(bounds.w() as u32, bounds.h() as u32),
+        };
+        let (w, h) = (bitmap.width() as f32, bitmap.height() as f32);
+        let (bw, bh) = (bounds.w() as f32, bounds.h() as f32);
+        let whole = Rectangle::new(0.0, 0.0, w, h);
+
+        match self.mode {
+            Mode::Stretch => canvas.draw_bitmap(bitmap, whole, bounds, self.alpha),
+            Mode::Fit => {
+                let scale = (bw / w).min(bh / h);
+                let (fw, fh) = ((w * scale).round() as i32, (h * scale).round() as i32);
+                let dest = Rectangle::new(bounds.x() + (bounds.w() - fw) / 2, bounds.y() + (bounds.h() - fh) / 2, fw, fh);
+                canvas.draw_bitmap(bitmap, whole, dest, self.alpha);
+            },
+            Mode::Fill => {
+                let scale = (bw / w).max(bh / h);
+                let (sw, sh) = (bw / scale, bh / scale);
+                let src = Rectangle::new((w - sw) / 2.0, (h - sh) / 2.0, sw, sh);
+                canvas.draw_bitmap(bitmap, src, bounds, self.alpha);
+            },
+            Mode::Tile => {
+                let (tw, th) = (bitmap.width() as i32, bitmap.height() as i32);
+                for y in (bounds.y()..bounds.bottom()).step_by(th as usize) {
+                    for x in (bounds.x()..bounds.right()).step_by(tw as usize) {
+                        canvas.draw_bitmap(bitmap, whole, Rectangle::new(x, y, tw, th), self.alpha);
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::bitmap::tests::encode;
+    use image::ImageFormat;
+    use std::fs;
+
+    fn def(options: &str) -> MeterDefinition {
+        MeterDefinition {
+            name: "picture".to_string(),
+            kind: "image".to_string(),
+            options: options.parse().unwrap(),
+        }
+    }
+
+    // A 4x2 image, red on the left half and blue on the right.
+    fn write_image(dir: &Path, name: &str) {
+        let (red, blue) = ([255, 0, 0, 255], [0, 0, 255, 255]);
+        let pixels: Vec<u8> = [red, red, blue, blue, red, red, blue, blue].concat();
+        fs::write(dir.join(name), encode(4, 2, &pixels, ImageFormat::Png)).unwrap();
+    }
+
+    #[test]
+    fn loads_images_by_measure() {
+        let dir = tempfile::tempdir().unwrap();
+        write_image(dir.path(), "a.png");
+
+        let mut image = Image::from_definition(&def("image = \"%1.png\"\nmeasure = \"Name\""), dir.path()).unwrap();
+        assert_eq!((image.path(), image.preferred_size(None)), (".png", (0, 0)));
+
+        let value = MeasureValue::new(0.0, 0.0, 1.0).with_string("a");
+        assert!(image.update(&[Some(&value)]));
+        assert!(!image.update(&[Some(&value)]));
+        assert_eq!(image.preferred_size(None), (4, 2));
+        assert_eq!(image.preferred_size(Some(8)), (8, 4));
+
+        let missing = MeasureValue::new(0.0, 0.0, 1.0).with_string("b");
+        assert!(image.update(&[Some(&missing)]));
+        assert_eq!(image.preferred_size(None), (0, 0));
+
+        assert!(Image::from_definition(&def("image = \"b.png\""), dir.path()).is_err());
+        assert!(Image::from_definition(&def("image = \"a.png\"\nmode = \"zoom\""), dir.path()).is_err());
+    }
+
+    #[test]
+    fn draws_in_modes() {
+        let dir = tempfile::tempdir().unwrap();
+        write_image(dir.path(), "a.png");
+        let draw = |options: &str, w, h| {
+            let image = Image::from_definition(&def(&format!("image = \"a.png\"\n{}", options)), dir.path()).unwrap();
+            let mut canvas = Canvas::new(w as u32, h as u32);
+            image.draw(&mut canvas, Rectangle::new(0, 0, w, h));
+            canvas
+        };
+        let (red, blue) = (Color::rgb(255, 0, 0), Color::rgb(0, 0, 255));
+
+        let fit = draw("mode = \"fit\"", 8, 8);
+        assert_eq!((fit.pixel(0, 1), fit.pixel(0, 2), fit.pixel(7, 5), fit.pixel(7, 6)), (Color::TRANSPARENT, red, blue, Color::TRANSPARENT));
+
+        let fill = draw("mode = \"fill\"", 4, 4);
+        assert_eq!((fill.pixel(0, 0), fill.pixel(3, 3)), (red, blue));
+
+        let tile = draw("mode = \"tile\"", 8, 4);
+        assert_eq!((tile.pixel(4, 2), tile.pixel(3, 3), tile.pixel(7, 0)), (red, blue, blue));
+
+        let grey = draw("greyscale = true\ntint = \"#ff0000\"\nalpha = 128", 4, 2);
+        assert_eq!(grey.pixel(0, 0), Color::rgba(76, 0, 0, 128));
+    }
+
+    #[test]
+    fn keeps_slice_borders() {
+        let dir = tempfile::tempdir().unwrap();
+        write_image(dir.path(), "a.png");
+        let image = Image::from_definition(&def("image = \"a.png\"\nslice = [1, 0, 1, 0]"), dir.path()).unwrap();
+
+        let mut canvas = Canvas::new(10, 2);
+        let bounds = canvas.bounds();
+        image.draw(&mut canvas, bounds);
+        let row: Vec<_> = (0..10).map(|x| canvas.pixel(x, 0)).collect();
+        assert_eq!(row[0], Color::rgb(255, 0, 0));
+        assert_eq!(row[9], Color::rgb(0, 0, 255));
+        assert_eq!(row[2], Color::rgb(255, 0, 0));
+        assert_eq!(row[7], Color::rgb(0, 0, 255));
+
+        assert_eq!(split(4, 3, 3, 0, 3), [(0, 0), (3, 2), (3, 2), (4, 3)]);
+    }
+}
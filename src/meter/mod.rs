@@ -0,0 +1,461 @@
+use std::convert::TryFrom;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::canvas::{Canvas, Color};
+use crate::geometry::Rectangle;
+use crate::measure::{MeasureSet, MeasureValue};
+use crate::skin::MeterDefinition;
+use crate::error::{Error, Result};
+
+pub mod arc;
+pub mod bar;
+pub mod graph;
+pub mod image;
+pub mod rotator;
+pub mod shape;
+pub mod text;
+
+/// What a meter's position along one axis is measured from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Anchor {
+    /// The window's top left corner.
+    #[default]
+    Window,
+    /// The previous meter's left or top edge, written `10r`.
+    PreviousStart,
+    /// The previous meter's right or bottom edge, written `10R`.
+    PreviousEnd,
+}
+
+/// A meter position along one axis, like `x = 10` or `x = "5R"`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "CoordinateValue")]
+pub struct Coordinate {
+    pub offset: i32,
+    pub anchor: Anchor,
+}
+
+impl Coordinate {
+    pub fn absolute(offset: i32) -> Coordinate {
+        Coordinate { offset, anchor: Anchor::Window }
+    }
+
+    /// Resolves the coordinate against the previous meter's span along the
+    /// same axis.
+    pub fn resolve(self, previous_start: i32, previous_end: i32) -> i32 {
+        match self.anchor {
+            Anchor::Window => self.offset,
+            Anchor::PreviousStart => previous_start + self.offset,
+            Anchor::PreviousEnd => previous_end + self.offset,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CoordinateValue {
+    Number(i32),
+    Text(String),
+}
+
+impl TryFrom<CoordinateValue> for Coordinate {
+    type Error = String;
+
+    fn try_from(v: CoordinateValue) -> std::result::Result<Coordinate, String> {
+        let s = match v {
+            CoordinateValue::Number(n) => return Ok(Coordinate::absolute(n)),
+            CoordinateValue::Text(s) => s,
+        };
+
+        let text = s.trim();
+        let (number, anchor) = match text.strip_suffix('r') {
+            Some(n) => (n, Anchor::PreviousStart),
+            None => match text.strip_suffix('R') {
+                Some(n) => (n, Anchor::PreviousEnd),
+                None => (text, Anchor::Window),
+            },
+        };
+        let offset = match number.trim() {
+            "" => 0,
+            n => n.parse().map_err(|_| format!("invalid position {:?}, expected a number optionally followed by r or R", s))?,
+        };
+        Ok(Coordinate { offset, anchor })
+    }
+}
+
+/// Options every meter has, next to the ones of its type.
+///
+/// ```toml
+/// [[meter]]
+/// name = "Label"
+/// type = "text"
+/// x = 5
+/// y = "2R"
+/// w = 100
+/// background = "#00000080"
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Common {
+    #[serde(default)]
+    pub x: Coordinate,
+    #[serde(default)]
+    pub y: Coordinate,
+    /// Width, the meter's own preferred width if unset.
+    pub w: Option<i32>,
+    /// Height, the meter's own preferred height if unset.
+    pub h: Option<i32>,
+    pub background: Option<Color>,
+    /// Hidden meters still take part in the layout but are not drawn.
+    #[serde(default)]
+    pub hidden: bool,
+    /// Bound measures, better read through `MeterDefinition::measure_names`.
+    #[serde(default)]
+    pub measure: Option<String>,
+    #[serde(default)]
+    pub measures: Vec<String>,
+}
+
+const COMMON: [&str; 8] = ["x", "y", "w", "h", "background", "hidden", "measure", "measures"];
+
+impl Common {
+    pub fn from_definition(def: &MeterDefinition) -> Result<Common> {
+        let table = def.options.iter()
+            .filter(|(k, _)| COMMON.contains(&k.as_str()))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        deserialize(def, table)
+    }
+}
+
+/// Deserializes the type specific options of a meter definition, leaving out
+/// the common ones.
+pub fn options<T: DeserializeOwned>(def: &MeterDefinition) -> Result<T> {
+    let table = def.options.iter()
+        .filter(|(k, _)| !COMMON.contains(&k.as_str()))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    deserialize(def, table)
+}
+
+fn deserialize<T: DeserializeOwned>(def: &MeterDefinition, table: toml::Table) -> Result<T> {
+    toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| Error::from(format!("meter {}: {}", def.name, e.message())))
+}
+
+/// Replaces `%1` to `%9` in `template` with the strings of `values`, nothing
+/// for values that are missing, and `%%` with `%`.
+pub fn substitute(template: &str, values: &[Option<&MeasureValue>]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some('%') => {
+                chars.next();
+                result.push('%');
+            },
+            Some(d @ '1'..='9') => {
+                chars.next();
+                let index = d as usize - '1' as usize;
+                if let Some(Some(value)) = values.get(index) {
+                    result.push_str(&value.string);
+                }
+            },
+            _ => result.push('%'),
+        }
+    }
+    result
+}
+
+/// Something drawn in a skin's window.
+pub trait Meter {
+    /// Takes new values of the bound measures, in the order they are bound,
+    /// `None` for measures without a value yet. Returns whether the meter
+    /// needs to be drawn again.
+    fn update(&mut self, values: &[Option<&MeasureValue>]) -> bool {
+        let _ = values;
+        false
+    }
+
+    /// Width and height the content needs, used for the sides the skin does
+    /// not set. `width` is the width the skin sets, if any, for content that
+    /// wraps.
+    fn preferred_size(&self, width: Option<i32>) -> (i32, i32) {
+        let _ = width;
+        (0, 0)
+    }
+
+    /// Draws the meter into `bounds` of `canvas`, over its background.
+    fn draw(&self, canvas: &mut Canvas, bounds: Rectangle<i32>);
+}
+
+/// Builds the meter described by `def`, with files it names relative to
+/// `dir`.
+pub fn create(def: &MeterDefinition, dir: &Path) -> Result<Box<dyn Meter>> {
+    match def.kind.as_str() {
+        "arc" => Ok(Box::new(arc::Arc::from_definition(def)?)),
+        "bar" => Ok(Box::new(bar::Bar::from_definition(def, dir)?)),
+        "histogram" => Ok(Box::new(graph::Graph::from_definition(def, graph::Style::Histogram)?)),
+        "image" => Ok(Box::new(image::Image::from_definition(def, dir)?)),
+        "line" => Ok(Box::new(graph::Graph::from_definition(def, graph::Style::Line)?)),
+        "rotator" => Ok(Box::new(rotator::Rotator::from_definition(def, dir)?)),
+        "shape" => Ok(Box::new(shape::Shape::from_definition(def)?)),
+        "text" => Ok(Box::new(text::Text::from_definition(def, dir)?)),
+        kind => Err(Error::from(format!("meter {}: unknown meter type {:?}", def.name, kind))),
+    }
+}
+
+struct Entry {
+    name: String,
+    common: Common,
+    bindings: Vec<String>,
+    meter: Box<dyn Meter>,
+    bounds: Rectangle<i32>,
+}
+
+/// The meters of one skin in drawing order, back to front.
+pub struct MeterSet {
+    meters: Vec<Entry>,
+}
+
+impl MeterSet {
+    pub fn new() -> MeterSet {
+        MeterSet {
+            meters: Vec::new(),
+        }
+    }
+
+    pub fn from_definitions(defs: &[MeterDefinition], dir: &Path) -> Result<MeterSet> {
+        let mut set = MeterSet::new();
+        for def in defs {
+            set.insert_definition(def, create(def, dir)?)?;
+        }
+        Ok(set)
+    }
+
+    /// Adds `meter` with the common options and bindings of `def`.
+    pub fn insert_definition(&mut self, def: &MeterDefinition, meter: Box<dyn Meter>) -> Result<()> {
+        let common = Common::from_definition(def)?;
+        let bindings = def.measure_names().into_iter().map(str::to_string).collect();
+        self.insert(&def.name, common, bindings, meter);
+        Ok(())
+    }
+
+    pub fn insert(&mut self, name: &str, common: Common, bindings: Vec<String>, meter: Box<dyn Meter>) {
+        self.meters.push(Entry {
+            name: name.to_string(),
+            common,
+            bindings,
+            meter,
+            bounds: Rectangle::new(0, 0, 0, 0),
+        });
+    }
+
+    /// Swaps the meter `def` names for `meter`, keeping its place. Returns false if there is no such meter.
+    pub fn replace(&mut self, def: &MeterDefinition, meter: Box<dyn Meter>) -> Result<bool> {
+        let common = Common::from_definition(def)?;
+        match self.meters.iter_mut().find(|e| e.name == def.name) {
+            Some(entry) => {
+                entry.common = common;
+                entry.bindings = def.measure_names().into_iter().map(str::to_string).collect();
+                entry.meter = meter;
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.meters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meters.is_empty()
+    }
+
+    /// Where the meter called `name` was placed by the last layout.
+    pub fn bounds(&self, name: &str) -> Option<Rectangle<i32>> {
+        self.meters.iter().find(|e| e.name == name).map(|e| e.bounds)
+    }
+
+    /// Places every meter, each relative to the one before it where asked.
+    /// Returns the width and height needed to show every visible meter.
+    pub fn layout(&mut self) -> (i32, i32) {
+        let mut previous = Rectangle::new(0, 0, 0, 0);
+        let (mut width, mut height) = (0, 0);
+
+        for entry in self.meters.iter_mut() {
+            let (preferred_w, preferred_h) = entry.meter.preferred_size(entry.common.w);
+            let x = entry.common.x.resolve(previous.x(), previous.right());
+            let y = entry.common.y.resolve(previous.y(), previous.bottom());
+            let w = entry.common.w.unwrap_or(preferred_w).max(0);
+            let h = entry.common.h.unwrap_or(preferred_h).max(0);
+
+            entry.bounds = Rectangle::new(x, y, w, h);
+            previous = entry.bounds;
+            if !entry.common.hidden {
+                width = width.max(entry.bounds.right());
+                height = height.max(entry.bounds.bottom());
+            }
+        }
+
+        (width, height)
+    }
+
+    /// Hands every meter the current values of its measures. Returns whether
+    /// any meter needs to be drawn again.
+    pub fn update(&mut self, measures: &MeasureSet) -> bool {
+        let mut changed = false;
+        for entry in self.meters.iter_mut() {
+            let values: Vec<_> = entry.bindings.iter().map(|b| measures.lookup(b)).collect();
+            changed |= entry.meter.update(&values);
+        }
+        changed
+    }
+
+    /// Draws the visible meters in order, each over its background and
+    /// clipped to its bounds.
+    pub fn draw(&self, canvas: &mut Canvas) {
+        for entry in self.meters.iter().filter(|e| !e.common.hidden) {
+            canvas.set_clip(entry.bounds);
+            if let Some(background) = entry.common.background {
+                canvas.fill_rect(entry.bounds, background);
+            }
+            entry.meter.draw(canvas, entry.bounds);
+        }
+        canvas.reset_clip();
+    }
+}
+
+impl Default for MeterSet {
+    fn default() -> MeterSet {
+        MeterSet::new()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A meter of a fixed preferred size that fills itself with its color
+    /// and grows with the value of its measure.
+    pub struct Block {
+        pub size: (i32, i32),
+        pub color: Color,
+    }
+
+    impl Meter for Block {
+        fn update(&mut self, values: &[Option<&MeasureValue>]) -> bool {
+            match values.first().copied().flatten() {
+                Some(v) if v.number as i32 != self.size.0 => {
+                    self.size.0 = v.number as i32;
+                    true
+                },
+                _ => false,
+            }
+        }
+
+        fn preferred_size(&self, _: Option<i32>) -> (i32, i32) {
+            self.size
+        }
+
+        fn draw(&self, canvas: &mut Canvas, bounds: Rectangle<i32>) {
+            canvas.fill_rect(bounds, self.color);
+        }
+    }
+
+    pub fn block(w: i32, h: i32) -> Box<dyn Meter> {
+        Box::new(Block { size: (w, h), color: Color::WHITE })
+    }
+
+    fn def(options: &str) -> MeterDefinition {
+        MeterDefinition {
+            name: "m".to_string(),
+            kind: "block".to_string(),
+            options: options.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn parses_coordinates() {
+        let common = Common::from_definition(&def("x = 5\ny = \"-3R\"\nw = 10\nbackground = \"#fff\"\ncolor = 1")).unwrap();
+        assert_eq!(common.x, Coordinate::absolute(5));
+        assert_eq!(common.y, Coordinate { offset: -3, anchor: Anchor::PreviousEnd });
+        assert_eq!(common.w, Some(10));
+        assert_eq!(common.background, Some(Color::WHITE));
+
+        let common = Common::from_definition(&def("x = \"r\"\ny = \" 7 \"")).unwrap();
+        assert_eq!(common.x, Coordinate { offset: 0, anchor: Anchor::PreviousStart });
+        assert_eq!(common.y, Coordinate::absolute(7));
+        assert!(Common::from_definition(&def("x = \"5px\"")).is_err());
+
+        #[derive(Debug, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Own {
+            color: i32,
+        }
+        assert_eq!(options::<Own>(&def("x = 5\ncolor = 1")).unwrap().color, 1);
+    }
+
+    #[test]
+    fn substitutes_measure_strings() {
+        let cpu = MeasureValue::new(42.0, 0.0, 100.0);
+        let mem = MeasureValue::new(1.5, 0.0, 8.0).with_string("1.5 GB");
+        let values = [Some(&cpu), None, Some(&mem)];
+        assert_eq!(substitute("CPU %1%, %3 used%2 %% %0", &values), "CPU 42%, 1.5 GB used % %0");
+        assert_eq!(substitute("%1", &[]), "");
+    }
+
+    #[test]
+    fn relative_layout_and_extent() {
+        let mut set = MeterSet::new();
+        let at = |x: Coordinate, y: Coordinate| Common { x, y, ..Common::default() };
+        let rel = |offset, anchor| Coordinate { offset, anchor };
+
+        set.insert("a", at(Coordinate::absolute(2), Coordinate::absolute(3)), vec![], block(10, 5));
+        set.insert("b", at(rel(4, Anchor::PreviousEnd), rel(0, Anchor::PreviousStart)), vec![], block(6, 6));
+        set.insert("c", at(rel(0, Anchor::PreviousStart), rel(1, Anchor::PreviousEnd)), vec![], block(3, 3));
+        let hidden = Common { hidden: true, ..at(Coordinate::absolute(100), Coordinate::absolute(100)) };
+        set.insert("d", hidden, vec![], block(1, 1));
+
+        assert_eq!(set.layout(), (22, 13));
+        assert_eq!(set.bounds("a"), Some(Rectangle::new(2, 3, 10, 5)));
+        assert_eq!(set.bounds("b"), Some(Rectangle::new(16, 3, 6, 6)));
+        assert_eq!(set.bounds("c"), Some(Rectangle::new(16, 10, 3, 3)));
+        assert_eq!(set.bounds("d"), Some(Rectangle::new(100, 100, 1, 1)));
+    }
+
+    #[test]
+    fn updates_and_draws() {
+        use crate::measure::tests::Counter;
+        use std::time::Duration;
+
+        let mut measures = MeasureSet::new(Duration::from_secs(1));
+        measures.insert("count", Box::new(Counter(0.0)));
+
+        let mut set = MeterSet::new();
+        let common = Common { w: Some(2), background: Some(Color::rgb(255, 0, 0)), ..Common::default() };
+        set.insert("a", common, vec!["count".to_string()], Box::new(Block { size: (0, 1), color: Color::TRANSPARENT }));
+
+        assert!(!set.update(&measures));
+        measures.update();
+        assert!(set.update(&measures));
+        assert!(!set.update(&measures));
+        assert_eq!(set.layout(), (2, 1));
+
+        let mut canvas = Canvas::new(3, 1);
+        set.draw(&mut canvas);
+        assert_eq!(canvas.pixel(1, 0), Color::rgb(255, 0, 0));
+        assert_eq!(canvas.pixel(2, 0), Color::TRANSPARENT);
+
+        assert!(create(&def(""), Path::new("")).is_err());
+    }
+}
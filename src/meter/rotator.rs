@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::canvas::{Canvas, Point, Transform};
+use crate::canvas::bitmap::ImageFile;
+use crate::geometry::Rectangle;
+use crate::measure::MeasureValue;
+use crate::meter::{self, Meter};
+use crate::skin::MeterDefinition;
+use crate::error::{Error, Result};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    /// Relative to the skin.
+    image: String,
+    /// Point of the image it turns around, in its pixels. Its center if
+    /// unset.
+    pivot: Option<[f32; 2]>,
+    /// Where the pivot sits in the meter. The meter's center if unset.
+    center: Option<[f32; 2]>,
+    /// Degrees clockwise at the bottom of the range.
+    #[serde(default)]
+    start_angle: f32,
+    /// Degrees turned from the bottom to the top of the range.
+    #[serde(default = "default_rotation")]
+    rotation: f32,
+}
+
+fn default_rotation() -> f32 {
+    360.0
+}
+
+/// An image turned around a pivot as far as its measure is through its
+/// range, for needles and dials.
+///
+/// ```toml
+/// [[meter]]
+/// name = "Needle"
+/// type = "rotator"
+/// measure = "Cpu"
+/// image = "needle.png"
+/// w = 80
+/// h = 80
+/// pivot = [3, 36]
+/// start_angle = -120
+/// rotation = 240
+/// ```
+pub struct Rotator {
+    image: ImageFile,
+    pivot: Option<Point>,
+    center: Option<Point>,
+    start_angle: f32,
+    rotation: f32,
+    value: f64,
+}
+
+impl Rotator {
+    pub fn from_definition(def: &MeterDefinition, dir: &Path) -> Result<Rotator> {
+        let options: Options = meter::options(def)?;
+        let image = ImageFile::load(dir.join(&options.image)).map_err(|e| Error::from(format!("meter {}: {}", def.name, e)))?;
+        Ok(Rotator {
+            image,
+            pivot: options.pivot.map(|[x, y]| Point::new(x, y)),
+            center: options.center.map(|[x, y]| Point::new(x, y)),
+            start_angle: options.start_angle,
+            rotation: options.rotation,
+            value: 0.0,
+        })
+    }
+
+    /// Degrees clockwise the image is turned.
+    pub fn angle(&self) -> f32 {
+        self.start_angle + self.value as f32 * self.rotation
+    }
+}
+
+impl Meter for Rotator {
+    fn update(&mut self, values: &[Option<&MeasureValue>]) -> bool {
+        let value = values.first().copied().flatten().map_or(0.0, MeasureValue::percent);
+        if value == self.value {
+            return false;
+        }
+        self.value = value;
+        true
+    }
+
+    fn preferred_size(&self, _: Option<i32>) -> (i32, i32) {
+        let (w, h) = self.image.size();
+        (w as i32, h as i32)
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rectangle<i32>) {
+        let bitmap = self.image.largest();
+        let pivot = self.pivot.unwrap_or_else(|| Point::new(bitmap.width() as f32 / 2.0, bitmap.height() as f32 / 2.0));
+        let center = self.center.unwrap_or_else(|| Point::new(bounds.w() as f32 / 2.0, bounds.h() as f32 / 2.0));
+
+        let transform = Transform::translate(-pivot.x, -pivot.y)
+            .then(Transform::rotate(self.angle().to_radians()))
+            .then(Transform::translate(bounds.x() as f32 + center.x, bounds.y() as f32 + center.y));
+        canvas.draw_bitmap_transformed(bitmap, &transform, 255);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Color;
+    use crate::canvas::bitmap::tests::encode;
+    use image::ImageFormat;
+    use std::fs;
+
+    fn def(options: &str) -> MeterDefinition {
+        MeterDefinition {
+            name: "needle".to_string(),
+            kind: "rotator".to_string(),
+            options: options.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn turns_with_the_value() {
+        let dir = tempfile::tempdir().unwrap();
+        // A needle 1 pixel wide pointing up from its pivot at the bottom.
+        fs::write(dir.path().join("needle.png"), encode(1, 4, &[255; 16], ImageFormat::Png)).unwrap();
+        let options = "image = \"needle.png\"\npivot = [0.5, 4]\nstart_angle = -90\nrotation = 180";
+        let mut rotator = Rotator::from_definition(&def(options), dir.path()).unwrap();
+        assert_eq!(rotator.preferred_size(None), (1, 4));
+
+        let draw = |rotator: &Rotator| {
+            let mut canvas = Canvas::new(9, 9);
+            rotator.draw(&mut canvas, Rectangle::new(0, 0, 9, 9));
+            canvas
+        };
+        let left = draw(&rotator);
+        assert_eq!((left.pixel(1, 4), left.pixel(7, 4), left.pixel(4, 1)), (Color::WHITE, Color::TRANSPARENT, Color::TRANSPARENT));
+
+        assert!(rotator.update(&[Some(&MeasureValue::new(50.0, 0.0, 100.0))]));
+        assert_eq!(rotator.angle(), 0.0);
+        let up = draw(&rotator);
+        assert_eq!((up.pixel(4, 1), up.pixel(1, 4)), (Color::WHITE, Color::TRANSPARENT));
+
+        assert!(Rotator::from_definition(&def("pivot = [1, 2]"), dir.path()).is_err());
+    }
+}
@@ -0,0 +1,419 @@
+use std::convert::TryFrom;
+
+use serde::Deserialize;
+
+use crate::canvas::{Canvas, Cap, Color, Gradient, Join, Path, Point, Stroke, Transform};
+use crate::canvas::gradient::Kind;
+use crate::geometry::Rectangle;
+use crate::meter::{self, Meter};
+use crate::skin::MeterDefinition;
+use crate::error::{Error, Result};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    /// Drawn in order, back to front.
+    shapes: Vec<ShapeOptions>,
+}
+
+/// One shape, set by exactly one of `rect`, `ellipse`, `arc` and `path`.
+/// Positions are in pixels from the meter's top left corner.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ShapeOptions {
+    rect: Option<RectOptions>,
+    ellipse: Option<EllipseOptions>,
+    arc: Option<ArcOptions>,
+    /// SVG path data.
+    path: Option<String>,
+    fill: Option<Paint>,
+    stroke: Option<StrokeOptions>,
+    /// Steps applied in the order written, around `origin`.
+    #[serde(default)]
+    transform: Vec<Step>,
+    #[serde(default)]
+    origin: [f32; 2],
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RectOptions {
+    #[serde(default)]
+    x: f32,
+    #[serde(default)]
+    y: f32,
+    w: f32,
+    h: f32,
+    /// Rounds the corners.
+    #[serde(default)]
+    radius: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EllipseOptions {
+    center: [f32; 2],
+    radius: Pair,
+}
+
+/// An open arc, drawn like the arc meter's ring.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ArcOptions {
+    center: [f32; 2],
+    radius: Pair,
+    /// Degrees clockwise from 12 o'clock.
+    #[serde(default)]
+    start_angle: f32,
+    /// Degrees, counter-clockwise when negative.
+    sweep: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrokeOptions {
+    #[serde(default = "default_color")]
+    color: Paint,
+    #[serde(default = "default_width")]
+    width: f32,
+    /// Lengths of dashes and the gaps between them, solid if empty.
+    #[serde(default)]
+    dash: Vec<f32>,
+    /// How far into `dash` the stroke starts.
+    #[serde(default)]
+    dash_offset: f32,
+    #[serde(default)]
+    join: Join,
+    #[serde(default)]
+    cap: Cap,
+}
+
+fn default_color() -> Paint {
+    Paint::Solid(Color::WHITE)
+}
+
+fn default_width() -> f32 {
+    1.0
+}
+
+/// A number for both axes, or one for each.
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(untagged)]
+enum Pair {
+    Both(f32),
+    Each([f32; 2]),
+}
+
+impl Pair {
+    fn xy(self) -> (f32, f32) {
+        match self {
+            Pair::Both(v) => (v, v),
+            Pair::Each([x, y]) => (x, y),
+        }
+    }
+}
+
+/// One step of a transform, like `{ rotate = 45 }`. Angles are in degrees,
+/// rotations clockwise.
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Step {
+    Translate([f32; 2]),
+    Rotate(f32),
+    Scale(Pair),
+    Skew([f32; 2]),
+}
+
+impl Step {
+    fn transform(self) -> Transform {
+        match self {
+            Step::Translate([x, y]) => Transform::translate(x, y),
+            Step::Rotate(angle) => Transform::rotate(angle.to_radians()),
+            Step::Scale(factor) => {
+                let (x, y) = factor.xy();
+                Transform::scale(x, y)
+            },
+            Step::Skew([x, y]) => Transform::skew(x.to_radians(), y.to_radians()),
+        }
+    }
+}
+
+/// What a shape is filled or stroked with: a color, or a gradient like
+/// `{ linear = { from = [0, 0], to = [0, 40] }, colors = ["#333", "#111"] }`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "PaintValue")]
+enum Paint {
+    Solid(Color),
+    Gradient(Gradient),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PaintValue {
+    Solid(Color),
+    Gradient(GradientOptions),
+}
+
+/// A gradient set by exactly one of `linear`, `radial` and `conic`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GradientOptions {
+    linear: Option<LinearOptions>,
+    radial: Option<RadialOptions>,
+    conic: Option<ConicOptions>,
+    colors: Vec<Color>,
+    /// Offsets of `colors`, 0 to 1, spread evenly if unset.
+    stops: Option<Vec<f32>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LinearOptions {
+    from: [f32; 2],
+    to: [f32; 2],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RadialOptions {
+    center: [f32; 2],
+    radius: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConicOptions {
+    center: [f32; 2],
+    /// Degrees clockwise from 12 o'clock.
+    #[serde(default)]
+    start_angle: f32,
+    #[serde(default = "default_sweep")]
+    sweep: f32,
+}
+
+fn default_sweep() -> f32 {
+    360.0
+}
+
+impl TryFrom<PaintValue> for Paint {
+    type Error = String;
+
+    fn try_from(v: PaintValue) -> std::result::Result<Paint, String> {
+        let g = match v {
+            PaintValue::Solid(color) => return Ok(Paint::Solid(color)),
+            PaintValue::Gradient(g) => g,
+        };
+
+        let kind = match (g.linear, g.radial, g.conic) {
+            (Some(l), None, None) => Kind::Linear { from: point(l.from), to: point(l.to) },
+            (None, Some(r), None) => Kind::Radial { center: point(r.center), radius: r.radius },
+            (None, None, Some(c)) => Kind::Conic {
+                center: point(c.center),
+                start: angle(c.start_angle),
+                sweep: c.sweep.to_radians(),
+            },
+            _ => return Err("a gradient needs one of linear, radial or conic".to_string()),
+        };
+        if g.colors.is_empty() {
+            return Err("a gradient needs at least one color".to_string());
+        }
+        let gradient = match g.stops {
+            None => Gradient::even(kind, &g.colors),
+            Some(stops) if stops.len() == g.colors.len() => Gradient::new(kind, stops.into_iter().zip(g.colors).collect()),
+            Some(_) => return Err("a gradient needs as many stops as colors".to_string()),
+        };
+        Ok(Paint::Gradient(gradient))
+    }
+}
+
+fn point([x, y]: [f32; 2]) -> Point {
+    Point::new(x, y)
+}
+
+// Radians the canvas measures from 3 o'clock, of degrees from 12.
+fn angle(degrees: f32) -> f32 {
+    (degrees - 90.0).to_radians()
+}
+
+/// Vector shapes filled and stroked with colors or gradients, for
+/// backgrounds and decorations without images.
+///
+/// ```toml
+/// [[meter]]
+/// name = "Panel"
+/// type = "shape"
+///
+/// [[meter.shapes]]
+/// rect = { w = 120, h = 40, radius = 6 }
+/// fill = { linear = { from = [0, 0], to = [0, 40] }, colors = ["#303030", "#101010"] }
+/// stroke = { color = "#ffffff40", width = 1, dash = [4, 2] }
+///
+/// [[meter.shapes]]
+/// path = "M 10 30 L 20 10 L 30 30 Z"
+/// fill = "#40c0ff"
+/// transform = [{ rotate = 15 }]
+/// origin = [20, 20]
+/// ```
+pub struct Shape {
+    // Outlines to fill in order, each with what it is filled with.
+    layers: Vec<(Path, Paint)>,
+}
+
+impl Shape {
+    pub fn from_definition(def: &MeterDefinition) -> Result<Shape> {
+        let options: Options = meter::options(def)?;
+        let mut layers = Vec::new();
+        for (i, shape) in options.shapes.into_iter().enumerate() {
+            add_layers(&mut layers, shape).map_err(|e| Error::from(format!("meter {}: shape {}: {}", def.name, i + 1, e)))?;
+        }
+        Ok(Shape { layers })
+    }
+}
+
+// Adds the fill, then the stroke of `shape`, placed by its transform.
+fn add_layers(layers: &mut Vec<(Path, Paint)>, shape: ShapeOptions) -> std::result::Result<(), String> {
+    let path = match (shape.rect, shape.ellipse, shape.arc, shape.path) {
+        (Some(r), None, None, None) => Path::rounded_rect(Rectangle::new(r.x, r.y, r.w, r.h), r.radius),
+        (None, Some(e), None, None) => {
+            let (rx, ry) = e.radius.xy();
+            Path::ellipse(point(e.center), rx, ry)
+        },
+        (None, None, Some(a), None) => {
+            let (rx, ry) = a.radius.xy();
+            let mut path = Path::new();
+            path.arc(point(a.center), rx, ry, angle(a.start_angle), a.sweep.to_radians());
+            path
+        },
+        (None, None, None, Some(data)) => data.parse().map_err(|e: Error| e.to_string())?,
+        _ => return Err("a shape needs one of rect, ellipse, arc or path".to_string()),
+    };
+
+    let [ox, oy] = shape.origin;
+    let transform = shape.transform.iter()
+        .fold(Transform::translate(-ox, -oy), |t, step| t.then(step.transform()))
+        .then(Transform::translate(ox, oy));
+    let place = |paint: Paint| match paint {
+        Paint::Gradient(g) => Paint::Gradient(g.transformed(&transform)),
+        solid => solid,
+    };
+
+    if let Some(fill) = shape.fill {
+        layers.push((path.transformed(&transform), place(fill)));
+    }
+    if let Some(s) = shape.stroke {
+        if s.width <= 0.0 {
+            return Err("stroke width has to be positive".to_string());
+        }
+        // Tiny dashes would take forever to cut and can't be seen anyway.
+        if s.dash.iter().any(|&l| l < 0.0 || (l > 0.0 && l < 0.01)) {
+            return Err("dash lengths have to be 0 or at least 0.01".to_string());
+        }
+        if !s.dash.is_empty() && s.dash.iter().sum::<f32>() < 1.0 {
+            return Err("dash lengths have to add up to at least 1".to_string());
+        }
+        let stroke = Stroke::new(s.width).with_cap(s.cap).with_join(s.join);
+        let outline = path.dashed(&s.dash, s.dash_offset).stroke(&stroke);
+        layers.push((outline.transformed(&transform), place(s.color)));
+    }
+    Ok(())
+}
+
+impl Meter for Shape {
+    fn preferred_size(&self, _: Option<i32>) -> (i32, i32) {
+        self.layers.iter()
+            .filter_map(|(path, _)| path.bounds())
+            .fold((0, 0), |(w, h), b| (w.max(b.right().ceil() as i32), h.max(b.bottom().ceil() as i32)))
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rectangle<i32>) {
+        let offset = Point::new(bounds.x() as f32, bounds.y() as f32);
+        for (path, paint) in &self.layers {
+            let path = path.translated(offset);
+            match paint {
+                Paint::Solid(color) => canvas.fill_path(&path, *color),
+                Paint::Gradient(g) => {
+                    canvas.fill_path_gradient(&path, &g.clone().transformed(&Transform::translate(offset.x, offset.y)));
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(options: &str) -> MeterDefinition {
+        MeterDefinition {
+            name: "shape".to_string(),
+            kind: "shape".to_string(),
+            options: options.parse().unwrap(),
+        }
+    }
+
+    fn draw(options: &str) -> Canvas {
+        let shape = Shape::from_definition(&def(options)).unwrap();
+        let mut canvas = Canvas::new(20, 20);
+        shape.draw(&mut canvas, Rectangle::new(2, 2, 18, 18));
+        canvas
+    }
+
+    #[test]
+    fn fills_and_strokes_shapes() {
+        let canvas = draw(r##"
+            [[shapes]]
+            rect = { w = 10, h = 10 }
+            fill = "#f00"
+            stroke = { color = "#00f", width = 2 }
+            [[shapes]]
+            path = "M 12 0 h 4 v 4 z"
+            fill = "#0f0"
+        "##);
+        assert_eq!(canvas.pixel(7, 7), Color::rgb(255, 0, 0));
+        assert_eq!(canvas.pixel(2, 7), Color::rgb(0, 0, 255));
+        assert_eq!(canvas.pixel(12, 7), Color::rgb(0, 0, 255));
+        assert_eq!(canvas.pixel(13, 7), Color::TRANSPARENT);
+        assert_eq!(canvas.pixel(17, 3), Color::rgb(0, 255, 0));
+        assert_eq!(canvas.pixel(15, 4), Color::TRANSPARENT);
+
+        let shape = Shape::from_definition(&def("[[shapes]]\nellipse = { center = [5, 5], radius = [5, 3] }\nstroke = {}")).unwrap();
+        assert_eq!(shape.preferred_size(None), (11, 9));
+
+        for bad in [
+            "[[shapes]]\nfill = \"#fff\"",
+            "[[shapes]]\nrect = { w = 1, h = 1 }\npath = \"M 0 0\"",
+            "[[shapes]]\npath = \"L 0 0\"",
+            "[[shapes]]\nrect = { w = 1, h = 1 }\nstroke = { width = 0 }",
+            "[[shapes]]\nrect = { w = 1, h = 1 }\nstroke = { dash = [0.000001, 2] }",
+            "[[shapes]]\nrect = { w = 1, h = 1 }\nstroke = { dash = [0.2, 0.2] }",
+            "[[shapes]]\nrect = { w = 1, h = 1 }\nfill = { colors = [\"#fff\"] }",
+            "[[shapes]]\nrect = { w = 1, h = 1 }\nfill = { radial = { center = [0, 0], radius = 1 }, colors = [\"#fff\"], stops = [0, 1] }",
+        ] {
+            assert!(Shape::from_definition(&def(bad)).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn places_gradients_and_transforms() {
+        let canvas = draw(r##"
+            [[shapes]]
+            rect = { y = 6, w = 16, h = 4 }
+            fill = { linear = { from = [0, 0], to = [16, 0] }, colors = ["#000", "#fff"] }
+            transform = [{ rotate = 90 }]
+            origin = [8, 8]
+        "##);
+        // Turned upright around the middle, running top to bottom.
+        assert_eq!(canvas.pixel(4, 10), Color::TRANSPARENT);
+        let (top, bottom) = (canvas.pixel(10, 2), canvas.pixel(10, 17));
+        assert!(top.a == 255 && bottom.a == 255 && top.r < 16 && bottom.r > 240, "{:?} {:?}", top, bottom);
+
+        let dashed = draw(r##"
+            [[shapes]]
+            arc = { center = [8, 8], radius = 6, start_angle = -90, sweep = 180 }
+            stroke = { width = 2, dash = [2], join = "miter", cap = "butt" }
+        "##);
+        assert_ne!(dashed.pixel(10, 3), Color::TRANSPARENT);
+        assert_eq!(dashed.pixel(10, 17), Color::TRANSPARENT);
+        assert!(draw("[[shapes]]\nrect = { w = 4, h = 4 }\nfill = \"#fff\"\ntransform = [{ scale = 0 }]").data().iter().all(|&b| b == 0));
+    }
+}
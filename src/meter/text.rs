@@ -0,0 +1,242 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::canvas::{Canvas, Color, Point};
+use crate::canvas::font::{self, Font, Weight};
+use crate::canvas::text::{self, Align, ShapedText, TextStyle, VAlign};
+use crate::geometry::Rectangle;
+use crate::measure::MeasureValue;
+use crate::meter::{self, Meter};
+use crate::skin::MeterDefinition;
+use crate::error::{Error, Result};
+
+/// A copy of the text drawn behind it, offset by `x` and `y` pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Shadow {
+    #[serde(default = "default_shadow_color")]
+    pub color: Color,
+    #[serde(default = "default_shadow_offset")]
+    pub x: f32,
+    #[serde(default = "default_shadow_offset")]
+    pub y: f32,
+}
+
+fn default_shadow_color() -> Color {
+    Color::rgba(0, 0, 0, 128)
+}
+
+fn default_shadow_offset() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Options {
+    #[serde(default = "default_text")]
+    text: String,
+    /// A family name, or a font file relative to the skin.
+    #[serde(default = "default_font")]
+    font: String,
+    #[serde(default = "default_size")]
+    size: f32,
+    #[serde(default)]
+    weight: Weight,
+    #[serde(default)]
+    italic: bool,
+    #[serde(default = "default_color")]
+    color: Color,
+    #[serde(default)]
+    align: Align,
+    #[serde(default)]
+    valign: VAlign,
+    #[serde(default)]
+    wrap: bool,
+    #[serde(default)]
+    ellipsis: bool,
+    #[serde(default = "default_line_height")]
+    line_height: f32,
+    shadow: Option<Shadow>,
+}
+
+fn default_text() -> String {
+    "%1".to_string()
+}
+
+fn default_font() -> String {
+    if cfg!(windows) {
+        "Segoe UI".to_string()
+    } else if cfg!(target_os = "macos") {
+        "Helvetica".to_string()
+    } else {
+        "DejaVu Sans".to_string()
+    }
+}
+
+fn default_size() -> f32 {
+    12.0
+}
+
+fn default_color() -> Color {
+    Color::WHITE
+}
+
+fn default_line_height() -> f32 {
+    1.0
+}
+
+/// Weight from which faces lighter than it are emboldened.
+const BOLD: Weight = Weight(600);
+
+/// A string, with `%1` to `%9` replaced by the bound measures' strings.
+///
+/// Without `w` the meter is as wide as its longest line. With `w` set,
+/// `wrap` breaks lines to fit it and `ellipsis` cuts what does not fit.
+///
+/// ```toml
+/// [[meter]]
+/// name = "Cpu"
+/// type = "text"
+/// measure = "Cpu"
+/// text = "CPU %1%"
+/// font = "fonts/Inter.ttf"
+/// size = 14
+/// weight = "bold"
+/// color = "#ffcc00"
+/// shadow = { color = "#000000c0", x = 1, y = 1 }
+/// ```
+pub struct Text {
+    template: String,
+    font: Font,
+    size: f32,
+    embolden: bool,
+    color: Color,
+    style: TextStyle,
+    shadow: Option<Shadow>,
+    string: String,
+    shaped: ShapedText,
+}
+
+impl Text {
+    pub fn new(template: &str, font: Font, size: f32) -> Text {
+        let string = meter::substitute(template, &[]);
+        Text {
+            shaped: ShapedText::new(&font, size, &string),
+            template: template.to_string(),
+            font,
+            size,
+            embolden: false,
+            color: default_color(),
+            style: TextStyle::default(),
+            shadow: None,
+            string,
+        }
+    }
+
+    pub fn from_definition(def: &MeterDefinition, dir: &Path) -> Result<Text> {
+        let options: Options = meter::options(def)?;
+        let error = |e: Error| Error::from(format!("meter {}: {}", def.name, e));
+        if options.size <= 0.0 {
+            return Err(error(Error::from(format!("font size must be positive, got {}", options.size))));
+        }
+
+        let font = if font::is_font_file(&options.font) {
+            Font::load(dir.join(&options.font), 0)
+        } else {
+            Font::system(&options.font, options.weight, options.italic)
+        }.map_err(error)?;
+
+        let mut text = Text::new(&options.text, font, options.size);
+        text.embolden = options.weight >= BOLD && text.font.info().weight < BOLD;
+        text.color = options.color;
+        text.style = TextStyle {
+            align: options.align,
+            valign: options.valign,
+            wrap: options.wrap,
+            ellipsis: options.ellipsis,
+            line_height: options.line_height,
+        };
+        text.shadow = options.shadow;
+        Ok(text)
+    }
+
+    /// The text as currently shown.
+    pub fn string(&self) -> &str {
+        &self.string
+    }
+}
+
+impl Meter for Text {
+    fn update(&mut self, values: &[Option<&MeasureValue>]) -> bool {
+        let string = meter::substitute(&self.template, values);
+        if string == self.string {
+            return false;
+        }
+        self.shaped = ShapedText::new(&self.font, self.size, &string);
+        self.string = string;
+        true
+    }
+
+    fn preferred_size(&self, width: Option<i32>) -> (i32, i32) {
+        let lines = self.shaped.lines(&self.style, width.map(|w| w as f32), None);
+        let (w, h) = self.shaped.extent(&lines, &self.style);
+        let (dx, dy) = self.shadow.map_or((0.0, 0.0), |s| (s.x.max(0.0), s.y.max(0.0)));
+        ((w + dx).ceil() as i32, (h + dy).ceil() as i32)
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rectangle<i32>) {
+        let bounds = Rectangle::new(bounds.x() as f32, bounds.y() as f32, bounds.w() as f32, bounds.h() as f32);
+        let lines = self.shaped.lines(&self.style, Some(bounds.w()), Some(bounds.h()));
+        let mut outline = self.shaped.outline(&lines, bounds, &self.style);
+        if self.embolden {
+            outline = text::embolden(&outline, self.size / 24.0);
+        }
+
+        if let Some(shadow) = self.shadow {
+            canvas.fill_path(&outline.translated(Point::new(shadow.x, shadow.y)), shadow.color);
+        }
+        canvas.fill_path(&outline, self.color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::font::tests::fixtures;
+
+    fn def(options: &str) -> MeterDefinition {
+        MeterDefinition {
+            name: "label".to_string(),
+            kind: "text".to_string(),
+            options: options.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn sizes_and_updates() {
+        let font = format!("font = {:?}", fixtures().join("DejaVuSans.ttf").to_str().unwrap());
+        let mut text = Text::from_definition(&def(&format!("{}\ntext = \"%1 MB\"\nsize = 20", font)), Path::new("")).unwrap();
+        assert_eq!(text.string(), " MB");
+
+        let value = MeasureValue::new(512.0, 0.0, 1024.0);
+        assert!(text.update(&[Some(&value)]));
+        assert!(!text.update(&[Some(&value)]));
+        assert_eq!(text.string(), "512 MB");
+        let (w, h) = text.preferred_size(None);
+        assert!(w > 40 && w < 100, "{}", w);
+        assert_eq!(h, 24);
+
+        let wrapped = Text::from_definition(&def(&format!("{}\ntext = \"one two three\"\nsize = 20\nwrap = true", font)), Path::new("")).unwrap();
+        assert_eq!(wrapped.preferred_size(Some(60)).1, 70);
+
+        let mut canvas = Canvas::new(100, 30);
+        text.draw(&mut canvas, Rectangle::new(0, 0, 100, 30));
+        assert!((0..100).any(|x| canvas.pixel(x, 12) == Color::WHITE));
+
+        let bold = Text::from_definition(&def(&format!("{}\nweight = \"bold\"", font)), Path::new("")).unwrap();
+        assert!(bold.embolden);
+        assert!(Text::from_definition(&def(&format!("{}\nsize = 0", font)), Path::new("")).is_err());
+        assert!(Text::from_definition(&def("font = \"missing.ttf\""), Path::new("")).is_err());
+    }
+}
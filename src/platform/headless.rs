@@ -0,0 +1,287 @@
+use std::collections::VecDeque;
+use std::thread;
+use std::time::Duration;
+
+use crate::geometry::Rectangle;
+use crate::event::{self, Event, Key, MouseButton};
+use crate::platform::{Platform, WindowId, WindowOptions};
+use crate::canvas::Canvas;
+use crate::error::{Error, Result};
+
+/// A window that only exists in memory.
+pub struct VirtualWindow {
+    pub id: WindowId,
+    pub name: String,
+    pub bounds: Rectangle<i32>,
+    pub options: WindowOptions,
+    pub visible: bool,
+    /// Premultiplied RGBA8 pixels as last presented, `bounds.w() *
+    /// bounds.h() * 4` bytes.
+    pub framebuffer: Vec<u8>,
+}
+
+enum Input {
+    Event(Event),
+    Idle,
+    Drag(WindowId, i32, i32),
+    Close(WindowId),
+}
+
+/// Backend without a display. Windows are kept in a list ordered back to
+/// front and input has to be injected by the caller.
+///
+/// `pump` delivers one queued input per call and reports the platform as
+/// finished once the queue runs dry, so `App::run` always returns. Queue idle
+/// pumps to let timers in `App::run` fire.
+pub struct Headless {
+    windows: Vec<VirtualWindow>,
+    input: VecDeque<Input>,
+    next_id: usize,
+    quit: bool,
+}
+
+impl Headless {
+    pub fn new() -> Headless {
+        Headless {
+            windows: Vec::new(),
+            input: VecDeque::new(),
+            next_id: 1,
+            quit: false,
+        }
+    }
+
+    pub fn window(&self, id: WindowId) -> Option<&VirtualWindow> {
+        self.windows.iter().find(|w| w.id == id)
+    }
+
+    pub fn window_mut(&mut self, id: WindowId) -> Option<&mut VirtualWindow> {
+        self.windows.iter_mut().find(|w| w.id == id)
+    }
+
+    pub fn windows(&self) -> &[VirtualWindow] {
+        &self.windows
+    }
+
+    /// Window ids from the bottom of the stack to the top.
+    pub fn z_order(&self) -> Vec<WindowId> {
+        self.stack().iter().map(|w| w.id).collect()
+    }
+
+    fn stack(&self) -> Vec<&VirtualWindow> {
+        let mut stack: Vec<_> = self.windows.iter().collect();
+        stack.sort_by_key(|w| w.options.z);
+        stack
+    }
+
+    pub fn raise(&mut self, id: WindowId) -> Result<()> {
+        let index = self.index(id)?;
+        let window = self.windows.remove(index);
+        self.windows.push(window);
+        Ok(())
+    }
+
+    pub fn framebuffer(&self, id: WindowId) -> Option<&[u8]> {
+        self.window(id).map(|w| w.framebuffer.as_slice())
+    }
+
+    pub fn is_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Queues a click at window relative coordinates.
+    pub fn click(&mut self, window: WindowId, button: MouseButton, x: i32, y: i32) {
+        self.input.push_back(Input::Event(Event::Click { window, button, x, y }));
+    }
+
+    /// Queues a click at screen coordinates on the top most visible window
+    /// under the point. Returns the window hit, if any.
+    pub fn click_at(&mut self, button: MouseButton, x: i32, y: i32) -> Option<WindowId> {
+        let hit = self.stack().into_iter()
+            .rev()
+            .find(|w| w.visible && !w.options.click_through && w.bounds.contains(x, y))
+            .map(|w| (w.id, w.bounds));
+
+        hit.map(|(id, bounds)| {
+            self.click(id, button, x - bounds.x(), y - bounds.y());
+            id
+        })
+    }
+
+    pub fn key(&mut self, window: WindowId, key: Key) {
+        self.input.push_back(Input::Event(Event::Key { window, key }));
+    }
+
+    /// Queues `count` pumps without input that each wait out the full timeout
+    /// they are given.
+    pub fn idle(&mut self, count: usize) {
+        for _ in 0..count {
+            self.input.push_back(Input::Idle);
+        }
+    }
+
+    /// Queues the user dragging `window` to screen position `x`, `y`. Locked
+    /// windows stay where they are.
+    pub fn drag(&mut self, window: WindowId, x: i32, y: i32) {
+        self.input.push_back(Input::Drag(window, x, y));
+    }
+
+    /// Queues a request from the "window manager" to close `window`.
+    pub fn request_close(&mut self, window: WindowId) {
+        self.input.push_back(Input::Close(window));
+    }
+
+    fn index(&self, id: WindowId) -> Result<usize> {
+        self.windows.iter()
+            .position(|w| w.id == id)
+            .ok_or_else(|| Error::from(format!("no headless window: {}", id)))
+    }
+}
+
+// Bytes of RGBA8 pixels for `bounds`, none for negative sizes.
+fn framebuffer_len(bounds: Rectangle<i32>) -> usize {
+    bounds.w().max(0) as usize * bounds.h().max(0) as usize * 4
+}
+
+impl Default for Headless {
+    fn default() -> Headless {
+        Headless::new()
+    }
+}
+
+impl Platform for Headless {
+    fn create_window(&mut self, name: &str, bounds: Rectangle<i32>, options: &WindowOptions) -> Result<WindowId> {
+        if bounds.w() < 0 || bounds.h() < 0 {
+            return Err(Error::from("Error creating window".to_string()));
+        }
+
+        let id = WindowId(self.next_id);
+        self.next_id += 1;
+
+        self.windows.push(VirtualWindow {
+            id,
+            name: name.to_string(),
+            bounds,
+            options: *options,
+            visible: false,
+            framebuffer: vec![0; framebuffer_len(bounds)],
+        });
+
+        Ok(id)
+    }
+
+    fn show_window(&mut self, id: WindowId) -> Result<()> {
+        let index = self.index(id)?;
+        self.windows[index].visible = true;
+        Ok(())
+    }
+
+    fn destroy_window(&mut self, id: WindowId) -> Result<()> {
+        let index = self.index(id)?;
+        self.windows.remove(index);
+        event::dispatch(Event::Destroyed(id));
+        Ok(())
+    }
+
+    fn configure_window(&mut self, id: WindowId, bounds: Rectangle<i32>, options: &WindowOptions) -> Result<()> {
+        let index = self.index(id)?;
+        let window = &mut self.windows[index];
+        if window.bounds.w() != bounds.w() || window.bounds.h() != bounds.h() {
+            window.framebuffer = vec![0; framebuffer_len(bounds)];
+        }
+        window.bounds = bounds;
+        window.options = *options;
+        Ok(())
+    }
+
+    fn present(&mut self, id: WindowId, canvas: &Canvas) -> Result<()> {
+        let index = self.index(id)?;
+        let window = &mut self.windows[index];
+        if canvas.width() as i32 != window.bounds.w() || canvas.height() as i32 != window.bounds.h() {
+            return Err(Error::from(format!("canvas of {}x{} presented to {}x{} window {}",
+                canvas.width(), canvas.height(), window.bounds.w(), window.bounds.h(), id)));
+        }
+        window.framebuffer.copy_from_slice(canvas.data());
+        Ok(())
+    }
+
+    fn pump(&mut self, timeout: Option<Duration>) -> Result<bool> {
+        if self.quit {
+            return Ok(false);
+        }
+
+        match self.input.pop_front() {
+            Some(Input::Event(e)) => event::dispatch(e),
+            Some(Input::Idle) => {
+                if let Some(t) = timeout {
+                    thread::sleep(t);
+                }
+            },
+            Some(Input::Drag(id, x, y)) => {
+                let index = self.index(id)?;
+                let window = &mut self.windows[index];
+                if !window.options.locked {
+                    let b = window.bounds;
+                    window.bounds = Rectangle::new(x, y, b.w(), b.h());
+                    event::dispatch(Event::Moved { window: id, x, y });
+                }
+            },
+            Some(Input::Close(id)) => self.destroy_window(id)?,
+            None => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    fn quit(&mut self) {
+        self.quit = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::ZPosition;
+
+    #[test]
+    fn click_at_hits_top_most_window() {
+        let mut platform = Headless::new();
+        let options = WindowOptions::default();
+        let bottom = platform.create_window("bottom", Rectangle::new(0, 0, 100, 100), &options).unwrap();
+        let top = platform.create_window("top", Rectangle::new(50, 50, 100, 100), &options).unwrap();
+        platform.show_window(bottom).unwrap();
+        platform.show_window(top).unwrap();
+
+        assert_eq!(platform.click_at(MouseButton::Left, 75, 75), Some(top));
+        assert_eq!(platform.click_at(MouseButton::Left, 10, 10), Some(bottom));
+        assert_eq!(platform.click_at(MouseButton::Left, 500, 500), None);
+
+        platform.raise(bottom).unwrap();
+        assert_eq!(platform.z_order(), vec![top, bottom]);
+        assert_eq!(platform.click_at(MouseButton::Left, 75, 75), Some(bottom));
+
+        let options = WindowOptions { z: ZPosition::Topmost, ..options };
+        platform.configure_window(top, Rectangle::new(50, 50, 100, 100), &options).unwrap();
+        assert_eq!(platform.z_order(), vec![bottom, top]);
+        assert_eq!(platform.click_at(MouseButton::Left, 75, 75), Some(top));
+    }
+
+    #[test]
+    fn framebuffer_matches_bounds() {
+        let mut platform = Headless::new();
+        let id = platform.create_window("w", Rectangle::new(0, 0, 4, 3), &WindowOptions::default()).unwrap();
+
+        assert_eq!(platform.framebuffer(id).unwrap().len(), 4 * 3 * 4);
+        assert_eq!(platform.window(id).unwrap().options.alpha, 255);
+
+        platform.configure_window(id, Rectangle::new(1, 1, 2, -2), &WindowOptions::default()).unwrap();
+        assert!(platform.framebuffer(id).unwrap().is_empty());
+        platform.configure_window(id, Rectangle::new(1, 1, 2, 2), &WindowOptions::default()).unwrap();
+        assert_eq!(platform.framebuffer(id).unwrap().len(), 2 * 2 * 4);
+
+        let mut canvas = Canvas::new(2, 2);
+        canvas.clear(crate::canvas::Color::rgba(255, 0, 0, 128));
+        platform.present(id, &canvas).unwrap();
+        assert_eq!(&platform.framebuffer(id).unwrap()[..4], &[128, 0, 0, 128]);
+        assert!(platform.present(id, &Canvas::new(4, 3)).is_err());
+    }
+}
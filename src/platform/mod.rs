@@ -0,0 +1,83 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::Rectangle;
+use crate::canvas::Canvas;
+use crate::error::Result;
+
+pub mod headless;
+#[cfg(windows)]
+pub mod win32;
+
+#[cfg(windows)]
+pub use self::win32::Win32 as Native;
+
+/// Backend neutral handle for a window owned by a `Platform`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WindowId(pub usize);
+
+impl fmt::Display for WindowId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+/// Where a window sits in the stack relative to other windows.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZPosition {
+    Bottom,
+    #[default]
+    Normal,
+    Topmost,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WindowOptions {
+    pub alpha: u8,
+    /// Mouse input passes through to whatever is below the window.
+    pub click_through: bool,
+    pub z: ZPosition,
+    /// Locked windows can not be dragged around by the user.
+    pub locked: bool,
+}
+
+impl Default for WindowOptions {
+    fn default() -> WindowOptions {
+        WindowOptions {
+            alpha: 255,
+            click_through: false,
+            z: ZPosition::Normal,
+            locked: false,
+        }
+    }
+}
+
+/// Window creation, message pumping and destruction for a windowing system.
+///
+/// Input and lifetime events are not returned directly; backends deliver them
+/// through the thread's `EVENT_HANDLER` so they reach `App` the same way no
+/// matter where they were raised.
+pub trait Platform {
+    fn create_window(&mut self, name: &str, bounds: Rectangle<i32>, options: &WindowOptions) -> Result<WindowId>;
+
+    fn show_window(&mut self, id: WindowId) -> Result<()>;
+
+    fn destroy_window(&mut self, id: WindowId) -> Result<()>;
+
+    /// Moves, resizes and reapplies `options` to an existing window.
+    fn configure_window(&mut self, id: WindowId, bounds: Rectangle<i32>, options: &WindowOptions) -> Result<()>;
+
+    /// Shows `canvas` as the window's content, with per pixel transparency.
+    /// The canvas has to be the size of the window.
+    fn present(&mut self, id: WindowId, canvas: &Canvas) -> Result<()>;
+
+    /// Waits for and dispatches pending platform messages, for no longer than
+    /// `timeout` if one is given. Returns `false` once the platform has been
+    /// asked to quit.
+    fn pump(&mut self, timeout: Option<Duration>) -> Result<bool>;
+
+    fn quit(&mut self);
+}
@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::time::Duration;
+
+use winapi::{
+    ctypes::{c_int, c_void},
+    shared::{
+        basetsd::LONG_PTR,
+        minwindef::{DWORD, HINSTANCE, LPARAM, LRESULT, FALSE, UINT, WPARAM},
+        ntdef::LPCWSTR,
+        windef::{HBRUSH, HICON, HMENU, HWND, POINT, HDC, RECT, HCURSOR, SIZE, HGDIOBJ},
+    },
+    um::{
+        libloaderapi,
+        wingdi::{self, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, BLENDFUNCTION, AC_SRC_OVER, AC_SRC_ALPHA},
+        winuser::{
+            self, WM_DESTROY, WNDCLASSW, MSG, SW_SHOW, VK_ESCAPE, ULW_ALPHA,
+            WM_CHAR, WM_LBUTTONUP, WM_RBUTTONUP, WM_MBUTTONUP, WS_POPUP, WS_EX_TOOLWINDOW, WS_EX_LAYERED, WS_EX_TRANSPARENT, WS_EX_TOPMOST,
+            WM_LBUTTONDOWN, WM_NCLBUTTONDOWN, WM_EXITSIZEMOVE, HTCAPTION, GWLP_USERDATA, GWL_EXSTYLE,
+            HWND_BOTTOM, HWND_TOPMOST, HWND_NOTOPMOST, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE,
+            WM_QUIT, PM_REMOVE, QS_ALLINPUT,
+        },
+        winbase::{INFINITE, WAIT_FAILED},
+        errhandlingapi,
+    },
+};
+
+use log::debug;
+
+use crate::geometry::Rectangle;
+use crate::tray_old::Application;
+use crate::util::to_os_string;
+use crate::event::{self, Event, Key, MouseButton};
+use crate::platform::{Platform, WindowId, WindowOptions, ZPosition};
+use crate::canvas::Canvas;
+use crate::error::{Error, Result};
+
+impl From<HWND> for WindowId {
+    fn from(hwnd: HWND) -> WindowId {
+        WindowId(hwnd as usize)
+    }
+}
+
+impl WindowId {
+    fn hwnd(self) -> HWND {
+        self.0 as HWND
+    }
+}
+
+// Lock state is kept in the window's user data so `window_proc` can see it.
+unsafe fn is_locked(hwnd: HWND) -> bool {
+    winuser::GetWindowLongPtrW(hwnd, GWLP_USERDATA) != 0
+}
+
+unsafe fn apply_z(hwnd: HWND, z: ZPosition) {
+    let after = match z {
+        ZPosition::Bottom => HWND_BOTTOM,
+        ZPosition::Normal => HWND_NOTOPMOST,
+        ZPosition::Topmost => HWND_TOPMOST,
+    };
+    winuser::SetWindowPos(hwnd, after, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE);
+}
+
+fn ex_style(options: &WindowOptions) -> DWORD {
+    let mut flags = WS_EX_TOOLWINDOW | WS_EX_LAYERED;
+    if options.click_through {
+        flags |= WS_EX_TRANSPARENT;
+    }
+    if options.z == ZPosition::Topmost {
+        flags |= WS_EX_TOPMOST;
+    }
+    flags
+}
+
+// Window opacity applied on top of the per pixel alpha of the content.
+fn blend(alpha: u8) -> BLENDFUNCTION {
+    BLENDFUNCTION {
+        BlendOp: AC_SRC_OVER,
+        BlendFlags: 0,
+        SourceConstantAlpha: alpha,
+        AlphaFormat: AC_SRC_ALPHA,
+    }
+}
+
+unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, w_param: WPARAM, l_param: LPARAM) -> LRESULT {
+    let x = (l_param & 0xffff) as i16 as i32;
+    let y = ((l_param >> 16) & 0xffff) as i16 as i32;
+
+    match msg {
+        WM_DESTROY => {
+            event::dispatch(Event::Destroyed(WindowId::from(hwnd)));
+            return 0;
+        },
+        WM_CHAR => {
+            let key = if w_param as c_int == VK_ESCAPE {
+                Key::Escape
+            } else {
+                match std::char::from_u32(w_param as u32) {
+                    Some(c) => Key::Char(c),
+                    None => return 0,
+                }
+            };
+            event::dispatch(Event::Key { window: WindowId::from(hwnd), key });
+            return 0;
+        },
+        WM_LBUTTONDOWN => {
+            if !is_locked(hwnd) {
+                let mut pt = POINT { x, y };
+                winuser::ClientToScreen(hwnd, &mut pt);
+
+                // Unlocked windows consume the button up while checking for a
+                // drag, so the click has to be raised here instead.
+                if winuser::DragDetect(hwnd, pt) != 0 {
+                    winuser::ReleaseCapture();
+                    winuser::SendMessageW(hwnd, WM_NCLBUTTONDOWN, HTCAPTION as WPARAM, 0);
+                } else {
+                    event::dispatch(Event::Click { window: WindowId::from(hwnd), button: MouseButton::Left, x, y });
+                }
+            }
+            return 0;
+        },
+        WM_LBUTTONUP => {
+            if is_locked(hwnd) {
+                event::dispatch(Event::Click { window: WindowId::from(hwnd), button: MouseButton::Left, x, y });
+            }
+            return 0;
+        },
+        WM_EXITSIZEMOVE => {
+            let mut rect = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+            if winuser::GetWindowRect(hwnd, &mut rect) != 0 {
+                event::dispatch(Event::Moved { window: WindowId::from(hwnd), x: rect.left, y: rect.top });
+            }
+            return 0;
+        },
+        WM_RBUTTONUP => {
+            event::dispatch(Event::Click { window: WindowId::from(hwnd), button: MouseButton::Right, x, y });
+            return 0;
+        },
+        WM_MBUTTONUP => {
+            event::dispatch(Event::Click { window: WindowId::from(hwnd), button: MouseButton::Middle, x, y });
+            return 0;
+        },
+        _ => return winuser::DefWindowProcW(hwnd, msg, w_param, l_param),
+    };
+}
+
+pub struct Win32 {
+    tray: Application,
+
+    running: Arc<AtomicBool>,
+
+    msg: MSG,
+
+    #[allow(dead_code)]
+    instance: HINSTANCE,
+
+    class: WNDCLASSW,
+
+    // Backs `class.lpszClassName` for as long as the class is in use.
+    #[allow(dead_code)]
+    class_name: Vec<u16>,
+
+    // Opacity of each window, needed whenever its content is updated.
+    alphas: HashMap<WindowId, u8>,
+}
+
+impl Win32 {
+    pub fn new() -> Result<Win32> {
+        let class_name = to_os_string("Sample Window Class");
+
+        unsafe {
+            let hinstance: HINSTANCE = libloaderapi::GetModuleHandleW(std::ptr::null_mut());
+            let wnd = WNDCLASSW {
+                style: 0,
+                lpfnWndProc: Some(window_proc),
+                cbClsExtra: 0,
+                cbWndExtra: 0,
+                hInstance: hinstance,
+                hIcon: 0 as HICON,
+                hCursor: 0 as HCURSOR,
+                hbrBackground: 0 as HBRUSH,
+                lpszMenuName: 0 as LPCWSTR,
+                lpszClassName: class_name.as_ptr(),
+            };
+            if winuser::RegisterClassW(&wnd) == 0 {
+                return Err(Error::from("Error creating window class".to_string()));
+            }
+
+            let mut tray = Application::new().unwrap();
+
+            tray.set_icon("./src/rust.ico").unwrap();
+
+            let running = Arc::new(AtomicBool::new(false));
+
+            tray.add_menu_item("filler", |_| {
+                println!("filler button");
+                Ok::<_, Error>(())
+            }).unwrap();
+
+            tray.add_menu_separator().unwrap();
+
+            let running_clone = running.clone();
+            tray.add_menu_item("Quit", move |window| {
+                println!("Quitting app");
+                window.quit();
+                running_clone.store(true, Ordering::SeqCst);
+                Ok::<_, Error>(())
+            }).unwrap();
+
+            tray.set_tooltip("luna").unwrap();
+
+            Ok(Win32 {
+                tray,
+                running,
+                msg: MSG {
+                    hwnd: 0 as HWND,
+                    message: 0 as UINT,
+                    wParam: 0 as WPARAM,
+                    lParam: 0 as LPARAM,
+                    time: 0 as DWORD,
+                    pt: POINT { x: 0, y: 0 },
+                },
+                instance: hinstance,
+                class: wnd,
+                class_name,
+                alphas: HashMap::new(),
+            })
+        }
+    }
+}
+
+impl Platform for Win32 {
+    fn create_window(&mut self, name: &str, bounds: Rectangle<i32>, options: &WindowOptions) -> Result<WindowId> {
+        unsafe {
+            let hwnd = winuser::CreateWindowExW(
+                ex_style(options),
+                self.class.lpszClassName,
+                to_os_string(name).as_ptr(),
+                WS_POPUP,
+                bounds.x(),
+                bounds.y(),
+                bounds.w(),
+                bounds.h(),
+                0 as HWND,
+                0 as HMENU,
+                self.class.hInstance,
+                std::ptr::null_mut(),
+            );
+
+            if hwnd == std::ptr::null_mut() {
+                return Err(Error::from("Error creating window".to_string()));
+            }
+
+            winuser::SetWindowLongPtrW(hwnd, GWLP_USERDATA, options.locked as LONG_PTR);
+            if options.z == ZPosition::Bottom {
+                apply_z(hwnd, options.z);
+            }
+            debug!("window created: {:?}", hwnd);
+
+            let id = WindowId::from(hwnd);
+            self.alphas.insert(id, options.alpha);
+            Ok(id)
+        }
+    }
+
+    fn show_window(&mut self, id: WindowId) -> Result<()> {
+        unsafe {
+            winuser::ShowWindow(id.hwnd(), SW_SHOW);
+        }
+        Ok(())
+    }
+
+    fn destroy_window(&mut self, id: WindowId) -> Result<()> {
+        unsafe {
+            if winuser::DestroyWindow(id.hwnd()) == 0 {
+                return Err(Error::from(errhandlingapi::GetLastError()));
+            }
+        }
+        self.alphas.remove(&id);
+        Ok(())
+    }
+
+    fn configure_window(&mut self, id: WindowId, bounds: Rectangle<i32>, options: &WindowOptions) -> Result<()> {
+        let hwnd = id.hwnd();
+        unsafe {
+            winuser::SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style(options) as LONG_PTR);
+            winuser::SetWindowLongPtrW(hwnd, GWLP_USERDATA, options.locked as LONG_PTR);
+
+            // Without a source only the opacity of the current content changes.
+            let mut blend = blend(options.alpha);
+            winuser::UpdateLayeredWindow(hwnd, 0 as HDC, std::ptr::null_mut(), std::ptr::null_mut(), 0 as HDC, std::ptr::null_mut(), 0, &mut blend, ULW_ALPHA);
+            self.alphas.insert(id, options.alpha);
+
+            apply_z(hwnd, options.z);
+            if winuser::MoveWindow(hwnd, bounds.x(), bounds.y(), bounds.w(), bounds.h(), 1) == 0 {
+                return Err(Error::from(errhandlingapi::GetLastError()));
+            }
+        }
+        Ok(())
+    }
+
+    fn present(&mut self, id: WindowId, canvas: &Canvas) -> Result<()> {
+        let (w, h) = (canvas.width() as i32, canvas.height() as i32);
+        if w == 0 || h == 0 {
+            return Ok(());
+        }
+
+        unsafe {
+            let screen = winuser::GetDC(0 as HWND);
+            let dc = wingdi::CreateCompatibleDC(screen);
+
+            let mut info: BITMAPINFO = std::mem::zeroed();
+            info.bmiHeader.biSize = std::mem::size_of::<BITMAPINFOHEADER>() as DWORD;
+            info.bmiHeader.biWidth = w;
+            // Negative for rows from the top, like the canvas.
+            info.bmiHeader.biHeight = -h;
+            info.bmiHeader.biPlanes = 1;
+            info.bmiHeader.biBitCount = 32;
+            info.bmiHeader.biCompression = BI_RGB;
+
+            let mut bits: *mut c_void = std::ptr::null_mut();
+            let bitmap = wingdi::CreateDIBSection(dc, &info, DIB_RGB_COLORS, &mut bits, std::ptr::null_mut(), 0);
+            if bitmap.is_null() {
+                let error = errhandlingapi::GetLastError();
+                wingdi::DeleteDC(dc);
+                winuser::ReleaseDC(0 as HWND, screen);
+                return Err(Error::from(error));
+            }
+
+            // Layered windows take premultiplied BGRA.
+            let pixels = std::slice::from_raw_parts_mut(bits as *mut u8, canvas.data().len());
+            for (dst, src) in pixels.chunks_exact_mut(4).zip(canvas.data().chunks_exact(4)) {
+                dst.copy_from_slice(&[src[2], src[1], src[0], src[3]]);
+            }
+
+            let old = wingdi::SelectObject(dc, bitmap as HGDIOBJ);
+            let mut size = SIZE { cx: w, cy: h };
+            let mut source = POINT { x: 0, y: 0 };
+            let mut blend = blend(self.alphas.get(&id).copied().unwrap_or(255));
+            let updated = winuser::UpdateLayeredWindow(id.hwnd(), screen, std::ptr::null_mut(), &mut size, dc, &mut source, 0, &mut blend, ULW_ALPHA);
+            let error = errhandlingapi::GetLastError();
+
+            wingdi::SelectObject(dc, old);
+            wingdi::DeleteObject(bitmap as HGDIOBJ);
+            wingdi::DeleteDC(dc);
+            winuser::ReleaseDC(0 as HWND, screen);
+
+            if updated == 0 {
+                return Err(Error::from(error));
+            }
+        }
+        Ok(())
+    }
+
+    fn pump(&mut self, timeout: Option<Duration>) -> Result<bool> {
+        unsafe {
+            self.tray.update().unwrap();
+            if self.running.load(Ordering::SeqCst) {
+                debug!("closing luna from tray");
+                winuser::PostQuitMessage(0);
+            }
+
+            // Rounded up, so waits under a millisecond don't spin.
+            let millis = match timeout {
+                Some(t) => t.as_nanos().div_ceil(1_000_000).min((INFINITE - 1) as u128) as DWORD,
+                None => INFINITE,
+            };
+            if winuser::MsgWaitForMultipleObjects(0, std::ptr::null(), FALSE, millis, QS_ALLINPUT) == WAIT_FAILED {
+                return Err(Error::from(format!("error on MsgWaitForMultipleObjects: {}", errhandlingapi::GetLastError())));
+            }
+
+            while winuser::PeekMessageW(&mut self.msg, 0 as HWND, 0, 0, PM_REMOVE) != 0 {
+                if self.msg.message == WM_QUIT {
+                    debug!("closing window");
+                    return Ok(false);
+                }
+
+                winuser::TranslateMessage(&self.msg);
+                winuser::DispatchMessageW(&self.msg);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn quit(&mut self) {
+        self.tray.quit();
+        unsafe {
+            winuser::PostQuitMessage(0);
+        }
+    }
+}
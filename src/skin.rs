@@ -0,0 +1,562 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::geometry::Rectangle;
+use crate::platform::{WindowOptions, ZPosition};
+use crate::measure::{self, calc};
+use crate::variables::Variables;
+use crate::error::{Error, Result};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(from = "[i32; 2]")]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl From<[i32; 2]> for Position {
+    fn from(v: [i32; 2]) -> Position {
+        Position { x: v[0], y: v[1] }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "[i32; 2]")]
+pub struct Size {
+    pub w: i32,
+    pub h: i32,
+}
+
+impl TryFrom<[i32; 2]> for Size {
+    type Error = String;
+
+    fn try_from(v: [i32; 2]) -> std::result::Result<Size, String> {
+        if v[0] <= 0 || v[1] <= 0 {
+            return Err(format!("size must be positive, got {}x{}", v[0], v[1]));
+        }
+        Ok(Size { w: v[0], h: v[1] })
+    }
+}
+
+/// A measure as written in the skin file. Everything besides `name` and
+/// `type` is left for the measure itself to interpret.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MeasureDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(flatten)]
+    pub options: toml::Table,
+}
+
+/// A meter as written in the skin file. Everything besides `name` and `type`
+/// is left for the meter itself to interpret.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MeterDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(flatten)]
+    pub options: toml::Table,
+}
+
+impl MeterDefinition {
+    /// Measures the meter is bound to, from either `measure = "Name"` or
+    /// `measures = ["A", "B"]`.
+    pub fn measure_names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        if let Some(name) = self.options.get("measure").and_then(|v| v.as_str()) {
+            names.push(name);
+        }
+        if let Some(list) = self.options.get("measures").and_then(|v| v.as_array()) {
+            names.extend(list.iter().filter_map(|v| v.as_str()));
+        }
+        names
+    }
+}
+
+/// A parsed skin file.
+///
+/// ```toml
+/// name = "Clock"
+/// position = [50, 50]
+/// size = [200, 80]
+/// opacity = 200
+/// click_through = false
+/// topmost = true
+/// update = 1000
+///
+/// [variables]
+/// Label = "CPU"
+///
+/// [[measure]]
+/// name = "Cpu"
+/// type = "cpu"
+///
+/// [[meter]]
+/// name = "Time"
+/// type = "text"
+/// measure = "Cpu"
+/// text = "#Label# %1"
+/// ```
+///
+/// `measures` and `meters` have their variables substituted, see
+/// `Variables`. The definitions as written are kept so the skin can be
+/// resolved again when a variable changes.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SkinDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub position: Position,
+    /// Window size, fitted to the meters when unset.
+    pub size: Option<Size>,
+    #[serde(default = "default_opacity")]
+    pub opacity: u8,
+    #[serde(default)]
+    pub click_through: bool,
+    #[serde(default)]
+    pub topmost: bool,
+    /// Milliseconds between measure updates.
+    #[serde(default = "default_update")]
+    pub update: u64,
+    #[serde(default, rename = "measure")]
+    pub measures: Vec<MeasureDefinition>,
+    #[serde(default, rename = "meter")]
+    pub meters: Vec<MeterDefinition>,
+    #[serde(default, rename = "variables")]
+    variable_table: toml::Table,
+    #[serde(skip)]
+    pub variables: Variables,
+    /// Directory that fonts, images and includes are relative to.
+    #[serde(skip)]
+    pub dir: PathBuf,
+    #[serde(skip)]
+    measure_templates: Vec<MeasureDefinition>,
+    #[serde(skip)]
+    meter_templates: Vec<MeterDefinition>,
+}
+
+/// Names of the measures and meters whose definitions changed after a
+/// variable was set.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Changed {
+    pub measures: Vec<String>,
+    pub meters: Vec<String>,
+}
+
+fn default_opacity() -> u8 {
+    255
+}
+
+fn default_update() -> u64 {
+    1000
+}
+
+impl SkinDefinition {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SkinDefinition> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        SkinDefinition::parse_in(&source, dir).map_err(|e| e.with_path(path))
+    }
+
+    /// Parses a skin whose includes and resources are relative to `dir`.
+    pub fn parse_in(source: &str, dir: &Path) -> Result<SkinDefinition> {
+        let mut skin: SkinDefinition = toml::from_str(source).map_err(|e| Error::from_toml(source, e))?;
+
+        skin.variables = Variables::from_table(&skin.variable_table, dir)
+            .map_err(|e| match e {
+                Error::Parse { .. } => e,
+                e => Error::parse(source, source.find("[variables]").unwrap_or(0), e.to_string()),
+            })?;
+        skin.dir = dir.to_path_buf();
+        skin.measure_templates = std::mem::take(&mut skin.measures);
+        skin.meter_templates = std::mem::take(&mut skin.meters);
+        skin.resolve();
+
+        skin.validate(source)?;
+        Ok(skin)
+    }
+
+    /// Where the window goes, sized to `content`, the extent of the meters,
+    /// unless the skin sets a size.
+    pub fn bounds(&self, content: (i32, i32)) -> Rectangle<i32> {
+        let (w, h) = match self.size {
+            Some(size) => (size.w, size.h),
+            None => (content.0.max(1), content.1.max(1)),
+        };
+        Rectangle::new(self.position.x, self.position.y, w, h)
+    }
+
+    pub fn update_interval(&self) -> Duration {
+        Duration::from_millis(self.update.max(1))
+    }
+
+    pub fn measure(&self, name: &str) -> Option<&MeasureDefinition> {
+        self.measures.iter().find(|m| m.name == name)
+    }
+
+    /// Sets a variable and resolves the measures and meters again, returning
+    /// the ones that changed. Fails, leaving the skin as it was, if the
+    /// resolved definitions don't pass the checks parsing does.
+    pub fn set_variable<V: Into<toml::Value>>(&mut self, name: &str, value: V) -> Result<Changed> {
+        let (skin, changed) = self.with_variable(name, value)?;
+        *self = skin;
+        Ok(changed)
+    }
+
+    /// The skin as `set_variable` would leave it, with the measures and
+    /// meters that changed.
+    pub fn with_variable<V: Into<toml::Value>>(&self, name: &str, value: V) -> Result<(SkinDefinition, Changed)> {
+        let mut skin = self.clone();
+        if !skin.variables.set(name, value) {
+            return Ok((skin, Changed::default()));
+        }
+        let changed = skin.resolve();
+        skin.check().map_err(|invalid| Error::from(format!("variable {}: {}", name, invalid.message)))?;
+        Ok((skin, changed))
+    }
+
+    fn resolve(&mut self) -> Changed {
+        let mut changed = Changed::default();
+
+        let measures: Vec<_> = self.measure_templates.iter()
+            .map(|m| MeasureDefinition { options: self.variables.substitute_table(&m.options), ..m.clone() })
+            .collect();
+        for (i, measure) in measures.iter().enumerate() {
+            if self.measures.get(i) != Some(measure) {
+                changed.measures.push(measure.name.clone());
+            }
+        }
+
+        let meters: Vec<_> = self.meter_templates.iter()
+            .map(|m| MeterDefinition { options: self.variables.substitute_table(&m.options), ..m.clone() })
+            .collect();
+        for (i, meter) in meters.iter().enumerate() {
+            if self.meters.get(i) != Some(meter) {
+                changed.meters.push(meter.name.clone());
+            }
+        }
+
+        self.measures = measures;
+        self.meters = meters;
+        changed
+    }
+
+    pub fn window_options(&self) -> WindowOptions {
+        WindowOptions {
+            alpha: self.opacity,
+            click_through: self.click_through,
+            z: if self.topmost { ZPosition::Topmost } else { ZPosition::Normal },
+            locked: false,
+        }
+    }
+}
+
+impl FromStr for SkinDefinition {
+    type Err = Error;
+
+    /// Parses a skin. Variable includes are relative to the working
+    /// directory, use `parse_in` to choose.
+    fn from_str(s: &str) -> Result<SkinDefinition> {
+        SkinDefinition::parse_in(s, Path::new(""))
+    }
+}
+
+// A problem `check` found, with where in the skin it is.
+struct Invalid {
+    message: String,
+    at: Spot,
+}
+
+enum Spot {
+    Start,
+    // A quoted value.
+    Value(String),
+    // Byte `offset` into the formula of the measure at `index`.
+    Formula { index: usize, offset: usize },
+}
+
+impl Invalid {
+    fn new<S: Into<String>>(at: Spot, message: S) -> Invalid {
+        Invalid { message: message.into(), at }
+    }
+}
+
+impl SkinDefinition {
+    // Runs `check` with errors pointing into the source `s`.
+    fn validate(&self, s: &str) -> Result<()> {
+        let invalid = match self.check() {
+            Ok(()) => return Ok(()),
+            Err(invalid) => invalid,
+        };
+        let formula = |defs: &[MeasureDefinition], index: usize| {
+            defs[index].options.get("formula").and_then(|v| v.as_str()).unwrap_or_default().to_string()
+        };
+        let (offset, message) = match invalid.at {
+            Spot::Start => (0, invalid.message),
+            Spot::Value(ref value) => (offset_of(s, value).unwrap_or(0), invalid.message),
+            Spot::Formula { index, offset } => {
+                // The source holds the formula as written, before variables
+                // were substituted. Positions in the string literal are exact
+                // unless it has escapes, which keep it from being found.
+                let (written, resolved) = (formula(&self.measure_templates, index), formula(&self.measures, index));
+                let start = offset_of(s, &written).map(|i| i + 1);
+                match start {
+                    Some(start) if written == resolved => (start + offset, invalid.message),
+                    _ => (start.unwrap_or(0), format!("{} at byte {} of formula {:?}", invalid.message, offset, resolved)),
+                }
+            },
+        };
+        Err(Error::parse(s, offset, message))
+    }
+
+    // Checks that can only be done once the whole skin is resolved, at
+    // parse time and again whenever a variable changes.
+    fn check(&self) -> std::result::Result<(), Invalid> {
+        let skin = self;
+        if skin.name.trim().is_empty() {
+            return Err(Invalid::new(Spot::Start, "skin name can not be empty"));
+        }
+
+        for (i, measure) in skin.measures.iter().enumerate() {
+            if skin.measures[..i].iter().any(|m| m.name == measure.name) {
+                return Err(Invalid::new(Spot::Value(measure.name.clone()), format!("duplicate measure {:?}", measure.name)));
+            }
+        }
+
+        for (i, meter) in skin.meters.iter().enumerate() {
+            if skin.meters[..i].iter().any(|m| m.name == meter.name) {
+                return Err(Invalid::new(Spot::Value(meter.name.clone()), format!("duplicate meter {:?}", meter.name)));
+            }
+        }
+
+        for (index, def) in skin.measures.iter().enumerate().filter(|(_, m)| m.kind == "calc") {
+            let formula = match def.options.get("formula").and_then(|v| v.as_str()) {
+                Some(f) => f,
+                None => continue,
+            };
+            let expr = calc::parse(formula).map_err(|e| Invalid::new(Spot::Formula { index, offset: e.offset }, e.message))?;
+            for (name, offset) in expr.reference_offsets() {
+                if skin.measure(name).is_none() {
+                    let message = format!("measure {:?} uses unknown measure {:?}", def.name, name);
+                    return Err(Invalid::new(Spot::Formula { index, offset }, message));
+                }
+            }
+        }
+
+        for meter in &skin.meters {
+            for name in meter.measure_names() {
+                if skin.measure(measure::parse_reference(name).0).is_none() {
+                    let message = format!("meter {:?} uses unknown measure {:?}", meter.name, name);
+                    return Err(Invalid::new(Spot::Value(name.to_string()), message));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Best effort location of a quoted `value` for errors found after parsing,
+// where toml no longer has spans.
+fn offset_of(source: &str, value: &str) -> Option<usize> {
+    let quoted = format!("\"{}\"", value);
+    source.match_indices(&quoted).map(|(i, _)| i).last()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_full_skin() {
+        let skin: SkinDefinition = r#"
+            name = "Clock"
+            position = [10, 20]
+            size = [200, 80]
+            opacity = 128
+            click_through = true
+            topmost = true
+
+            update = 500
+
+            [[measure]]
+            name = "Cpu"
+            type = "cpu"
+
+            [[meter]]
+            name = "Time"
+            type = "text"
+            text = "12:00"
+            measures = ["Cpu"]
+        "#.parse().unwrap();
+
+        assert_eq!(skin.name, "Clock");
+        assert_eq!(skin.bounds((5, 5)), Rectangle::new(10, 20, 200, 80));
+        assert_eq!(skin.window_options(), WindowOptions { alpha: 128, click_through: true, z: ZPosition::Topmost, locked: false });
+        assert_eq!(skin.meters.len(), 1);
+        assert_eq!(skin.meters[0].kind, "text");
+        assert_eq!(skin.meters[0].options["text"].as_str(), Some("12:00"));
+        assert_eq!(skin.meters[0].measure_names(), vec!["Cpu"]);
+        assert_eq!(skin.measure("Cpu").unwrap().kind, "cpu");
+        assert_eq!(skin.update_interval(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn defaults() {
+        let skin: SkinDefinition = "name = \"a\"\nsize = [1, 1]\n".parse().unwrap();
+
+        assert_eq!(skin.position, Position { x: 0, y: 0 });
+        assert_eq!(skin.bounds((0, 0)), Rectangle::new(0, 0, 1, 1));
+        let fitted: SkinDefinition = "name = \"a\"\nposition = [3, 4]\n".parse().unwrap();
+        assert_eq!(fitted.bounds((30, 20)), Rectangle::new(3, 4, 30, 20));
+        assert_eq!(skin.window_options(), WindowOptions::default());
+        assert!(skin.meters.is_empty());
+    }
+
+    #[test]
+    fn variables_and_runtime_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("shared.toml"), "[variables]\nSource = \"Load\"\nLeft = 1\n").unwrap();
+        fs::write(dir.path().join("skin.toml"), r##"
+            name = "Vars"
+            size = [10, 10]
+
+            [variables]
+            "@include" = "shared.toml"
+            Left = 5
+            Label = "Load"
+
+            [[measure]]
+            name = "Load"
+            type = "calc"
+            formula = "#Left# * 2"
+
+            [[meter]]
+            name = "Title"
+            type = "text"
+            text = "#Label#: %1"
+            x = "#Left#"
+            measure = "#Source#"
+
+            [[meter]]
+            name = "Static"
+            type = "text"
+            text = "fixed"
+        "##).unwrap();
+
+        let mut skin = SkinDefinition::load(dir.path().join("skin.toml")).unwrap();
+        assert_eq!(skin.meters[0].options["text"].as_str(), Some("Load: %1"));
+        assert_eq!(skin.meters[0].options["x"].as_integer(), Some(5));
+        assert_eq!(skin.meters[0].measure_names(), vec!["Load"]);
+        assert_eq!(skin.measure("Load").unwrap().options["formula"].as_str(), Some("5 * 2"));
+
+        let changed = skin.set_variable("Left", 7).unwrap();
+        assert_eq!(changed, Changed { measures: vec!["Load".to_string()], meters: vec!["Title".to_string()] });
+        assert_eq!(skin.meters[0].options["x"].as_integer(), Some(7));
+        assert_eq!(skin.set_variable("Left", 7).unwrap(), Changed::default());
+        assert_eq!(skin.set_variable("Label", "Busy").unwrap().meters, vec!["Title"]);
+
+        // Values parsing would have rejected leave the skin as it was.
+        let before = skin.clone();
+        assert!(skin.set_variable("Source", "Missing").is_err());
+        assert!(skin.set_variable("Left", "(").is_err());
+        assert!(skin.set_variable("Left", "Nowhere + 1").is_err());
+        assert_eq!(skin, before);
+        assert_eq!(skin.variables.get("Left"), Some(&toml::Value::Integer(7)));
+
+        let err = "name = \"a\"\nsize = [1, 1]\n[variables]\n\"@include\" = \"missing.toml\"\n".parse::<SkinDefinition>().unwrap_err();
+        match err {
+            Error::Parse { line, .. } => assert_eq!(line, 3),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn reports_error_position() {
+        let err = "name = \"a\"\nsize = [1, 1]\nopacity = 300\n".parse::<SkinDefinition>().unwrap_err();
+        match err {
+            Error::Parse { line, column, .. } => assert_eq!((line, column), (3, 11)),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let err = "name = \"a\"\nsize = [0, 1]\n".parse::<SkinDefinition>().unwrap_err();
+        match err {
+            Error::Parse { line, ref message, .. } => {
+                assert_eq!(line, 2);
+                assert!(message.contains("size must be positive"));
+            },
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let err = "name = \"a\"\nsize = [1, 1]\n[[meter]]\nname = \"m\"\ntype = \"text\"\nmeasure = \"Cpu\"\n".parse::<SkinDefinition>().unwrap_err();
+        match err {
+            Error::Parse { line, ref message, .. } => {
+                assert_eq!(line, 6);
+                assert!(message.contains("unknown measure \"Cpu\""));
+            },
+            e => panic!("unexpected error: {}", e),
+        }
+
+        for (kind, type_) in [("measure", "time"), ("meter", "text")] {
+            let source = format!("name = \"a\"\nsize = [1, 1]\n[[{0}]]\nname = \"x\"\ntype = \"{1}\"\n[[{0}]]\nname = \"x\"\ntype = \"{1}\"\n", kind, type_);
+            match source.parse::<SkinDefinition>().unwrap_err() {
+                Error::Parse { line, ref message, .. } => {
+                    assert_eq!(line, 7);
+                    assert_eq!(message, &format!("duplicate {} \"x\"", kind));
+                },
+                e => panic!("unexpected error: {}", e),
+            }
+        }
+
+        let err = "name = \"a\"\nsize = [1, 1]\n[[measure]]\nname = \"c\"\ntype = \"calc\"\nformula = \"1 + c * \"\n".parse::<SkinDefinition>().unwrap_err();
+        match err {
+            Error::Parse { line, column, ref message, .. } => {
+                assert_eq!((line, column), (6, 20));
+                assert!(message.contains("end of formula"));
+            },
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let err = "name = \"a\"\nsize = [1, 1]\n[[measure]]\nname = \"c\"\ntype = \"calc\"\nformula = \"1 + Cpu\"\n".parse::<SkinDefinition>().unwrap_err();
+        match err {
+            Error::Parse { line, column, ref message, .. } => {
+                assert_eq!((line, column), (6, 16));
+                assert!(message.contains("unknown measure \"Cpu\""));
+            },
+            e => panic!("unexpected error: {}", e),
+        }
+
+        // With variables, the position within the substituted formula is
+        // named instead.
+        let err = "name = \"a\"\nsize = [1, 1]\n[variables]\nX = 2\n[[measure]]\nname = \"c\"\ntype = \"calc\"\nformula = \"#X# + Cpu\"\n".parse::<SkinDefinition>().unwrap_err();
+        match err {
+            Error::Parse { line, column, ref message, .. } => {
+                assert_eq!((line, column), (8, 12));
+                assert!(message.ends_with("at byte 4 of formula \"2 + Cpu\""), "{}", message);
+            },
+            e => panic!("unexpected error: {}", e),
+        }
+
+        // Single quoted formulas aren't found in the source, so the position
+        // within them is named instead.
+        let err = "# éééééééééé\nname = \"a\"\nsize = [1, 1]\n[[measure]]\nname = \"c\"\ntype = \"calc\"\nformula = '1 + (('\n".parse::<SkinDefinition>().unwrap_err();
+        match err {
+            Error::Parse { line, column, ref message, .. } => {
+                assert_eq!((line, column), (1, 1));
+                assert!(message.ends_with("at byte 6 of formula \"1 + ((\""), "{}", message);
+            },
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let err = "name = \"a\"\nsize = [1, 1]\nopacty = 3\n".parse::<SkinDefinition>().unwrap_err();
+        assert!(err.with_path("a.toml").to_string().starts_with("a.toml:3:1: "));
+    }
+}
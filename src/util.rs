@@ -1,12 +1,32 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+#[cfg(windows)]
 use std:: {
     self,
     ffi::OsStr,
     os::windows::ffi::OsStrExt
 };
 
+#[cfg(windows)]
 pub fn to_os_string(s: &str) -> Vec<u16> {
     OsStr::new(s)
     .encode_wide()
     .chain(Some(0).into_iter())
     .collect::<Vec<_>>()
-}
\ No newline at end of file
+}
+
+/// luna's configuration directory: `$XDG_CONFIG_HOME/luna` (falling back to
+/// `~/.config/luna`), or `%APPDATA%\luna` on Windows.
+pub fn config_dir() -> Option<PathBuf> {
+    let config = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".config")))
+    };
+
+    config.map(|c| c.join("luna"))
+}
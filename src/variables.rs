@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// Key of a `[variables]` table naming files to read variables from, either
+/// one path or a list, relative to the file the table is in.
+pub const INCLUDE: &str = "@include";
+
+/// Values skins refer to as `#Name#` inside their string options.
+///
+/// ```toml
+/// [variables]
+/// "@include" = "../shared/colors.toml"
+/// Accent = "#ff8800"
+/// Left = 10
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Variables {
+    values: BTreeMap<String, toml::Value>,
+}
+
+// A variable file only holds a `[variables]` table.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VariableFile {
+    #[serde(default)]
+    variables: toml::Table,
+}
+
+impl Variables {
+    pub fn new() -> Variables {
+        Variables::default()
+    }
+
+    /// Reads a `[variables]` table whose includes are relative to `dir`.
+    /// Included files are read first, so the table's own entries win.
+    pub fn from_table(table: &toml::Table, dir: &Path) -> Result<Variables> {
+        let mut variables = Variables::new();
+        variables.read_table(table, dir, &mut Vec::new())?;
+        Ok(variables)
+    }
+
+    /// Reads a variable file, which has a `[variables]` table of its own and
+    /// may include further files.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Variables> {
+        let mut variables = Variables::new();
+        variables.read_file(path.as_ref(), &mut Vec::new())?;
+        Ok(variables)
+    }
+
+    fn read_table(&mut self, table: &toml::Table, dir: &Path, stack: &mut Vec<PathBuf>) -> Result<()> {
+        if let Some(include) = table.get(INCLUDE) {
+            let paths = match *include {
+                toml::Value::String(ref path) => vec![path.as_str()],
+                toml::Value::Array(ref list) => list.iter()
+                    .map(|v| v.as_str().ok_or_else(|| Error::from(format!("{} entries must be paths", INCLUDE))))
+                    .collect::<Result<_>>()?,
+                _ => return Err(Error::from(format!("{} must be a path or a list of paths", INCLUDE))),
+            };
+            for path in paths {
+                self.read_file(&dir.join(path), stack)?;
+            }
+        }
+
+        for (name, value) in table.iter().filter(|(name, _)| name.as_str() != INCLUDE) {
+            if !is_name(name) {
+                return Err(Error::from(format!("invalid variable name {:?}, use letters, digits and _", name)));
+            }
+            self.values.insert(name.clone(), value.clone());
+        }
+        Ok(())
+    }
+
+    fn read_file(&mut self, path: &Path, stack: &mut Vec<PathBuf>) -> Result<()> {
+        let canonical = path.canonicalize()
+            .map_err(|e| Error::from(format!("can not include {}: {}", path.display(), e)))?;
+        if stack.contains(&canonical) {
+            return Err(Error::from(format!("{} includes itself", path.display())));
+        }
+
+        let source = fs::read_to_string(path)?;
+        let file: VariableFile = toml::from_str(&source).map_err(|e| Error::from_toml(&source, e).with_path(path))?;
+
+        stack.push(canonical);
+        self.read_table(&file.variables, path.parent().unwrap_or_else(|| Path::new("")), stack)?;
+        stack.pop();
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&toml::Value> {
+        self.values.get(name)
+    }
+
+    /// Sets a variable, returning whether its value changed.
+    pub fn set<V: Into<toml::Value>>(&mut self, name: &str, value: V) -> bool {
+        let value = value.into();
+        if self.values.get(name) == Some(&value) {
+            return false;
+        }
+        self.values.insert(name.to_string(), value);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Replaces every `#Name#` of a known variable in `text`. Anything else,
+    /// colors like `#ff0000` included, is left as is.
+    pub fn substitute_str(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find('#') {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            match after.find('#').and_then(|end| self.lookup(&after[..end]).map(|v| (end, v))) {
+                Some((end, value)) => {
+                    out.push_str(&display(value));
+                    rest = &after[end + 1..];
+                },
+                None => {
+                    out.push('#');
+                    rest = after;
+                },
+            }
+        }
+
+        out.push_str(rest);
+        out
+    }
+
+    /// Substitutes variables in every string inside `value`. A string that is
+    /// nothing but `#Name#` takes the variable's value with its type, so
+    /// `x = "#Left#"` can stand in for a number.
+    pub fn substitute(&self, value: &toml::Value) -> toml::Value {
+        match *value {
+            toml::Value::String(ref s) => {
+                let whole = s.strip_prefix('#')
+                    .and_then(|s| s.strip_suffix('#'))
+                    .and_then(|name| self.lookup(name));
+                match whole {
+                    Some(v) => v.clone(),
+                    None => toml::Value::String(self.substitute_str(s)),
+                }
+            },
+            toml::Value::Array(ref list) => toml::Value::Array(list.iter().map(|v| self.substitute(v)).collect()),
+            toml::Value::Table(ref table) => toml::Value::Table(self.substitute_table(table)),
+            ref other => other.clone(),
+        }
+    }
+
+    pub fn substitute_table(&self, table: &toml::Table) -> toml::Table {
+        table.iter().map(|(k, v)| (k.clone(), self.substitute(v))).collect()
+    }
+
+    fn lookup(&self, name: &str) -> Option<&toml::Value> {
+        if is_name(name) { self.values.get(name) } else { None }
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn display(value: &toml::Value) -> String {
+    match *value {
+        toml::Value::String(ref s) => s.clone(),
+        ref other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> Variables {
+        let table: toml::Table = "Accent = \"#ff8800\"\nLeft = 10\nName = \"Cpu\"\nScale = 0.5".parse().unwrap();
+        Variables::from_table(&table, Path::new("")).unwrap()
+    }
+
+    #[test]
+    fn substitutes_strings() {
+        let vars = variables();
+        assert_eq!(vars.substitute_str("#Name# at #Scale#x"), "Cpu at 0.5x");
+        assert_eq!(vars.substitute_str("#000000 #Missing# # #Left#"), "#000000 #Missing# # 10");
+        assert_eq!(vars.substitute_str("##Left##"), "#10#");
+
+        let table: toml::Table = "x = \"#Left#\"\ncolor = \"#Accent#\"\nlist = [\"#Name#\", 1]".parse().unwrap();
+        let resolved = vars.substitute_table(&table);
+        assert_eq!(resolved["x"], toml::Value::Integer(10));
+        assert_eq!(resolved["color"].as_str(), Some("#ff8800"));
+        assert_eq!(resolved["list"][0].as_str(), Some("Cpu"));
+    }
+
+    #[test]
+    fn set_reports_changes() {
+        let mut vars = variables();
+        assert!(!vars.set("Left", 10));
+        assert!(vars.set("Left", 20));
+        assert!(vars.set("New", "value"));
+        assert_eq!(vars.substitute_str("#Left# #New#"), "20 value");
+        assert_eq!(vars.len(), 5);
+    }
+
+    #[test]
+    fn includes_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("shared")).unwrap();
+        fs::write(dir.path().join("shared/colors.toml"), "[variables]\n\"@include\" = \"base.toml\"\nAccent = \"red\"\nText = \"white\"\n").unwrap();
+        fs::write(dir.path().join("shared/base.toml"), "[variables]\nAccent = \"blue\"\nFont = \"Sans\"\n").unwrap();
+
+        let table: toml::Table = "\"@include\" = [\"shared/colors.toml\"]\nText = \"black\"".parse().unwrap();
+        let vars = Variables::from_table(&table, dir.path()).unwrap();
+        assert_eq!(vars.substitute_str("#Accent# #Text# #Font#"), "red black Sans");
+
+        fs::write(dir.path().join("shared/base.toml"), "[variables]\n\"@include\" = \"colors.toml\"\n").unwrap();
+        assert!(Variables::from_table(&table, dir.path()).unwrap_err().to_string().contains("includes itself"));
+
+        let missing: toml::Table = "\"@include\" = \"nope.toml\"".parse().unwrap();
+        assert!(Variables::from_table(&missing, dir.path()).is_err());
+        let bad: toml::Table = "\"a b\" = 1".parse().unwrap();
+        assert!(Variables::from_table(&bad, dir.path()).is_err());
+    }
+}
@@ -1,67 +1,152 @@
-use std::sync::mpsc::Sender;
-use std::convert::TryInto;
-
-use winapi::{
-    ctypes::c_int,
-    shared::{
-        minwindef::{DWORD, HINSTANCE, LPARAM, LRESULT, FALSE, UINT, WPARAM},
-        ntdef::LPCWSTR,
-        windef::{HBRUSH, HICON, HMENU, HWND, POINT, HDC, RECT, HCURSOR},
-    },
-    um::{
-        libloaderapi,
-        winuser::{
-            self, CW_USEDEFAULT, WM_DESTROY, WNDCLASSW, MSG, GWL_EXSTYLE, GWL_STYLE, LWA_COLORKEY, LWA_ALPHA, SB_BOTH,
-            PAINTSTRUCT, COLOR_WINDOW, WM_PAINT, VK_ESCAPE, WM_CHAR, WM_LBUTTONUP,
-            WS_OVERLAPPEDWINDOW, WS_OVERLAPPED, WS_CHILDWINDOW, WS_POPUP, WS_SYSMENU, WS_CAPTION, WS_BORDER, WS_HSCROLL, SW_SHOW, WS_DISABLED,
-            WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT, WS_EX_LAYERED, WS_EX_WINDOWEDGE,
-        },
-        errhandlingapi,
-        wingdi::RGB,
-    },
-};
+use std::time::{Duration, Instant};
 
 use crate::geometry::Rectangle;
 use crate::event::Callback;
-use crate::util::to_os_string;
+use crate::platform::{Platform, WindowId, WindowOptions};
+use crate::skin::SkinDefinition;
+use crate::library::SkinRef;
+use crate::measure::{self, MeasureSet};
+use crate::meter::{self, Common, MeterSet};
+use crate::canvas::{Canvas, Color};
 use crate::error::{Error, Result};
 
 pub struct Window {
-    pub hwnd: HWND,
-    pub hmenu: Option<HMENU>,
+    pub id: WindowId,
+    pub name: String,
+    pub bounds: Rectangle<i32>,
+    pub options: WindowOptions,
+    pub skin: Option<SkinDefinition>,
+    /// Library skin the window was activated from.
+    pub source: Option<SkinRef>,
+    pub measures: MeasureSet,
+    pub meters: MeterSet,
     pub callback: Option<Callback>,
+    canvas: Canvas,
+    // Set when the meters need to be drawn again before the next render.
+    dirty: bool,
 }
 
 impl Window {
-    pub fn new(class: WNDCLASSW, name: &str, bounds: Rectangle<c_int>, flags: DWORD, style: DWORD, hmenu: Option<HMENU>, callback: Option<Callback>) -> Result<Window> {
-        let hwnd;
-        unsafe {
-            hwnd = winuser::CreateWindowExW(
-                flags,
-                class.lpszClassName,
-                to_os_string(name).as_ptr(),
-                style,
-                bounds.x(),
-                bounds.y(),
-                bounds.w(),
-                bounds.h(),
-                0 as HWND,
-                0 as HMENU,
-                class.hInstance,
-                std::ptr::null_mut(),
-            );
-
-            if hwnd == std::ptr::null_mut() {
-                return Err(Error::from("Error creating window".to_string()));
-            }
-
-            //winuser::SetLayeredWindowAttributes(hwnd, 0, 100, LWA_ALPHA);
-        }
+    pub fn new<P: Platform>(platform: &mut P, name: &str, bounds: Rectangle<i32>, options: WindowOptions, callback: Option<Callback>) -> Result<Window> {
+        let id = platform.create_window(name, bounds, &options)?;
 
         Ok(Window {
-            hwnd,
-            hmenu,
+            id,
+            name: name.to_string(),
+            bounds,
+            options,
+            skin: None,
+            source: None,
+            measures: MeasureSet::new(Duration::from_secs(1)),
+            meters: MeterSet::new(),
             callback,
+            canvas: Canvas::new(bounds.w().max(0) as u32, bounds.h().max(0) as u32),
+            dirty: true,
         })
     }
-}
\ No newline at end of file
+
+    pub fn from_skin<P: Platform>(platform: &mut P, skin: SkinDefinition) -> Result<Window> {
+        let options = skin.window_options();
+        Window::from_skin_with(platform, skin, options)
+    }
+
+    /// Like `from_skin`, but with `options` in place of the skin's own.
+    pub fn from_skin_with<P: Platform>(platform: &mut P, skin: SkinDefinition, options: WindowOptions) -> Result<Window> {
+        let measures = MeasureSet::from_definitions(&skin.measures, skin.update_interval())?;
+        let mut meters = MeterSet::from_definitions(&skin.meters, &skin.dir)?;
+        let bounds = skin.bounds(meters.layout());
+
+        let mut window = Window::new(platform, &skin.name, bounds, options, None)?;
+        window.measures = measures;
+        window.meters = meters;
+        window.skin = Some(skin);
+        Ok(window)
+    }
+
+    /// Updates the window's measures if they are due and hands the new values
+    /// to the meters. Returns whether anything was updated.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        if !self.measures.tick(now) {
+            return false;
+        }
+        if self.meters.update(&self.measures) {
+            self.relayout();
+        }
+        true
+    }
+
+    /// Places the meters again, resizing the window if its skin has no fixed
+    /// size. The platform still has to be told about the new bounds.
+    pub fn relayout(&mut self) {
+        let content = self.meters.layout();
+        if let Some(ref skin) = self.skin {
+            let bounds = skin.bounds(content);
+            self.bounds = Rectangle::new(self.bounds.x(), self.bounds.y(), bounds.w(), bounds.h());
+        }
+        self.dirty = true;
+    }
+
+    /// Hands the window's content to the platform if it changed since it
+    /// was last presented.
+    pub fn present<P: Platform>(&mut self, platform: &mut P) -> Result<()> {
+        if !self.dirty && self.canvas.width() as i32 == self.bounds.w() && self.canvas.height() as i32 == self.bounds.h() {
+            return Ok(());
+        }
+        let id = self.id;
+        platform.present(id, self.render())
+    }
+
+    /// The window's content, drawn again if anything changed since the last
+    /// call.
+    pub fn render(&mut self) -> &Canvas {
+        let (w, h) = (self.bounds.w().max(0) as u32, self.bounds.h().max(0) as u32);
+        if self.canvas.width() != w || self.canvas.height() != h {
+            self.canvas = Canvas::new(w, h);
+            self.dirty = true;
+        }
+        if self.dirty {
+            self.canvas.clear(Color::TRANSPARENT);
+            self.meters.draw(&mut self.canvas);
+            self.dirty = false;
+        }
+        &self.canvas
+    }
+
+    /// Sets a skin variable. Measures and meters using it are rebuilt and the
+    /// names of the meters are returned. On failure the window and its skin
+    /// are left as they were.
+    pub fn set_variable<V: Into<toml::Value>>(&mut self, name: &str, value: V) -> Result<Vec<String>> {
+        let skin = match self.skin {
+            Some(ref skin) => skin,
+            None => return Err(Error::from(format!("{} has no skin to hold variables", self.name))),
+        };
+        let (skin, changed) = skin.with_variable(name, value)?;
+
+        // Everything is built before anything is swapped in.
+        let measures = changed.measures.iter()
+            .filter_map(|name| skin.measure(name))
+            .map(|def| Ok((def.name.clone(), measure::create(def)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let meters = changed.meters.iter()
+            .filter_map(|name| skin.meters.iter().find(|m| &m.name == name))
+            .map(|def| {
+                Common::from_definition(def)?;
+                Ok((def, meter::create(def, &skin.dir)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if !measures.is_empty() {
+            self.measures.replace_ordered(measures)?;
+        }
+
+        // With the common options checked above, replacing can't fail.
+        for (def, meter) in meters {
+            self.meters.replace(def, meter)?;
+        }
+        self.skin = Some(skin);
+        if !changed.meters.is_empty() {
+            self.meters.update(&self.measures);
+            self.relayout();
+        }
+        Ok(changed.meters)
+    }
+}
@@ -0,0 +1,275 @@
+//! Renders the skins under `tests/golden` with fixed measure values and
+//! compares them against the expected images next to them.
+//!
+//! Every case is a directory holding `skin.toml`, `expected.png` and an
+//! optional `case.toml`:
+//!
+//! ```toml
+//! # Channel difference, 0 to 255, up to which pixels count as equal.
+//! tolerance = 2
+//! # How many pixels may differ by more than that.
+//! max_diff_pixels = 0
+//!
+//! [values]
+//! Cpu = 42
+//! Memory = { number = 3.5, max = 8, string = "3.5 GB" }
+//! Network = [10, 40, 25, 80]
+//! ```
+//!
+//! The skin's own measures are not run, meters see the values above under
+//! the same names instead. Lists are taken one value per update, the window
+//! updating as often as the longest list is long, for meters that keep a
+//! history.
+//!
+//! On a mismatch the rendered image and a diff, differing pixels in red over
+//! a faded copy of the expected image, are written to `target/golden`. Run
+//! with `LUNA_BLESS=1` to take the rendered images as the new expected ones.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use luna::canvas::{Canvas, Color};
+use luna::measure::{Measure, MeasureSet, MeasureValue};
+use luna::platform::headless::Headless;
+use luna::skin::SkinDefinition;
+use luna::window::Window;
+use luna::error::{Error, Result};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Case {
+    #[serde(default = "default_tolerance")]
+    tolerance: u8,
+    #[serde(default)]
+    max_diff_pixels: usize,
+    #[serde(default)]
+    values: BTreeMap<String, Value>,
+}
+
+fn default_tolerance() -> u8 {
+    2
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Value {
+    Number(f64),
+    Series(Vec<f64>),
+    Full {
+        number: f64,
+        #[serde(default)]
+        min: f64,
+        #[serde(default = "default_max")]
+        max: f64,
+        string: Option<String>,
+        #[serde(default)]
+        sub: Vec<f64>,
+    },
+}
+
+fn default_max() -> f64 {
+    100.0
+}
+
+impl Value {
+    fn measure_values(&self) -> Vec<MeasureValue> {
+        match *self {
+            Value::Number(n) => vec![MeasureValue::new(n, 0.0, 100.0)],
+            Value::Series(ref numbers) => numbers.iter().map(|&n| MeasureValue::new(n, 0.0, 100.0)).collect(),
+            Value::Full { number, min, max, ref string, ref sub } => {
+                let mut value = MeasureValue::new(number, min, max)
+                    .with_sub(sub.iter().map(|&n| MeasureValue::new(n, min, max)).collect());
+                if let Some(s) = string {
+                    value = value.with_string(s.as_str());
+                }
+                vec![value]
+            },
+        }
+    }
+}
+
+/// Reports its values in turn, then keeps reporting the last one.
+struct Fixed(Vec<MeasureValue>, usize);
+
+impl Measure for Fixed {
+    fn update(&mut self) -> Result<MeasureValue> {
+        let value = self.0[self.1.min(self.0.len() - 1)].clone();
+        self.1 += 1;
+        Ok(value)
+    }
+}
+
+/// Straight, not premultiplied, RGBA8 pixels.
+#[derive(Debug, Clone, PartialEq)]
+struct Image {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Image {
+    fn from_canvas(canvas: &Canvas) -> Image {
+        let data = canvas.data()
+            .chunks_exact(4)
+            .flat_map(|p| {
+                let c = Color::from_premultiplied([p[0], p[1], p[2], p[3]]);
+                [c.r, c.g, c.b, c.a]
+            })
+            .collect();
+        Image { width: canvas.width(), height: canvas.height(), data }
+    }
+
+    fn load(path: &Path) -> Result<Image> {
+        let mut decoder = png::Decoder::new(BufReader::new(fs::File::open(path)?));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|e| Error::from(format!("{}: {}", path.display(), e)))?;
+        let mut buf = vec![0; reader.output_buffer_size().unwrap_or(0)];
+        let info = reader.next_frame(&mut buf).map_err(|e| Error::from(format!("{}: {}", path.display(), e)))?;
+        buf.truncate(info.buffer_size());
+
+        let data = match info.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::Rgb => buf.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => buf.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            png::ColorType::Grayscale => buf.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+            png::ColorType::Indexed => return Err(Error::from(format!("{}: palette was not expanded", path.display()))),
+        };
+        Ok(Image { width: info.width, height: info.height, data })
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut encoder = png::Encoder::new(fs::File::create(path)?, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()
+            .and_then(|mut w| w.write_image_data(&self.data))
+            .map_err(|e| Error::from(format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// Pixels differing by more than `tolerance` in any channel and an image
+/// marking them, `None` if the images match.
+fn compare(expected: &Image, actual: &Image, tolerance: u8) -> Option<(usize, Image)> {
+    if (expected.width, expected.height) != (actual.width, actual.height) {
+        return Some((usize::MAX, actual.clone()));
+    }
+
+    let mut differing = 0;
+    let mut diff = Vec::with_capacity(expected.data.len());
+    for (e, a) in expected.data.chunks_exact(4).zip(actual.data.chunks_exact(4)) {
+        if e.iter().zip(a).any(|(e, a)| e.abs_diff(*a) > tolerance) {
+            differing += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let grey = ((e[0] as u32 * 3 + e[1] as u32 * 6 + e[2] as u32) / 10) as u8;
+            diff.extend_from_slice(&[grey, grey, grey, e[3] / 4]);
+        }
+    }
+
+    if differing == 0 {
+        return None;
+    }
+    Some((differing, Image { width: expected.width, height: expected.height, data: diff }))
+}
+
+/// Renders `skin.toml` in `dir` the way a window would show it.
+fn render(dir: &Path, case: &Case) -> Result<Image> {
+    let mut skin = SkinDefinition::load(dir.join("skin.toml"))?;
+    skin.measures.clear();
+
+    let mut platform = Headless::new();
+    let mut window = Window::from_skin(&mut platform, skin)?;
+    let interval = Duration::from_secs(1);
+    window.measures = MeasureSet::new(interval);
+    let mut updates = 1;
+    for (name, value) in &case.values {
+        let values = value.measure_values();
+        if values.is_empty() {
+            return Err(Error::from(format!("{} has no values", name)));
+        }
+        updates = updates.max(values.len());
+        window.measures.insert(name, Box::new(Fixed(values, 0)));
+    }
+    let start = Instant::now();
+    for i in 0..updates {
+        window.tick(start + interval * i as u32);
+    }
+
+    Ok(Image::from_canvas(window.render()))
+}
+
+fn check(dir: &Path, out: &Path, bless: bool) -> std::result::Result<(), String> {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let case: Case = match fs::read_to_string(dir.join("case.toml")) {
+        Ok(source) => toml::from_str(&source).map_err(|e| format!("{}: case.toml: {}", name, e))?,
+        Err(_) => Case { tolerance: default_tolerance(), ..Case::default() },
+    };
+    let actual = render(dir, &case).map_err(|e| format!("{}: {}", name, e))?;
+
+    let expected_path = dir.join("expected.png");
+    if bless {
+        return actual.save(&expected_path).map_err(|e| e.to_string());
+    }
+    let expected = Image::load(&expected_path).map_err(|e| format!("{}: {}", name, e))?;
+
+    match compare(&expected, &actual, case.tolerance) {
+        Some((differing, diff)) if differing > case.max_diff_pixels => {
+            let actual_path = out.join(format!("{}.actual.png", name));
+            let diff_path = out.join(format!("{}.diff.png", name));
+            actual.save(&actual_path).map_err(|e| e.to_string())?;
+            diff.save(&diff_path).map_err(|e| e.to_string())?;
+            if differing == usize::MAX {
+                return Err(format!("{}: rendered {}x{}, expected {}x{}, see {}",
+                    name, actual.width, actual.height, expected.width, expected.height, actual_path.display()));
+            }
+            Err(format!("{}: {} pixels differ by more than {}, see {}", name, differing, case.tolerance, diff_path.display()))
+        },
+        _ => Ok(()),
+    }
+}
+
+fn cases() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut cases: Vec<_> = fs::read_dir(root).unwrap()
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.join("skin.toml").is_file())
+        .collect();
+    cases.sort();
+    cases
+}
+
+#[test]
+fn golden_images() {
+    let out = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden");
+    let bless = std::env::var_os("LUNA_BLESS").is_some();
+
+    let failures: Vec<_> = cases().iter().filter_map(|dir| check(dir, &out, bless).err()).collect();
+    assert!(failures.is_empty(), "golden images differ:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn compare_respects_tolerance() {
+    let image = |pixels: &[[u8; 4]]| Image {
+        width: pixels.len() as u32,
+        height: 1,
+        data: pixels.iter().flatten().copied().collect(),
+    };
+    let expected = image(&[[10, 20, 30, 255], [0, 0, 0, 0]]);
+
+    assert_eq!(compare(&expected, &image(&[[12, 18, 30, 255], [0, 0, 0, 2]]), 2), None);
+
+    let (differing, diff) = compare(&expected, &image(&[[13, 20, 30, 255], [0, 0, 0, 0]]), 2).unwrap();
+    assert_eq!(differing, 1);
+    assert_eq!(&diff.data[..4], &[255, 0, 0, 255]);
+    assert_eq!(&diff.data[4..], &[0, 0, 0, 0]);
+
+    assert_eq!(compare(&expected, &image(&[[10, 20, 30, 255]]), 255).unwrap().0, usize::MAX);
+}